@@ -16,10 +16,40 @@ use embedded_can::Id;
 use libc::{sa_family_t, sockaddr, sockaddr_can, sockaddr_storage, socklen_t};
 use nix::net::if_::if_nametoindex;
 use socket2::SockAddr;
-use std::{fmt, io, mem, mem::size_of, os::raw::c_int};
+use std::{
+    fmt, io, mem,
+    mem::{size_of, size_of_val},
+    os::raw::c_int,
+};
 
 pub use libc::{AF_CAN, CAN_RAW, PF_CAN};
 
+/// A specification of the interface for a CAN socket address.
+///
+/// This allows an interface to be given uniformly by name, by index, or as
+/// "any" interface. It can be created from a `&str` name or a `u32` index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddrSpec<'a> {
+    /// An interface by name, like "can0"
+    Name(&'a str),
+    /// An interface by kernel index
+    Index(u32),
+    /// All interfaces (index zero)
+    Any,
+}
+
+impl<'a> From<&'a str> for AddrSpec<'a> {
+    fn from(name: &'a str) -> Self {
+        Self::Name(name)
+    }
+}
+
+impl From<u32> for AddrSpec<'_> {
+    fn from(ifindex: u32) -> Self {
+        Self::Index(ifindex)
+    }
+}
+
 /// CAN socket address.
 ///
 /// This is the address for use with CAN sockets. It is simply an address to
@@ -72,6 +102,18 @@ impl CanAddr {
         Ok(Self::new(ifindex))
     }
 
+    /// Try to create an address from an interface name, index, or "any".
+    ///
+    /// This fails if an interface is given by name and the name can not be
+    /// found.
+    pub fn from_spec<'a>(spec: impl Into<AddrSpec<'a>>) -> io::Result<Self> {
+        match spec.into() {
+            AddrSpec::Name(ifname) => Self::from_iface(ifname),
+            AddrSpec::Index(ifindex) => Ok(Self::new(ifindex)),
+            AddrSpec::Any => Ok(Self::default()),
+        }
+    }
+
     /// Try to create a J1939 address from an interface name.
     pub fn from_iface_j1939(ifname: &str, name: u64, pgn: u32, jaddr: u8) -> io::Result<Self> {
         let mut addr = Self::from_iface(ifname)?;
@@ -93,6 +135,70 @@ impl CanAddr {
         Ok(addr)
     }
 
+    /// Creates a new CAN socket address for the specified interface by
+    /// index, with the protocol-specific address fields set from raw bytes.
+    ///
+    /// The bytes are copied into the start of the `can_addr` union of the
+    /// `sockaddr_can`, with the remainder zeroed. This allows addresses for
+    /// CAN protocols that aren't directly supported by this crate to be
+    /// built. It is an error if there are more bytes than fit in the union,
+    /// as given by `CanAddr::raw_len()`.
+    pub fn with_raw(ifindex: u32, addr_bytes: &[u8]) -> io::Result<Self> {
+        let mut addr = Self::new(ifindex);
+        addr.raw_addr_mut()
+            .get_mut(..addr_bytes.len())
+            .ok_or(io::ErrorKind::InvalidInput)?
+            .copy_from_slice(addr_bytes);
+        Ok(addr)
+    }
+
+    /// Gets the index of the interface for the address.
+    ///
+    /// An index of zero refers to all interfaces.
+    pub fn ifindex(&self) -> u32 {
+        self.0.can_ifindex as u32
+    }
+
+    /// Gets the J1939 NAME from the address.
+    ///
+    /// This is only meaningful for a J1939 address.
+    pub fn j1939_name(&self) -> u64 {
+        unsafe { self.0.can_addr.j1939.name }
+    }
+
+    /// Gets the J1939 Parameter Group Number (PGN) from the address.
+    ///
+    /// This is only meaningful for a J1939 address.
+    pub fn j1939_pgn(&self) -> u32 {
+        unsafe { self.0.can_addr.j1939.pgn }
+    }
+
+    /// Gets the J1939 node address from the address.
+    ///
+    /// This is only meaningful for a J1939 address.
+    pub fn j1939_addr(&self) -> u8 {
+        unsafe { self.0.can_addr.j1939.addr }
+    }
+
+    /// Gets the protocol-specific address fields as raw bytes.
+    ///
+    /// This is the `can_addr` union of the `sockaddr_can`, which holds the
+    /// ISO-TP IDs, the J1939 NAME, PGN, and address, or the fields of any
+    /// other protocol.
+    pub fn raw_addr(&self) -> &[u8] {
+        crate::as_bytes(&self.0.can_addr)
+    }
+
+    /// Gets the protocol-specific address fields as mutable raw bytes.
+    pub fn raw_addr_mut(&mut self) -> &mut [u8] {
+        crate::as_bytes_mut(&mut self.0.can_addr)
+    }
+
+    /// Gets the size of the protocol-specific address fields.
+    pub fn raw_len() -> usize {
+        size_of_val(&Self::default().0.can_addr)
+    }
+
     /// Gets the address of the structure as a `sockaddr_can` pointer.
     pub fn as_ptr(&self) -> *const sockaddr_can {
         &self.0
@@ -151,6 +257,14 @@ impl fmt::Debug for CanAddr {
     }
 }
 
+impl TryFrom<AddrSpec<'_>> for CanAddr {
+    type Error = io::Error;
+
+    fn try_from(spec: AddrSpec) -> io::Result<Self> {
+        Self::from_spec(spec)
+    }
+}
+
 impl From<sockaddr_can> for CanAddr {
     fn from(addr: sockaddr_can) -> Self {
         Self(addr)
@@ -176,16 +290,62 @@ impl AsRef<sockaddr_can> for CanAddr {
 mod tests {
     use super::*;
     use crate::as_bytes;
+    use embedded_can::StandardId;
 
     const IDX: u32 = 42;
 
     #[test]
     fn test_addr() {
-        let _addr = CanAddr::new(IDX);
+        let addr = CanAddr::new(IDX);
+        assert_eq!(IDX, addr.ifindex());
 
         assert_eq!(size_of::<sockaddr_can>(), CanAddr::len());
     }
 
+    #[test]
+    fn test_addr_j1939() {
+        let addr = CanAddr::new_j1939(IDX, 0x1234, 0xEF00, 0x20);
+        assert_eq!(IDX, addr.ifindex());
+        assert_eq!(0x1234, addr.j1939_name());
+        assert_eq!(0xEF00, addr.j1939_pgn());
+        assert_eq!(0x20, addr.j1939_addr());
+    }
+
+    #[test]
+    fn test_addr_raw() {
+        let addr = CanAddr::new_j1939(IDX, 0x1234, 0xEF00, 0x20);
+        assert_eq!(CanAddr::raw_len(), addr.raw_addr().len());
+
+        let raw = CanAddr::with_raw(IDX, addr.raw_addr()).unwrap();
+        assert_eq!(IDX, raw.ifindex());
+        assert_eq!(0x1234, raw.j1939_name());
+        assert_eq!(0xEF00, raw.j1939_pgn());
+        assert_eq!(0x20, raw.j1939_addr());
+        assert_eq!(addr.as_bytes(), raw.as_bytes());
+
+        let addr = CanAddr::with_raw(IDX, &[]).unwrap();
+        assert!(addr.raw_addr().iter().all(|&b| b == 0));
+
+        // The ISO-TP IDs are the first two words in the union
+        let addr = CanAddr::new_isotp(
+            IDX,
+            StandardId::new(0x7E8).unwrap(),
+            StandardId::new(0x7E0).unwrap(),
+        );
+        let mut bytes = [0u8; 8];
+        bytes[..4].copy_from_slice(&0x7E8u32.to_ne_bytes());
+        bytes[4..].copy_from_slice(&0x7E0u32.to_ne_bytes());
+        assert_eq!(&bytes, &addr.raw_addr()[..8]);
+
+        let mut raw = CanAddr::with_raw(IDX, &bytes).unwrap();
+        assert_eq!(addr.as_bytes(), raw.as_bytes());
+        raw.raw_addr_mut()[..4].copy_from_slice(&0x7E9u32.to_ne_bytes());
+        assert_eq!(0x7E9, unsafe { raw.as_ref().can_addr.tp.rx_id });
+
+        let too_long = vec![0u8; CanAddr::raw_len() + 1];
+        assert!(CanAddr::with_raw(IDX, &too_long).is_err());
+    }
+
     #[test]
     fn test_addr_to_sock_addr() {
         let addr = CanAddr::new(IDX);
@@ -195,4 +355,22 @@ mod tests {
         assert_eq!(CanAddr::len() as socklen_t, len);
         assert_eq!(as_bytes(&addr), &as_bytes(&sock_addr)[0..len as usize]);
     }
+
+    #[test]
+    fn test_addr_from_spec() {
+        let addr = CanAddr::from_spec(IDX).unwrap();
+        assert_eq!(IDX, addr.ifindex());
+
+        let addr = CanAddr::from_spec(AddrSpec::Index(IDX)).unwrap();
+        assert_eq!(IDX, addr.ifindex());
+
+        let addr = CanAddr::try_from(AddrSpec::Any).unwrap();
+        assert_eq!(0, addr.ifindex());
+
+        // The loopback interface should exist on any Linux system
+        let addr = CanAddr::from_spec("lo").unwrap();
+        assert_eq!(if_nametoindex("lo").unwrap(), addr.ifindex());
+
+        assert!(CanAddr::from_spec("no_such_iface").is_err());
+    }
 }
@@ -53,6 +53,15 @@ impl CanSocket {
     pub async fn read_frame(&self) -> io::Result<CanFrame> {
         self.0.read_with(|fd| fd.read_frame()).await
     }
+
+    /// Reads a frame and the index of the interface it arrived on,
+    /// asynchronously.
+    ///
+    /// There is no stream of these, as with `frames_with_iface()` on the
+    /// tokio socket, so call this in a loop to read them continuously.
+    pub async fn read_frame_with_iface(&self) -> io::Result<(CanFrame, u32)> {
+        self.0.read_with(|fd| fd.read_frame_with_iface()).await
+    }
 }
 
 impl SocketOptions for CanSocket {}
@@ -32,17 +32,21 @@
 use crate::{
     frame::Frame,
     id::{id_from_raw, FdFlags},
-    CanAnyFrame, CanDataFrame, CanFdFrame, CanFrame, CanRemoteFrame, ConstructionError,
+    CanAnyFrame, CanDataFrame, CanFdFrame, CanFdSocket, CanFrame, CanRemoteFrame, CanSocket,
+    ConstructionError, IoResult, Socket,
 };
-use embedded_can::Frame as EmbeddedFrame;
+use embedded_can::{ExtendedId, Frame as EmbeddedFrame, Id};
 use hex::FromHex;
 use itertools::Itertools;
 use libc::canid_t;
 use std::{
+    collections::HashMap,
     fmt,
     fs::File,
-    io::{self, BufRead, BufReader},
+    io::{self, BufRead, BufReader, Write},
     path::Path,
+    thread,
+    time::{Duration, Instant, SystemTime},
 };
 use thiserror::Error;
 
@@ -67,11 +71,45 @@ pub enum ParseError {
     /// Error creating the frame
     #[error(transparent)]
     ConstructionError(#[from] ConstructionError),
+    /// The buffer doesn't yet hold a complete record
+    #[error("Incomplete data")]
+    Incomplete,
+    /// A wire record of a kind that is not known. This holds the total
+    /// size of the record, so that it can be skipped.
+    #[error("Unknown frame kind in a record of {0} bytes")]
+    UnknownFrameKind(usize),
+}
+
+/// The direction of a recorded frame, relative to the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// The frame was received from the bus
+    Rx,
+    /// The frame was transmitted by the host
+    Tx,
+}
+
+impl Direction {
+    /// Parses a direction marker from a log line.
+    ///
+    /// This accepts the `T` / `R` markers emitted by `candump -x -L`, as
+    /// well as `TX` / `RX` annotations.
+    fn from_marker(s: &str) -> Option<Self> {
+        match s {
+            "R" | "RX" => Some(Direction::Rx),
+            "T" | "TX" => Some(Direction::Tx),
+            _ => None,
+        }
+    }
 }
 
 /// Recorded CAN frame.
 /// This corresponds to the information in a line from the candump log.
+///
+/// New fields may be added to the record, so it can't be built with a
+/// struct literal outside of this crate. Use `CanDumpRecord::new()`.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct CanDumpRecord {
     /// The timestamp
     pub t_us: u64,
@@ -79,17 +117,39 @@ pub struct CanDumpRecord {
     pub device: String,
     /// The parsed frame
     pub frame: CanAnyFrame,
+    /// The direction of the frame, if it was marked in the log
+    pub direction: Option<Direction>,
+}
+
+impl CanDumpRecord {
+    /// Creates a record of a frame on the device at the timestamp, in
+    /// microseconds, with no direction.
+    pub fn new<S: Into<String>>(t_us: u64, device: S, frame: CanAnyFrame) -> Self {
+        Self {
+            t_us,
+            device: device.into(),
+            frame,
+            direction: None,
+        }
+    }
+
+    /// Sets the direction of the frame.
+    pub fn with_direction(mut self, direction: Direction) -> Self {
+        self.direction = Some(direction);
+        self
+    }
 }
 
 impl fmt::Display for CanDumpRecord {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "({:.6}) {} {:03X}",
-            1.0e-6 * self.t_us as f64,
-            self.device,
-            self.frame.raw_id()
-        )?;
+        write!(f, "({:.6}) {} ", 1.0e-6 * self.t_us as f64, self.device)?;
+
+        // Like can-utils, extended IDs are always printed with 8 digits
+        if self.frame.is_extended() {
+            write!(f, "{:08X}", self.frame.raw_id())?;
+        } else {
+            write!(f, "{:03X}", self.frame.raw_id())?;
+        }
 
         use CanAnyFrame::*;
         match self.frame {
@@ -104,6 +164,12 @@ impl fmt::Display for CanDumpRecord {
                 let mut parts = frame.data().iter().map(|v| format!("{:02X}", v));
                 write!(f, "##{}", parts.join(""))
             }
+        }?;
+
+        match self.direction {
+            Some(Direction::Rx) => f.write_str(" R"),
+            Some(Direction::Tx) => f.write_str(" T"),
+            None => Ok(()),
         }
     }
 }
@@ -140,10 +206,24 @@ impl Reader<File> {
 impl<R: BufRead> Reader<R> {
     /// Returns an iterator over all records
     #[deprecated(since = "3.5.0", note = "Use `iter()`")]
-    pub fn records(&mut self) -> CanDumpRecords<R> {
+    pub fn records(&mut self) -> CanDumpRecords<'_, R> {
         CanDumpRecords { src: self }
     }
 
+    /// Reads all the remaining records and groups them by device name.
+    ///
+    /// This is useful to split a log that was captured from multiple
+    /// buses. The records for each device are kept in the order in which
+    /// they appear in the log.
+    pub fn records_by_device(self) -> Result<HashMap<String, Vec<CanDumpRecord>>, ParseError> {
+        let mut map: HashMap<String, Vec<CanDumpRecord>> = HashMap::new();
+        for rec in self {
+            let rec = rec?;
+            map.entry(rec.device.clone()).or_default().push(rec);
+        }
+        Ok(map)
+    }
+
     /// Advance state, returning next record.
     pub fn next_record(&mut self) -> Result<Option<CanDumpRecord>, ParseError> {
         self.buf.clear();
@@ -155,7 +235,7 @@ impl<R: BufRead> Reader<R> {
         }
 
         let line = self.buf[..nread].trim();
-        let mut field_iter = line.split(' ');
+        let mut field_iter = line.split_whitespace();
 
         // parse timestamp field
         let ts = field_iter.next().ok_or(ParseError::UnexpectedEndOfLine)?;
@@ -185,8 +265,24 @@ impl<R: BufRead> Reader<R> {
             .ok_or(ParseError::UnexpectedEndOfLine)?
             .to_string();
 
+        // The packet is the first field with a '#' separator. It might be
+        // preceded or followed by a direction marker, and followed by
+        // other fields, which are ignored.
+        let mut direction = None;
+        let mut can_raw = None;
+
+        for field in field_iter {
+            if field.contains('#') {
+                if can_raw.is_none() {
+                    can_raw = Some(field);
+                }
+            } else if direction.is_none() {
+                direction = Direction::from_marker(field);
+            }
+        }
+
         // parse packet
-        let can_raw = field_iter.next().ok_or(ParseError::UnexpectedEndOfLine)?;
+        let can_raw = can_raw.ok_or(ParseError::UnexpectedEndOfLine)?;
 
         let (can_id_str, mut can_data) = match can_raw.split_once('#') {
             Some((id, data)) => (id, data),
@@ -194,9 +290,15 @@ impl<R: BufRead> Reader<R> {
         };
 
         // Parse the CAN ID
+        // can-utils prints standard IDs with 3 hex digits, and extended IDs
+        // with 8, so an ID with more than 3 digits is extended, even if its
+        // value would fit in a standard ID.
         let can_id = canid_t::from_str_radix(can_id_str, 16)
             .ok()
-            .and_then(id_from_raw)
+            .and_then(|id| match can_id_str.len() {
+                n if n > 3 => ExtendedId::new(id).map(Id::from),
+                _ => id_from_raw(id),
+            })
             .ok_or(ParseError::InvalidCanFrame)?;
 
         // Determine frame type (FD or classical) and skip separator(s)
@@ -234,6 +336,7 @@ impl<R: BufRead> Reader<R> {
             t_us,
             device,
             frame,
+            direction,
         }))
     }
 }
@@ -270,6 +373,291 @@ impl<R: io::Read> Iterator for CanDumpRecords<'_, BufReader<R>> {
     }
 }
 
+/////////////////////////////////////////////////////////////////////////////
+// Writer
+
+/// A CAN log writer.
+///
+/// This writes records to a log in the candump format, one per line, so
+/// that they can be read back by a `Reader` or the can-utils tools.
+#[derive(Debug)]
+pub struct Writer<W: Write> {
+    // The underlying writer
+    wtr: W,
+}
+
+impl<W: Write> Writer<W> {
+    /// Creates a CAN log writer on top of an I/O writer.
+    pub fn from_writer(wtr: W) -> Self {
+        Self { wtr }
+    }
+
+    /// Writes a single record to the log.
+    pub fn write_record(&mut self, rec: &CanDumpRecord) -> io::Result<()> {
+        writeln!(self.wtr, "{}", rec)
+    }
+
+    /// Writes a frame to the log, with the time that it was received.
+    ///
+    /// The timestamp, such as one read from the socket with
+    /// `read_frame_with_timestamp()`, is written with microsecond
+    /// resolution. It is an error if it is before the Unix epoch.
+    pub fn write_timestamped(
+        &mut self,
+        device: &str,
+        frame: &CanFrame,
+        ts: SystemTime,
+    ) -> io::Result<()> {
+        let t_us = ts
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "timestamp before epoch"))?
+            .as_micros() as u64;
+
+        self.write_record(&CanDumpRecord::new(t_us, device, CanAnyFrame::from(*frame)))
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.wtr.flush()
+    }
+
+    /// Unwraps the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.wtr
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Playback
+
+/// A timed schedule to replay a set of recorded frames.
+///
+/// This takes the records parsed from a candump log and computes the
+/// delay between successive frames from their timestamps, so that the
+/// frames can be sent back out to a bus with the original timing.
+#[derive(Debug, Clone, Default)]
+pub struct Playback {
+    records: Vec<CanDumpRecord>,
+}
+
+impl Playback {
+    /// Creates a playback schedule from a collection of records.
+    pub fn new<I>(records: I) -> Self
+    where
+        I: IntoIterator<Item = CanDumpRecord>,
+    {
+        Self {
+            records: records.into_iter().collect(),
+        }
+    }
+
+    /// Creates a playback schedule by reading all the records from a
+    /// candump log reader.
+    pub fn from_reader<R: BufRead>(rdr: Reader<R>) -> Result<Self, ParseError> {
+        Ok(Self {
+            records: rdr.collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Gets the records in the schedule.
+    pub fn records(&self) -> &[CanDumpRecord] {
+        &self.records
+    }
+
+    /// Returns an iterator over the frames in the schedule along with the
+    /// delay since the previous frame.
+    ///
+    /// The first frame has no delay. If the timestamps go backward, the
+    /// delay is zero.
+    pub fn iter(&self) -> PlaybackIter<'_> {
+        PlaybackIter {
+            records: self.records.iter(),
+            prev_us: None,
+        }
+    }
+
+    /// Sends all the frames out on the socket, sleeping between them to
+    /// respect the original timing.
+    ///
+    /// The frames are scheduled relative to the start of playback, so the
+    /// time spent writing the frames doesn't accumulate as drift. Error
+    /// frames in the log can't be sent to a bus, and are skipped.
+    pub fn play(&self, sock: &CanFdSocket) -> IoResult<()> {
+        self.play_with_report(sock).map(|_| ())
+    }
+
+    /// Sends all the frames out on the socket, like `play()`, and reports
+    /// how closely the frames were sent to their scheduled times.
+    pub fn play_with_report(&self, sock: &CanFdSocket) -> IoResult<TimingReport> {
+        play_scheduled(self.iter().map(Ok), |frame| {
+            use CanAnyFrame::*;
+            match frame {
+                Normal(frame) => sock.write_frame(&frame)?,
+                Remote(frame) => sock.write_frame(&frame)?,
+                Fd(frame) => sock.write_frame(&frame)?,
+                Error(_) => return Ok(false),
+            }
+            Ok(true)
+        })
+    }
+}
+
+// Sends the frames, each one the delay after the one before it, using
+// the `send` function, which returns whether the frame was actually sent.
+//
+// The frames are scheduled relative to the start of playback, so the time
+// spent writing the frames doesn't accumulate as drift.
+fn play_scheduled<I, F>(frames: I, mut send: F) -> IoResult<TimingReport>
+where
+    I: IntoIterator<Item = IoResult<(Duration, CanAnyFrame)>>,
+    F: FnMut(CanAnyFrame) -> IoResult<bool>,
+{
+    let mut report = TimingReport::new();
+    let mut next = Instant::now();
+
+    for item in frames {
+        let (delay, frame) = item?;
+        next += delay;
+        if let Some(dur) = next.checked_duration_since(Instant::now()) {
+            thread::sleep(dur);
+        }
+
+        let now = Instant::now();
+        if send(frame)? {
+            report.record(next, now);
+        }
+    }
+    Ok(report)
+}
+
+/// Statistics on how accurately frames were sent at their scheduled
+/// times.
+///
+/// The jitter of each frame is the difference between the time that it
+/// was meant to be sent and the time that it actually was, whether early
+/// or late.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimingReport {
+    count: u32,
+    total: Duration,
+    max: Duration,
+}
+
+impl TimingReport {
+    /// Creates an empty report.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the intended and actual send times of a frame to the report.
+    pub fn record(&mut self, intended: Instant, actual: Instant) {
+        let jitter = match actual.checked_duration_since(intended) {
+            Some(late) => late,
+            None => intended.duration_since(actual),
+        };
+        self.count += 1;
+        self.total += jitter;
+        self.max = self.max.max(jitter);
+    }
+
+    /// Gets the number of frames in the report.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Gets the mean jitter of the frames, or zero if there were none.
+    pub fn mean_jitter(&self) -> Duration {
+        self.total.checked_div(self.count).unwrap_or_default()
+    }
+
+    /// Gets the largest jitter of any frame.
+    pub fn max_jitter(&self) -> Duration {
+        self.max
+    }
+}
+
+/// Replays a candump log file out to a socket.
+///
+/// See [`replay()`] for details.
+pub fn replay_file(path: &Path, sock: &CanSocket, speed: f64) -> io::Result<u64> {
+    replay(Reader::from_file(path)?, sock, speed)
+}
+
+/// Replays the frames from a candump log out to a socket, with the
+/// original timing between them scaled by `speed`.
+///
+/// A speed of 2.0 plays the log twice as fast as it was recorded, and 0.5
+/// at half speed. The records are streamed from the reader as they are
+/// sent, so a large log doesn't need to fit into memory.
+///
+/// Only classic data and remote frames can be sent on a `CanSocket`, so
+/// any FD or error frames in the log are skipped. Returns the number of
+/// frames that were sent.
+pub fn replay<R: BufRead>(rdr: Reader<R>, sock: &CanSocket, speed: f64) -> io::Result<u64> {
+    if !speed.is_finite() || speed <= 0.0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "replay speed must be positive",
+        ));
+    }
+
+    let mut prev_us = None;
+    let frames = rdr.map(|rec| {
+        let rec = rec.map_err(|err| match err {
+            ParseError::Io(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        })?;
+
+        let delay_us = prev_us.map_or(0, |prev| rec.t_us.saturating_sub(prev));
+        prev_us = Some(rec.t_us);
+        let delay = Duration::try_from_secs_f64(1.0e-6 * delay_us as f64 / speed)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "replay speed is too slow"))?;
+        Ok((delay, rec.frame))
+    });
+
+    let report = play_scheduled(frames, |frame| {
+        let frame = match frame {
+            CanAnyFrame::Normal(frame) => CanFrame::Data(frame),
+            CanAnyFrame::Remote(frame) => CanFrame::Remote(frame),
+            CanAnyFrame::Error(_) | CanAnyFrame::Fd(_) => return Ok(false),
+        };
+        sock.write_frame(&frame)?;
+        Ok(true)
+    })?;
+    Ok(report.count().into())
+}
+
+impl<'a> IntoIterator for &'a Playback {
+    type Item = (Duration, CanAnyFrame);
+    type IntoIter = PlaybackIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the frames of a playback schedule, with the delay
+/// since the previous frame.
+#[derive(Debug, Clone)]
+pub struct PlaybackIter<'a> {
+    records: std::slice::Iter<'a, CanDumpRecord>,
+    prev_us: Option<u64>,
+}
+
+impl Iterator for PlaybackIter<'_> {
+    type Item = (Duration, CanAnyFrame);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rec = self.records.next()?;
+        let delay_us = self
+            .prev_us
+            .map(|prev| rec.t_us.saturating_sub(prev))
+            .unwrap_or(0);
+        self.prev_us = Some(rec.t_us);
+        Some((Duration::from_micros(delay_us), rec.frame))
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
@@ -419,6 +807,24 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_extended_id_width() {
+        let input: &[u8] = b"(1234.567890) can0 00000123#11\n\
+                             (1234.567900) can0 123#22";
+
+        let mut reader = Reader::from_reader(input);
+
+        let rec = reader.next_record().unwrap().unwrap();
+        assert!(rec.frame.is_extended());
+        assert_eq!(0x123, rec.frame.raw_id());
+        assert_eq!(rec.to_string(), "(1234.567890) can0 00000123#11");
+
+        let rec = reader.next_record().unwrap().unwrap();
+        assert!(!rec.frame.is_extended());
+        assert_eq!(0x123, rec.frame.raw_id());
+        assert_eq!(rec.to_string(), "(1234.567900) can0 123#22");
+    }
+
     #[test]
     fn test_fd() {
         let input: &[u8] = b"(1469439874.299591) can1 080##0\n\
@@ -467,4 +873,156 @@ mod test {
 
         assert!(reader.next_record().unwrap().is_none());
     }
+
+    #[test]
+    fn test_records_by_device() {
+        let input: &[u8] = b"(1469439874.299591) can0 080#\n\
+                             (1469439874.299654) can1 701#7F\n\
+                             (1469439874.299700) can0 123#0102\n\
+                             (1469439874.299800) can1 702#R";
+
+        let reader = Reader::from_reader(input);
+        let map = reader.records_by_device().unwrap();
+
+        assert_eq!(map.len(), 2);
+
+        let can0 = &map["can0"];
+        assert_eq!(can0.len(), 2);
+        assert_eq!(can0[0].t_us, 1469439874299591);
+        assert_eq!(can0[0].frame.raw_id(), 0x080);
+        assert_eq!(can0[1].t_us, 1469439874299700);
+        assert_eq!(can0[1].frame.raw_id(), 0x123);
+
+        let can1 = &map["can1"];
+        assert_eq!(can1.len(), 2);
+        assert_eq!(can1[0].frame.raw_id(), 0x701);
+        assert!(matches!(can1[1].frame, CanAnyFrame::Remote(_)));
+        assert!(can1.iter().all(|rec| rec.device == "can1"));
+    }
+
+    #[test]
+    fn test_playback_delays() {
+        let input: &[u8] = b"(1469439874.299591) can0 080#\n\
+                             (1469439874.299654) can0 701#7F\n\
+                             (1469439874.300654) can1 123##1AABB\n\
+                             (1469439875.300654) can0 456#R";
+
+        let playback = Playback::from_reader(Reader::from_reader(input)).unwrap();
+        assert_eq!(playback.records().len(), 4);
+
+        let sched: Vec<_> = playback.iter().collect();
+        assert_eq!(sched.len(), 4);
+
+        assert_eq!(sched[0].0, Duration::ZERO);
+        assert_eq!(sched[0].1.raw_id(), 0x080);
+        assert_eq!(sched[1].0, Duration::from_micros(63));
+        assert_eq!(sched[1].1.raw_id(), 0x701);
+        assert_eq!(sched[2].0, Duration::from_millis(1));
+        assert!(matches!(sched[2].1, CanAnyFrame::Fd(_)));
+        assert_eq!(sched[3].0, Duration::from_secs(1));
+        assert!(matches!(sched[3].1, CanAnyFrame::Remote(_)));
+    }
+
+    #[test]
+    fn test_direction() {
+        let input: &[u8] = b"(1469439874.299591) can0 080#11 T\n\
+                             (1469439874.299654) can0 RX 701#7F\n\
+                             (1469439874.299700) can1 123#0102";
+
+        let mut reader = Reader::from_reader(input);
+
+        let rec = reader.next_record().unwrap().unwrap();
+        assert_eq!(rec.frame.raw_id(), 0x080);
+        assert_eq!(rec.direction, Some(Direction::Tx));
+        assert_eq!(rec.to_string(), "(1469439874.299591) can0 080#11 T");
+
+        let rec = reader.next_record().unwrap().unwrap();
+        assert_eq!(rec.frame.raw_id(), 0x701);
+        assert_eq!(rec.direction, Some(Direction::Rx));
+
+        let rec = reader.next_record().unwrap().unwrap();
+        assert_eq!(rec.direction, None);
+        assert_eq!(rec.to_string(), "(1469439874.299700) can1 123#0102");
+
+        let rec = CanDumpRecord::new(rec.t_us, "can1", rec.frame).with_direction(Direction::Rx);
+        assert_eq!(rec.to_string(), "(1469439874.299700) can1 123#0102 R");
+    }
+
+    #[test]
+    fn test_extra_whitespace_and_fields() {
+        let input: &[u8] = b"  (1469439874.299591)   can0\t 080#1122   extra  \n";
+
+        let mut reader = Reader::from_reader(input);
+        let rec = reader.next_record().unwrap().unwrap();
+
+        assert_eq!(rec.t_us, 1469439874299591);
+        assert_eq!(rec.device, "can0");
+        assert_eq!(rec.frame.raw_id(), 0x080);
+        assert_eq!(rec.frame.data(), &[0x11, 0x22]);
+        assert_eq!(rec.direction, None);
+    }
+
+    #[test]
+    fn test_timing_report() {
+        let mut report = TimingReport::new();
+        assert_eq!(0, report.count());
+        assert_eq!(Duration::ZERO, report.mean_jitter());
+
+        let ms = Duration::from_millis;
+        let start = Instant::now();
+        report.record(start, start + ms(1));
+        report.record(start + ms(10), start + ms(10));
+        report.record(start + ms(20), start + ms(15));
+
+        // Early and late sends both count as jitter
+        assert_eq!(3, report.count());
+        assert_eq!(ms(2), report.mean_jitter());
+        assert_eq!(ms(5), report.max_jitter());
+    }
+
+    #[test]
+    fn test_write_timestamped() {
+        let frame = CanFrame::from_raw_id(0x123, &[0x11, 0x22]).unwrap();
+        let ts = SystemTime::UNIX_EPOCH + Duration::new(1469439874, 299_591_750);
+
+        let mut writer = Writer::from_writer(Vec::new());
+        writer.write_timestamped("can0", &frame, ts).unwrap();
+        let log = writer.into_inner();
+        assert_eq!(&log[..], b"(1469439874.299591) can0 123#1122\n");
+
+        let mut reader = Reader::from_reader(&log[..]);
+        let rec = reader.next_record().unwrap().unwrap();
+        assert_eq!(1469439874299591, rec.t_us);
+        assert_eq!("can0", rec.device);
+        assert_eq!(0x123, rec.frame.raw_id());
+        assert_eq!(&[0x11, 0x22], rec.frame.data());
+
+        let mut writer = Writer::from_writer(Vec::new());
+        let ts = SystemTime::UNIX_EPOCH - Duration::from_secs(1);
+        assert!(writer.write_timestamped("can0", &frame, ts).is_err());
+    }
+
+    #[test]
+    fn test_replay_speed() {
+        use std::os::fd::OwnedFd;
+
+        // A datagram socket pair stands in for a CAN bus
+        let (tx, rx) =
+            socket2::Socket::pair(socket2::Domain::UNIX, socket2::Type::DGRAM, None).unwrap();
+        let sock = CanSocket::from(OwnedFd::from(tx));
+
+        let input: &[u8] = b"(1469439874.299591) can0 080#11\n\
+                             (1469439874.299654) can0 701#7F";
+
+        let n = replay(Reader::from_reader(input), &sock, 100.0).unwrap();
+        assert_eq!(2, n);
+        assert!(rx.recv(&mut [std::mem::MaybeUninit::uninit(); 16]).is_ok());
+
+        // Bad speeds are rejected, including one so slow that the delay
+        // overflows, rather than panicking
+        for speed in [0.0, -1.0, f64::NAN, 1.0e-300] {
+            let err = replay(Reader::from_reader(input), &sock, speed).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        }
+    }
 }
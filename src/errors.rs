@@ -38,7 +38,7 @@
 //! [linux/can/error.h](https://raw.githubusercontent.com/torvalds/linux/master/include/uapi/linux/can/error.h)
 //!
 
-use crate::{CanErrorFrame, EmbeddedFrame, Frame};
+use crate::{id::CAN_ERR_CNT, CanErrorFrame, EmbeddedFrame, Frame};
 use std::{convert::TryFrom, error, fmt, io};
 use thiserror::Error;
 
@@ -194,12 +194,33 @@ impl embedded_can::Error for CanError {
     }
 }
 
+impl From<CanError> for io::Error {
+    /// Converts a CAN error into an I/O error.
+    ///
+    /// The general type of error is mapped onto the closest `io::ErrorKind`,
+    /// and the original `CanError` is kept as the inner error, where it can
+    /// be recovered with `get_ref()` or `into_inner()`.
+    fn from(err: CanError) -> Self {
+        use CanError::*;
+        let kind = match err {
+            TransmitTimeout => io::ErrorKind::TimedOut,
+            NoAck => io::ErrorKind::BrokenPipe,
+            BusOff => io::ErrorKind::NotConnected,
+            Restarted => io::ErrorKind::ConnectionReset,
+            DecodingFailure(_) => io::ErrorKind::InvalidData,
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
 impl From<CanErrorFrame> for CanError {
     /// Constructs a CAN error from an error frame.
     fn from(frame: CanErrorFrame) -> Self {
         // Note that the CanErrorFrame is guaranteed to have the full 8-byte
-        // data payload.
-        match frame.error_bits() {
+        // data payload. The error counters, if present, are extra info that
+        // can accompany any of the error classes.
+        match frame.error_bits() & !CAN_ERR_CNT {
             0x0001 => CanError::TransmitTimeout,
             0x0002 => CanError::LostArbitration(frame.data()[0]),
             0x0004 => match ControllerProblem::try_from(frame.data()[1]) {
@@ -225,6 +246,192 @@ impl From<CanErrorFrame> for CanError {
     }
 }
 
+// ===== ErrorMask =====
+
+/// A builder for the error mask applied to a socket.
+///
+/// Each method ORs in the `CAN_ERR_*` class bit for one type of error,
+/// such that the final mask can be passed to
+/// [`set_error_filter()`](crate::SocketOptions::set_error_filter) with
+/// [`bits()`](ErrorMask::bits).
+///
+/// ```
+/// use socketcan::errors::ErrorMask;
+///
+/// let mask = ErrorMask::new().bus_off().no_ack().bits();
+/// assert_eq!(mask, 0x0060);
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ErrorMask(u32);
+
+impl ErrorMask {
+    /// Creates an empty error mask, which reports no errors.
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    /// Report TX timeouts (by the netdevice driver)
+    pub const fn tx_timeout(self) -> Self {
+        Self(self.0 | libc::CAN_ERR_TX_TIMEOUT)
+    }
+
+    /// Report lost arbitration
+    pub const fn lost_arbitration(self) -> Self {
+        Self(self.0 | libc::CAN_ERR_LOSTARB)
+    }
+
+    /// Report controller problems
+    pub const fn controller(self) -> Self {
+        Self(self.0 | libc::CAN_ERR_CRTL)
+    }
+
+    /// Report protocol violations
+    pub const fn protocol(self) -> Self {
+        Self(self.0 | libc::CAN_ERR_PROT)
+    }
+
+    /// Report transceiver status
+    pub const fn transceiver(self) -> Self {
+        Self(self.0 | libc::CAN_ERR_TRX)
+    }
+
+    /// Report when no ACK is received on transmission
+    pub const fn no_ack(self) -> Self {
+        Self(self.0 | libc::CAN_ERR_ACK)
+    }
+
+    /// Report the bus going off
+    pub const fn bus_off(self) -> Self {
+        Self(self.0 | libc::CAN_ERR_BUSOFF)
+    }
+
+    /// Report bus errors
+    pub const fn bus_error(self) -> Self {
+        Self(self.0 | libc::CAN_ERR_BUSERROR)
+    }
+
+    /// Report the controller restarting
+    pub const fn restarted(self) -> Self {
+        Self(self.0 | libc::CAN_ERR_RESTARTED)
+    }
+
+    /// Report all errors.
+    pub const fn all(self) -> Self {
+        Self(libc::CAN_ERR_MASK)
+    }
+
+    /// Clear the mask so that no errors are reported.
+    pub const fn none(self) -> Self {
+        Self(0)
+    }
+
+    /// Gets the raw bitmask, suitable to pass to `set_error_filter()`
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Creates a mask from the raw class bits, such as the error bits of
+    /// an error frame. Any bits outside of `CAN_ERR_MASK` are dropped.
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits & libc::CAN_ERR_MASK)
+    }
+
+    /// Adds a class of error to the mask.
+    pub const fn with(self, class: ErrorClass) -> Self {
+        Self(self.0 | class.bit())
+    }
+
+    /// Determines if the mask includes the class of error.
+    pub const fn contains(self, class: ErrorClass) -> bool {
+        self.0 & class.bit() != 0
+    }
+
+    /// Determines if an error frame reports any of the classes of error
+    /// in the mask.
+    ///
+    /// This is the same test that the kernel uses to decide whether to
+    /// deliver an error frame to a socket with this error mask.
+    pub fn matches(self, frame: &CanErrorFrame) -> bool {
+        self.0 & frame.error_bits() != 0
+    }
+
+    /// Gets an iterator over the classes of error in the mask.
+    pub fn classes(self) -> impl Iterator<Item = ErrorClass> {
+        ErrorClass::ALL
+            .into_iter()
+            .filter(move |class| self.contains(*class))
+    }
+}
+
+impl From<ErrorMask> for u32 {
+    fn from(mask: ErrorMask) -> Self {
+        mask.bits()
+    }
+}
+
+impl From<ErrorClass> for ErrorMask {
+    fn from(class: ErrorClass) -> Self {
+        Self::new().with(class)
+    }
+}
+
+impl FromIterator<ErrorClass> for ErrorMask {
+    fn from_iter<I: IntoIterator<Item = ErrorClass>>(iter: I) -> Self {
+        iter.into_iter().fold(Self::new(), Self::with)
+    }
+}
+
+// ===== ErrorClass =====
+
+/// A class of error that can be reported in an error frame.
+///
+/// Each class is one of the `CAN_ERR_*` bits in the ID word of an error
+/// frame. A single frame can report more than one class of error, so the
+/// classes of a frame are gathered into an [`ErrorMask`], as with
+/// [`CanErrorFrame::error_classes()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum ErrorClass {
+    /// TX timeout (by the netdevice driver)
+    TxTimeout = libc::CAN_ERR_TX_TIMEOUT,
+    /// Lost arbitration
+    LostArbitration = libc::CAN_ERR_LOSTARB,
+    /// Controller problems
+    Controller = libc::CAN_ERR_CRTL,
+    /// Protocol violations
+    Protocol = libc::CAN_ERR_PROT,
+    /// Transceiver status
+    Transceiver = libc::CAN_ERR_TRX,
+    /// No ACK received on transmission
+    NoAck = libc::CAN_ERR_ACK,
+    /// The bus went off
+    BusOff = libc::CAN_ERR_BUSOFF,
+    /// Bus error
+    BusError = libc::CAN_ERR_BUSERROR,
+    /// The controller restarted
+    Restarted = libc::CAN_ERR_RESTARTED,
+}
+
+impl ErrorClass {
+    /// All of the classes of error, in order of their bits.
+    pub const ALL: [ErrorClass; 9] = [
+        ErrorClass::TxTimeout,
+        ErrorClass::LostArbitration,
+        ErrorClass::Controller,
+        ErrorClass::Protocol,
+        ErrorClass::Transceiver,
+        ErrorClass::NoAck,
+        ErrorClass::BusOff,
+        ErrorClass::BusError,
+        ErrorClass::Restarted,
+    ];
+
+    /// Gets the `CAN_ERR_*` class bit for the error.
+    pub const fn bit(self) -> u32 {
+        self as u32
+    }
+}
+
 // ===== ControllerProblem =====
 
 /// Error status of the CAN controller.
@@ -610,7 +817,8 @@ impl fmt::Display for ConstructionError {
 
 #[cfg(test)]
 mod tests {
-    use crate::Error;
+    use super::{CanError, CanErrorDecodingFailure, ErrorClass, ErrorMask};
+    use crate::{id::ERR_MASK_ALL, CanErrorFrame, Error};
     use std::io;
 
     #[test]
@@ -633,4 +841,97 @@ mod tests {
             panic!("Wrong error conversion");
         }
     }
+
+    #[test]
+    fn test_error_mask() {
+        assert_eq!(ErrorMask::new().bits(), 0);
+        assert_eq!(ErrorMask::default(), ErrorMask::new());
+
+        assert_eq!(ErrorMask::new().tx_timeout().bits(), 0x0001);
+        assert_eq!(ErrorMask::new().lost_arbitration().bits(), 0x0002);
+        assert_eq!(ErrorMask::new().controller().bits(), 0x0004);
+        assert_eq!(ErrorMask::new().protocol().bits(), 0x0008);
+        assert_eq!(ErrorMask::new().transceiver().bits(), 0x0010);
+        assert_eq!(ErrorMask::new().no_ack().bits(), 0x0020);
+        assert_eq!(ErrorMask::new().bus_off().bits(), 0x0040);
+        assert_eq!(ErrorMask::new().bus_error().bits(), 0x0080);
+        assert_eq!(ErrorMask::new().restarted().bits(), 0x0100);
+
+        let mask = ErrorMask::new().bus_off().no_ack().controller();
+        assert_eq!(mask.bits(), 0x0064);
+        assert_eq!(u32::from(mask), 0x0064);
+
+        assert_eq!(ErrorMask::new().all().bits(), ERR_MASK_ALL);
+        assert_eq!(mask.all().none().bits(), 0);
+        assert_eq!(ErrorMask::new().all().bus_off().bits(), ERR_MASK_ALL);
+    }
+
+    #[test]
+    fn test_error_class() {
+        let mask = ErrorMask::new().bus_off().no_ack();
+        assert!(mask.contains(ErrorClass::BusOff));
+        assert!(mask.contains(ErrorClass::NoAck));
+        assert!(!mask.contains(ErrorClass::Controller));
+        assert_eq!(
+            vec![ErrorClass::NoAck, ErrorClass::BusOff],
+            mask.classes().collect::<Vec<_>>()
+        );
+
+        let classes = [ErrorClass::NoAck, ErrorClass::BusOff];
+        assert_eq!(mask, classes.into_iter().collect());
+        assert_eq!(mask, ErrorMask::from(ErrorClass::BusOff).no_ack());
+        let all: ErrorMask = ErrorClass::ALL.into_iter().collect();
+        assert_eq!(0x01FF, all.bits());
+        assert_eq!(9, ErrorMask::new().all().classes().count());
+        assert_eq!(0x0004, ErrorMask::from_bits(0xE000_0004).bits());
+    }
+
+    #[test]
+    fn test_error_class_filter() {
+        let mask = ErrorMask::new().bus_off().controller();
+
+        let frame = CanErrorFrame::new_error(libc::CAN_ERR_BUSOFF, &[]).unwrap();
+        assert!(mask.matches(&frame));
+
+        let frame = CanErrorFrame::new_error(libc::CAN_ERR_ACK, &[]).unwrap();
+        assert!(!mask.matches(&frame));
+        assert_eq!(
+            vec![ErrorClass::NoAck],
+            frame.error_classes().classes().collect::<Vec<_>>()
+        );
+
+        // A frame with several classes matches if any of them are in the mask
+        let frame =
+            CanErrorFrame::new_error(libc::CAN_ERR_ACK | libc::CAN_ERR_CRTL, &[0, 0x04]).unwrap();
+        assert!(mask.matches(&frame));
+        assert!(frame.error_classes().contains(ErrorClass::Controller));
+        assert!(frame.error_classes().contains(ErrorClass::NoAck));
+        assert!(!frame.error_classes().contains(ErrorClass::BusOff));
+    }
+
+    #[test]
+    fn test_can_error_to_io_error() {
+        use io::ErrorKind::*;
+
+        let cases = [
+            (CanError::TransmitTimeout, TimedOut),
+            (CanError::NoAck, BrokenPipe),
+            (CanError::BusOff, NotConnected),
+            (CanError::Restarted, ConnectionReset),
+            (
+                CanError::DecodingFailure(CanErrorDecodingFailure::NotAnError),
+                InvalidData,
+            ),
+            (CanError::BusError, Other),
+            (CanError::LostArbitration(3), Other),
+        ];
+
+        for (can_err, kind) in cases {
+            let err = io::Error::from(can_err);
+            assert_eq!(err.kind(), kind);
+
+            let inner = err.get_ref().unwrap().downcast_ref::<CanError>().unwrap();
+            assert_eq!(inner.to_string(), can_err.to_string());
+        }
+    }
 }
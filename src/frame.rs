@@ -30,11 +30,17 @@
 //!   [Error](https://doc.rust-lang.org/std/error/trait.Error.html) types.
 //!
 
-use crate::{id::CanId, CanError, ConstructionError};
+#[cfg(feature = "netlink")]
+use crate::nl::CanState;
+use crate::{
+    id::{CanId, CAN_ERR_CNT, CAN_MAX_RAW_DLC},
+    CanError, ConstructionError, ErrorMask,
+};
 use embedded_can::{ExtendedId, Frame as EmbeddedFrame, Id, StandardId};
 use itertools::Itertools;
 use libc::{can_frame, canfd_frame, canid_t};
 use std::{
+    cmp::Ordering,
     ffi::c_void,
     mem::size_of,
     {convert::TryFrom, fmt, matches, mem},
@@ -63,6 +69,62 @@ pub fn canfd_frame_default() -> canfd_frame {
     unsafe { mem::zeroed() }
 }
 
+// ===== CAN ID word =====
+
+/// The parts of a composite SocketCAN ID word.
+///
+/// This is the result of `decode_canid()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CanIdParts {
+    /// The numeric ID, masked to 11 or 29 bits.
+    /// For an error frame, these are the error class bits.
+    pub id: canid_t,
+    /// Whether this is a 29-bit extended ID
+    pub is_extended: bool,
+    /// Whether the remote request (RTR) flag is set
+    pub is_remote: bool,
+    /// Whether the error flag is set
+    pub is_error: bool,
+}
+
+impl CanIdParts {
+    /// Gets the ID as an `embedded_can` ID.
+    ///
+    /// This is `None` for an error frame, which doesn't have an ID.
+    pub fn to_id(&self) -> Option<Id> {
+        match (self.is_error, self.is_extended) {
+            (true, _) => None,
+            (false, true) => ExtendedId::new(self.id).map(Id::from),
+            (false, false) => StandardId::new(self.id as u16).map(Id::from),
+        }
+    }
+}
+
+/// Decodes a composite SocketCAN ID word into its parts.
+///
+/// The ID word, as found in the `can_id` field of the C frame structs,
+/// holds the numeric ID along with the extended, remote, and error flags.
+/// This splits it apart without needing to create a frame, which can be
+/// useful for raw frame data from sources other than a SocketCAN socket.
+pub fn decode_canid(canid: canid_t) -> CanIdParts {
+    let flags = IdFlags::from_bits_truncate(canid);
+    let is_extended = flags.contains(IdFlags::EFF);
+    let is_error = flags.contains(IdFlags::ERR);
+
+    let id = match (is_error, is_extended) {
+        (true, _) => canid & CAN_ERR_MASK,
+        (false, true) => canid & CAN_EFF_MASK,
+        (false, false) => canid & CAN_SFF_MASK,
+    };
+
+    CanIdParts {
+        id,
+        is_extended,
+        is_remote: flags.contains(IdFlags::RTR),
+        is_error,
+    }
+}
+
 // ===== AsPtr trait =====
 
 /// Trait to get a pointer to an inner type
@@ -104,6 +166,12 @@ pub trait AsPtr {
 
 // ===== Frame trait =====
 
+// Gets `N` bytes from the payload, starting at `offset`, if they're all
+// within the data.
+fn payload_bytes<const N: usize>(data: &[u8], offset: usize) -> Option<[u8; N]> {
+    data.get(offset..offset.checked_add(N)?)?.try_into().ok()
+}
+
 /// Shared trait for CAN frames
 #[allow(clippy::len_without_is_empty)]
 pub trait Frame: EmbeddedFrame {
@@ -116,6 +184,17 @@ pub trait Frame: EmbeddedFrame {
         Self::new(id_from_raw(id)?, data)
     }
 
+    /// Creates a frame using a raw, integer CAN ID, reporting why it
+    /// couldn't be created on failure.
+    ///
+    /// This is like `from_raw_id()`, but returns `IDTooLarge` if the `id`
+    /// is out of the 29-bit extended range, and `TooMuchData` if the data
+    /// doesn't fit in the frame.
+    fn try_from_raw_id(id: u32, data: &[u8]) -> Result<Self, ConstructionError> {
+        let id = id_from_raw(id).ok_or(ConstructionError::IDTooLarge)?;
+        Self::new(id, data).ok_or(ConstructionError::TooMuchData)
+    }
+
     /// Creates a remote frame using a raw, integer CAN ID.
     ///
     /// If the `id` is <= 0x7FF, it's assumed to be a standard ID, otherwise
@@ -172,11 +251,186 @@ pub trait Frame: EmbeddedFrame {
         self.id_flags().contains(IdFlags::ERR)
     }
 
+    /// Whether the frame uses a bit rate switch for the data phase.
+    ///
+    /// This is one of the FD flags, which are separate from the flags in
+    /// the ID word. It is always false for classic CAN frames.
+    fn is_brs(&self) -> bool {
+        false
+    }
+
+    /// Whether the transmitting node was error passive.
+    ///
+    /// This is the FD error state indicator flag, which is separate from
+    /// the flags in the ID word. It is always false for classic CAN frames.
+    fn is_esi(&self) -> bool {
+        false
+    }
+
     /// Sets the CAN ID for the frame
     fn set_id(&mut self, id: impl Into<Id>);
 
     /// Sets the data payload of the frame.
     fn set_data(&mut self, data: &[u8]) -> Result<(), ConstructionError>;
+
+    /// Gets the data of the frame as a mutable slice, to modify in place.
+    ///
+    /// This covers only the valid data of the frame, so it can't be used
+    /// to change the length; use `set_data()` for that. Remote and error
+    /// frames have no data that can be modified, so they give an empty
+    /// slice, as does the default implementation.
+    fn data_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+
+    /// Reads a byte from the payload at `offset`.
+    ///
+    /// This, and the other `read_` functions, return `None` if the value
+    /// would extend past the end of the data.
+    fn read_u8(&self, offset: usize) -> Option<u8> {
+        self.data().get(offset).copied()
+    }
+
+    /// Reads a signed byte from the payload at `offset`.
+    fn read_i8(&self, offset: usize) -> Option<i8> {
+        self.read_u8(offset).map(|b| b as i8)
+    }
+
+    /// Reads an unsigned 16-bit, big-endian integer from the payload at `offset`.
+    fn read_u16_be(&self, offset: usize) -> Option<u16> {
+        payload_bytes(self.data(), offset).map(u16::from_be_bytes)
+    }
+
+    /// Reads an unsigned 16-bit, little-endian integer from the payload at `offset`.
+    fn read_u16_le(&self, offset: usize) -> Option<u16> {
+        payload_bytes(self.data(), offset).map(u16::from_le_bytes)
+    }
+
+    /// Reads a signed 16-bit, big-endian integer from the payload at `offset`.
+    fn read_i16_be(&self, offset: usize) -> Option<i16> {
+        payload_bytes(self.data(), offset).map(i16::from_be_bytes)
+    }
+
+    /// Reads a signed 16-bit, little-endian integer from the payload at `offset`.
+    fn read_i16_le(&self, offset: usize) -> Option<i16> {
+        payload_bytes(self.data(), offset).map(i16::from_le_bytes)
+    }
+
+    /// Reads an unsigned 32-bit, big-endian integer from the payload at `offset`.
+    fn read_u32_be(&self, offset: usize) -> Option<u32> {
+        payload_bytes(self.data(), offset).map(u32::from_be_bytes)
+    }
+
+    /// Reads an unsigned 32-bit, little-endian integer from the payload at `offset`.
+    fn read_u32_le(&self, offset: usize) -> Option<u32> {
+        payload_bytes(self.data(), offset).map(u32::from_le_bytes)
+    }
+
+    /// Reads a signed 32-bit, big-endian integer from the payload at `offset`.
+    fn read_i32_be(&self, offset: usize) -> Option<i32> {
+        payload_bytes(self.data(), offset).map(i32::from_be_bytes)
+    }
+
+    /// Reads a signed 32-bit, little-endian integer from the payload at `offset`.
+    fn read_i32_le(&self, offset: usize) -> Option<i32> {
+        payload_bytes(self.data(), offset).map(i32::from_le_bytes)
+    }
+
+    /// Reads an unsigned 64-bit, big-endian integer from the payload at `offset`.
+    fn read_u64_be(&self, offset: usize) -> Option<u64> {
+        payload_bytes(self.data(), offset).map(u64::from_be_bytes)
+    }
+
+    /// Reads an unsigned 64-bit, little-endian integer from the payload at `offset`.
+    fn read_u64_le(&self, offset: usize) -> Option<u64> {
+        payload_bytes(self.data(), offset).map(u64::from_le_bytes)
+    }
+
+    /// Reads a signed 64-bit, big-endian integer from the payload at `offset`.
+    fn read_i64_be(&self, offset: usize) -> Option<i64> {
+        payload_bytes(self.data(), offset).map(i64::from_be_bytes)
+    }
+
+    /// Reads a signed 64-bit, little-endian integer from the payload at `offset`.
+    fn read_i64_le(&self, offset: usize) -> Option<i64> {
+        payload_bytes(self.data(), offset).map(i64::from_le_bytes)
+    }
+
+    /// Creates a copy of the frame with a new CAN ID.
+    ///
+    /// The payload is kept the same. A remote frame keeps its RTR flag and
+    /// DLC, and an error frame is copied unchanged, since its ID word holds
+    /// the error class.
+    fn with_id(&self, id: impl Into<Id>) -> Self
+    where
+        Self: Clone,
+    {
+        let mut frame = self.clone();
+        frame.set_id(id);
+        frame
+    }
+
+    /// Converts the frame into a `CanAnyFrame`.
+    ///
+    /// This allows generic code over any `Frame` to handle the frames
+    /// uniformly. The default implementation rebuilds the frame from its
+    /// parts, creating an FD frame if the payload is too large for a
+    /// classic frame or if any of the FD flags are set. The frame types in
+    /// this crate override it to convert directly to the matching variant.
+    fn into_any(self) -> CanAnyFrame {
+        let data = self.data();
+        let n = data.len();
+
+        if n > CAN_MAX_DLEN || self.is_brs() || self.is_esi() {
+            let mut frame = canfd_frame_default();
+            frame.can_id = self.id_word();
+            frame.len = n as u8;
+            frame.data[..n].copy_from_slice(data);
+            let mut frame = CanFdFrame::from(frame);
+            frame.set_brs(self.is_brs());
+            frame.set_esi(self.is_esi());
+            frame.into()
+        } else {
+            let mut frame = can_frame_default();
+            frame.can_id = self.id_word();
+            frame.can_dlc = self.dlc() as u8;
+            frame.data[..n].copy_from_slice(data);
+            frame.into()
+        }
+    }
+
+    /// Compares the bus priority of two frames, as decided by arbitration.
+    ///
+    /// The frame that would win arbitration, if both were sent at the same
+    /// time, is `Less`. Arbitration compares the bits of the frame header
+    /// on the wire, where a dominant 0 beats a recessive 1, so a lower ID
+    /// wins. For an extended ID, the top 11 bits are sent first, followed
+    /// by the recessive SRR and IDE bits, so a standard frame beats an
+    /// extended one with the same base ID. At the same ID, a data frame
+    /// beats a remote frame. Frames that are `Equal` can't be resolved by
+    /// arbitration, which is an error on a real bus.
+    fn arbitration_cmp(&self, other: &impl Frame) -> Ordering {
+        arbitration_bits(self).cmp(&arbitration_bits(other))
+    }
+}
+
+// Gets the arbitration field of the frame as it's sent on the bus, with
+// the first bit sent as the most significant one:
+//   Standard: [ID 11] [RTR] [IDE=0]
+//   Extended: [Base ID 11] [SRR=1] [IDE=1] [ID ext 18] [RTR]
+// The bits after the IDE of a standard frame are left as zero, since
+// arbitration is always decided by then against an extended frame.
+fn arbitration_bits<F: Frame>(frame: &F) -> u32 {
+    let rtr = u32::from(frame.is_remote_frame());
+    let id = frame.raw_id();
+
+    if frame.is_extended() {
+        let base = id >> 18;
+        let ext = id & 0x3FFFF;
+        (base << 21) | (1 << 20) | (1 << 19) | (ext << 1) | rtr
+    } else {
+        (id << 21) | (rtr << 20)
+    }
 }
 
 // ===== CanAnyFrame =====
@@ -228,6 +482,22 @@ impl Frame for CanAnyFrame {
         }
     }
 
+    /// Whether the frame uses a bit rate switch for the data phase.
+    fn is_brs(&self) -> bool {
+        match self {
+            CanAnyFrame::Fd(frame) => frame.is_brs(),
+            _ => false,
+        }
+    }
+
+    /// Whether the transmitting node was error passive.
+    fn is_esi(&self) -> bool {
+        match self {
+            CanAnyFrame::Fd(frame) => frame.is_esi(),
+            _ => false,
+        }
+    }
+
     /// Sets the CAN ID for the frame
     fn set_id(&mut self, id: impl Into<Id>) {
         use CanAnyFrame::*;
@@ -249,6 +519,21 @@ impl Frame for CanAnyFrame {
             Fd(frame) => frame.set_data(data),
         }
     }
+
+    fn data_mut(&mut self) -> &mut [u8] {
+        use CanAnyFrame::*;
+        match self {
+            Normal(frame) => frame.data_mut(),
+            Remote(frame) => frame.data_mut(),
+            Error(frame) => frame.data_mut(),
+            Fd(frame) => frame.data_mut(),
+        }
+    }
+
+    /// Converts the frame into a `CanAnyFrame`.
+    fn into_any(self) -> CanAnyFrame {
+        self
+    }
 }
 
 impl EmbeddedFrame for CanAnyFrame {
@@ -329,6 +614,23 @@ impl fmt::UpperHex for CanAnyFrame {
     }
 }
 
+impl fmt::Display for CanAnyFrame {
+    /// Formats the frame as hex, like `UpperHex`, prefixed with a label
+    /// for the kind of frame: `[DATA]`, `[RTR]`, `[ERR]`, or `[FD]`.
+    ///
+    /// For example, `[FD] 123##5 DE AD`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use CanAnyFrame::*;
+        let label = match self {
+            Normal(_) => "DATA",
+            Remote(_) => "RTR",
+            Error(_) => "ERR",
+            Fd(_) => "FD",
+        };
+        write!(f, "[{}] {:X}", label, self)
+    }
+}
+
 impl From<CanFrame> for CanAnyFrame {
     fn from(frame: CanFrame) -> Self {
         use CanFrame::*;
@@ -388,6 +690,121 @@ impl From<CanRawFrame> for CanAnyFrame {
     }
 }
 
+#[cfg(feature = "dump")]
+impl CanAnyFrame {
+    // The kind tags for the wire format
+    const WIRE_DATA: u8 = 0;
+    const WIRE_REMOTE: u8 = 1;
+    const WIRE_ERROR: u8 = 2;
+    const WIRE_FD: u8 = 3;
+
+    /// Encodes the frame into a compact, self-describing wire format,
+    /// suitable for tunneling over a byte stream like TCP.
+    ///
+    /// Each record is a 1-byte kind tag, a 1-byte length of the body, and
+    /// then the body, which always starts with the 32-bit ID word (with
+    /// the EFF/RTR/ERR flags) in big-endian order:
+    ///
+    /// ```text
+    /// Data:   [0][len][id:4][dlc:1][data:n]
+    /// Remote: [1][len][id:4][dlc:1]
+    /// Error:  [2][len][id:4][data:8]
+    /// FD:     [3][len][id:4][flags:1][data:n]
+    /// ```
+    ///
+    /// The DLC of a classic data frame is its `len8_dlc`, if it has one.
+    /// Since each record has its length, a reader can skip the records
+    /// with kinds that it doesn't know.
+    pub fn to_wire(&self) -> Vec<u8> {
+        let (tag, mut body) = match self {
+            CanAnyFrame::Normal(frame) => {
+                let dlc = frame.len8_dlc().unwrap_or(frame.dlc() as u8);
+                (Self::WIRE_DATA, [&[dlc], frame.data()].concat())
+            }
+            CanAnyFrame::Remote(frame) => (Self::WIRE_REMOTE, vec![frame.dlc() as u8]),
+            CanAnyFrame::Error(frame) => (Self::WIRE_ERROR, frame.data().to_vec()),
+            CanAnyFrame::Fd(frame) => (
+                Self::WIRE_FD,
+                [&[frame.flags().bits()], frame.data()].concat(),
+            ),
+        };
+        body.splice(0..0, self.id_word().to_be_bytes());
+
+        let mut buf = Vec::with_capacity(2 + body.len());
+        buf.push(tag);
+        buf.push(body.len() as u8);
+        buf.extend(body);
+        buf
+    }
+
+    /// Decodes a frame from the start of a buffer in the wire format
+    /// created by [`to_wire`][Self::to_wire].
+    ///
+    /// On success, this returns the frame and the number of bytes used
+    /// from the buffer, so that the next record can be read after it. If
+    /// the buffer doesn't hold a complete record, this returns
+    /// `ParseError::Incomplete`, and the read can be retried when more
+    /// data arrives. A record of an unknown kind gives
+    /// `ParseError::UnknownFrameKind` with the size of the record.
+    pub fn from_wire(buf: &[u8]) -> Result<(Self, usize), crate::dump::ParseError> {
+        use crate::dump::ParseError;
+
+        let (tag, len) = match buf {
+            [tag, len, ..] => (*tag, usize::from(*len)),
+            _ => return Err(ParseError::Incomplete),
+        };
+        let n = 2 + len;
+        let body = buf.get(2..n).ok_or(ParseError::Incomplete)?;
+
+        if tag > Self::WIRE_FD {
+            return Err(ParseError::UnknownFrameKind(n));
+        }
+        if body.len() < 4 {
+            return Err(ParseError::InvalidCanFrame);
+        }
+        let (id, rest) = body.split_at(4);
+        let id = canid_t::from_be_bytes([id[0], id[1], id[2], id[3]]);
+
+        let frame = match (tag, rest) {
+            (Self::WIRE_DATA, [dlc, data @ ..]) if id & (CAN_RTR_FLAG | CAN_ERR_FLAG) == 0 => {
+                let mut frame = CanDataFrame::init(id, data)?;
+                if usize::from(*dlc) > CAN_MAX_DLEN {
+                    frame.set_len8_dlc(*dlc)?;
+                } else if usize::from(*dlc) != data.len() {
+                    return Err(ParseError::InvalidCanFrame);
+                }
+                CanAnyFrame::Normal(frame)
+            }
+            (Self::WIRE_REMOTE, [dlc])
+                if id & CAN_RTR_FLAG != 0 && usize::from(*dlc) <= CAN_MAX_DLEN =>
+            {
+                let mut frame = can_frame_default();
+                frame.can_id = id;
+                frame.can_dlc = *dlc;
+                CanAnyFrame::from(frame)
+            }
+            (Self::WIRE_ERROR, data) if id & CAN_ERR_FLAG != 0 && data.len() == CAN_MAX_DLEN => {
+                let mut frame = can_frame_default();
+                frame.can_id = id;
+                frame.can_dlc = CAN_MAX_DLEN as u8;
+                frame.data.copy_from_slice(data);
+                CanAnyFrame::from(frame)
+            }
+            (Self::WIRE_FD, [flags, data @ ..]) => {
+                let flags = FdFlags::from_bits(*flags).ok_or(ParseError::InvalidCanFrame)?;
+                let frame = CanFdFrame::init(id, data, flags)?;
+                if frame.len() != data.len() {
+                    return Err(ParseError::InvalidCanFrame);
+                }
+                CanAnyFrame::Fd(frame)
+            }
+            (Self::WIRE_DATA..=Self::WIRE_FD, _) => return Err(ParseError::InvalidCanFrame),
+            _ => return Err(ParseError::UnknownFrameKind(n)),
+        };
+        Ok((frame, n))
+    }
+}
+
 impl AsPtr for CanAnyFrame {
     type Inner = c_void;
 
@@ -592,6 +1009,20 @@ impl Frame for CanFrame {
             Error(frame) => frame.set_data(data),
         }
     }
+
+    fn data_mut(&mut self) -> &mut [u8] {
+        use CanFrame::*;
+        match self {
+            Data(frame) => frame.data_mut(),
+            Remote(frame) => frame.data_mut(),
+            Error(frame) => frame.data_mut(),
+        }
+    }
+
+    /// Converts the frame into a `CanAnyFrame`.
+    fn into_any(self) -> CanAnyFrame {
+        self.into()
+    }
 }
 
 impl Default for CanFrame {
@@ -658,6 +1089,18 @@ impl AsRef<can_frame> for CanFrame {
     }
 }
 
+impl From<CanFrame> for can_frame {
+    /// Consumes the frame, returning the underlying C struct.
+    fn from(frame: CanFrame) -> Self {
+        use CanFrame::*;
+        match frame {
+            Data(frame) => frame.into(),
+            Remote(frame) => frame.into(),
+            Error(frame) => frame.into(),
+        }
+    }
+}
+
 impl TryFrom<CanFrame> for CanDataFrame {
     type Error = ConstructionError;
 
@@ -725,6 +1168,78 @@ impl CanDataFrame {
             _ => Err(ConstructionError::TooMuchData),
         }
     }
+
+    /// Creates a data frame with a DLC that may be larger than the data.
+    ///
+    /// The payload is zero-padded up to `dlc` bytes. This is useful for
+    /// devices that expect a fixed DLC, regardless of how many of the
+    /// bytes are meaningful. Returns `None` if `dlc` is greater than 8 or
+    /// less than the length of `data`.
+    pub fn with_len(id: impl Into<Id>, data: &[u8], dlc: usize) -> Option<Self> {
+        if dlc > CAN_MAX_DLEN || dlc < data.len() {
+            return None;
+        }
+        let mut frame = Self::init(id_to_canid_t(id), data).ok()?;
+        frame.0.can_dlc = dlc as u8;
+        Some(frame)
+    }
+
+    /// Gets the full 8-byte data array of the frame, regardless of the DLC.
+    ///
+    /// Bytes beyond the data length of the frame are normally zero.
+    pub fn data_full(&self) -> [u8; CAN_MAX_DLEN] {
+        self.0.data
+    }
+
+    /// Gets the raw DLC of a frame with 8 data bytes, when it is greater
+    /// than 8.
+    ///
+    /// With the `CC_LEN8_DLC` control mode enabled on the interface, a
+    /// classic frame with 8 data bytes can be sent or received with a DLC
+    /// of 9 to 15. This returns that DLC, or `None` if the frame has a
+    /// normal DLC.
+    pub fn len8_dlc(&self) -> Option<u8> {
+        match (usize::from(self.0.can_dlc), usize::from(self.0.len8_dlc)) {
+            (CAN_MAX_DLEN, dlc) if (CAN_MAX_DLEN + 1..=CAN_MAX_RAW_DLC).contains(&dlc) => {
+                Some(dlc as u8)
+            }
+            _ => None,
+        }
+    }
+
+    /// Sets the raw DLC for a frame with 8 data bytes.
+    ///
+    /// The DLC must be 9 to 15, or 8 to clear it, and the frame must have
+    /// 8 bytes of data. This only has an effect on an interface with the
+    /// `CC_LEN8_DLC` control mode enabled.
+    pub fn set_len8_dlc(&mut self, dlc: u8) -> Result<(), ConstructionError> {
+        let dlc = usize::from(dlc);
+        if usize::from(self.0.can_dlc) != CAN_MAX_DLEN
+            || !(CAN_MAX_DLEN..=CAN_MAX_RAW_DLC).contains(&dlc)
+        {
+            return Err(ConstructionError::TooMuchData);
+        }
+        self.0.len8_dlc = if dlc == CAN_MAX_DLEN { 0 } else { dlc as u8 };
+        Ok(())
+    }
+
+    /// Converts an FD frame into a classic data frame with the raw DLC.
+    ///
+    /// This is the counterpart to converting a classic frame with a
+    /// `len8_dlc` into an FD frame, which can't carry that DLC. The `dlc`
+    /// must match the data length of the FD frame, or be 9 to 15 for an
+    /// FD frame with 8 bytes of data.
+    pub fn try_from_fd_with_dlc(frame: CanFdFrame, dlc: u8) -> Result<Self, ConstructionError> {
+        let mut classic = Self::try_from(frame)?;
+        match usize::from(dlc) {
+            n if n == classic.len() => Ok(classic),
+            n if n > CAN_MAX_DLEN => {
+                classic.set_len8_dlc(dlc)?;
+                Ok(classic)
+            }
+            _ => Err(ConstructionError::TooMuchData),
+        }
+    }
 }
 
 impl AsPtr for CanDataFrame {
@@ -803,6 +1318,15 @@ impl Frame for CanDataFrame {
             _ => Err(ConstructionError::TooMuchData),
         }
     }
+
+    fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.0.data[..(self.0.can_dlc as usize)]
+    }
+
+    /// Converts the frame into a `CanAnyFrame`.
+    fn into_any(self) -> CanAnyFrame {
+        self.into()
+    }
 }
 
 impl Default for CanDataFrame {
@@ -847,6 +1371,12 @@ impl TryFrom<can_frame> for CanDataFrame {
 impl TryFrom<CanFdFrame> for CanDataFrame {
     type Error = ConstructionError;
 
+    /// Converts an FD frame with up to 8 bytes of data into a classic
+    /// data frame.
+    ///
+    /// An FD frame has no DLC beyond its data length, so the classic frame
+    /// never has a `len8_dlc`. To restore one, use
+    /// [`CanDataFrame::try_from_fd_with_dlc()`].
     fn try_from(frame: CanFdFrame) -> Result<Self, Self::Error> {
         match frame.len() {
             n if n > CAN_MAX_DLEN => Err(ConstructionError::TooMuchData),
@@ -861,6 +1391,13 @@ impl AsRef<can_frame> for CanDataFrame {
     }
 }
 
+impl From<CanDataFrame> for can_frame {
+    /// Consumes the frame, returning the underlying C struct.
+    fn from(frame: CanDataFrame) -> Self {
+        frame.0
+    }
+}
+
 // ===== CanRemoteFrame =====
 
 /// The classic CAN 2.0 remote request frame.
@@ -974,6 +1511,11 @@ impl Frame for CanRemoteFrame {
     fn set_data(&mut self, data: &[u8]) -> Result<(), ConstructionError> {
         self.set_dlc(data.len())
     }
+
+    /// Converts the frame into a `CanAnyFrame`.
+    fn into_any(self) -> CanAnyFrame {
+        self.into()
+    }
 }
 
 impl Default for CanRemoteFrame {
@@ -1022,6 +1564,13 @@ impl AsRef<can_frame> for CanRemoteFrame {
     }
 }
 
+impl From<CanRemoteFrame> for can_frame {
+    /// Consumes the frame, returning the underlying C struct.
+    fn from(frame: CanRemoteFrame) -> Self {
+        frame.0
+    }
+}
+
 // ===== CanErrorFrame =====
 
 /// A SocketCAN error frame.
@@ -1064,15 +1613,84 @@ impl CanErrorFrame {
         }
     }
 
+    /// Creates an error frame for the error, which also reports the bus
+    /// error counters of the controller.
+    ///
+    /// This sets the `CAN_ERR_CNT` class bit and places the TX and RX
+    /// error counters in bytes 6 and 7 of the data, as some drivers do. As
+    /// with the other constructors, this is meant mainly for mocks and
+    /// tests. The counters can be read back with `berr_counters()`.
+    pub fn with_berr_counters(base: CanError, tx: u8, rx: u8) -> Self {
+        let mut frame = Self::from(base);
+        frame.0.can_id |= CAN_ERR_CNT;
+        frame.0.data[6] = tx;
+        frame.0.data[7] = rx;
+        frame
+    }
+
+    /// Gets the TX and RX bus error counters from the frame, if the
+    /// driver reported them.
+    ///
+    /// These are in bytes 6 and 7 of the data, when the `CAN_ERR_CNT`
+    /// class bit is set in the ID word.
+    pub fn berr_counters(&self) -> Option<(u8, u8)> {
+        (self.error_bits() & CAN_ERR_CNT != 0).then_some((self.0.data[6], self.0.data[7]))
+    }
+
     /// Return the error bits from the ID word of the error frame.
     pub fn error_bits(&self) -> u32 {
         self.id_word() & CAN_ERR_MASK
     }
 
+    /// Gets the classes of error reported by the frame.
+    ///
+    /// This is the error bits from the ID word as an `ErrorMask`, so the
+    /// individual classes can be checked with `contains()` or iterated with
+    /// `classes()`.
+    pub fn error_classes(&self) -> ErrorMask {
+        ErrorMask::from_bits(self.error_bits())
+    }
+
     /// Converts this error frame into a `CanError`
     pub fn into_error(self) -> CanError {
         CanError::from(self)
     }
+
+    /// Makes a best guess at the state of the bus from the error frame.
+    ///
+    /// When error reporting is enabled, the kernel reports changes in the
+    /// bus state with error frames. This looks at the bus-off, restarted,
+    /// and controller status bits to determine the state that the
+    /// controller has entered, so that an application can track it without
+    /// having to query the interface over netlink.
+    ///
+    /// Returns `None` if the frame does not indicate a state change.
+    #[cfg(feature = "netlink")]
+    pub fn inferred_state(&self) -> Option<CanState> {
+        let bits = self.error_bits();
+
+        if bits & libc::CAN_ERR_BUSOFF != 0 {
+            return Some(CanState::BusOff);
+        }
+        if bits & libc::CAN_ERR_RESTARTED != 0 {
+            return Some(CanState::ErrorActive);
+        }
+        if bits & libc::CAN_ERR_CRTL == 0 {
+            return None;
+        }
+
+        let ctrl = self.0.data[1];
+        if ctrl & (libc::CAN_ERR_CRTL_RX_PASSIVE | libc::CAN_ERR_CRTL_TX_PASSIVE) as u8 != 0 {
+            Some(CanState::ErrorPassive)
+        } else if ctrl & (libc::CAN_ERR_CRTL_RX_WARNING | libc::CAN_ERR_CRTL_TX_WARNING) as u8 != 0
+        {
+            Some(CanState::ErrorWarning)
+        } else if ctrl & libc::CAN_ERR_CRTL_ACTIVE as u8 != 0 {
+            Some(CanState::ErrorActive)
+        } else {
+            None
+        }
+    }
 }
 
 impl AsPtr for CanErrorFrame {
@@ -1157,6 +1775,11 @@ impl Frame for CanErrorFrame {
     fn set_data(&mut self, _data: &[u8]) -> Result<(), ConstructionError> {
         Err(ConstructionError::WrongFrameType)
     }
+
+    /// Converts the frame into a `CanAnyFrame`.
+    fn into_any(self) -> CanAnyFrame {
+        self.into()
+    }
 }
 
 impl fmt::Debug for CanErrorFrame {
@@ -1228,6 +1851,13 @@ impl AsRef<can_frame> for CanErrorFrame {
     }
 }
 
+impl From<CanErrorFrame> for can_frame {
+    /// Consumes the frame, returning the underlying C struct.
+    fn from(frame: CanErrorFrame) -> Self {
+        frame.0
+    }
+}
+
 // ===== CanFdFrame =====
 
 // Valid extended data lengths
@@ -1247,6 +1877,13 @@ const VALID_EXT_DLENGTHS: [usize; 7] = [12, 16, 20, 24, 32, 48, 64];
 #[derive(Clone, Copy)]
 pub struct CanFdFrame(canfd_frame);
 
+// Compile-time check that an array will fit in an FD frame.
+struct FdDataLen<const N: usize>;
+
+impl<const N: usize> FdDataLen<N> {
+    const CHECK: () = assert!(N <= CANFD_MAX_DLEN, "too much data for an FD frame");
+}
+
 impl CanFdFrame {
     /// Create a new FD frame with FD flags
     pub fn with_flags(id: impl Into<Id>, data: &[u8], flags: FdFlags) -> Option<Self> {
@@ -1254,6 +1891,25 @@ impl CanFdFrame {
         Self::init(can_id, data, flags).ok()
     }
 
+    /// Create a new FD frame from a fixed-size array of data.
+    ///
+    /// The size of the array is checked at compile time, so an array that
+    /// is larger than the maximum FD payload of 64 bytes fails to build,
+    /// rather than failing at runtime:
+    ///
+    /// ```compile_fail
+    /// # use socketcan::{CanFdFrame, StandardId};
+    /// let id = StandardId::new(0x123).unwrap();
+    /// let frame = CanFdFrame::from_array(id, [0u8; 65]);
+    /// ```
+    ///
+    /// As with other FD frames, the data is padded with zeros up to the
+    /// next valid FD length.
+    pub fn from_array<const N: usize>(id: impl Into<Id>, data: [u8; N]) -> Option<Self> {
+        let () = FdDataLen::<N>::CHECK;
+        Self::init(id_to_canid_t(id), &data, FdFlags::empty()).ok()
+    }
+
     /// Initialize an FD frame from the raw components.
     pub(crate) fn init(
         can_id: u32,
@@ -1273,6 +1929,14 @@ impl CanFdFrame {
         }
     }
 
+    /// Gets the full 64-byte data array of the frame, regardless of the
+    /// data length.
+    ///
+    /// Bytes beyond the data length of the frame are normally zero.
+    pub fn data_full(&self) -> [u8; CANFD_MAX_DLEN] {
+        self.0.data
+    }
+
     /// Gets the flags for the FD frame.
     ///
     /// These are the bits from the separate FD frame flags, not the flags
@@ -1281,6 +1945,15 @@ impl CanFdFrame {
         FdFlags::from_bits_truncate(self.0.flags)
     }
 
+    /// Gets the FD flags (BRS, ESI, FDF) for the frame.
+    ///
+    /// This is the same as `flags()`, but named to make it clear that
+    /// these are not the EFF/RTR/ERR flags in the ID word, which are
+    /// returned by `id_flags()`.
+    pub fn fd_flags(&self) -> FdFlags {
+        self.flags()
+    }
+
     /// Whether the frame uses a bit rate switch (second bit rate for
     /// payload data).
     pub fn is_brs(&self) -> bool {
@@ -1310,6 +1983,28 @@ impl CanFdFrame {
         }
     }
 
+    /// Checks that the frame is well-formed.
+    ///
+    /// A frame built with the constructors is always valid, but one that
+    /// was converted from a raw `canfd_frame`, such as from data read off
+    /// the wire, might not be. This checks that the length is one of the
+    /// valid FD data lengths, so that the data slice covers exactly the
+    /// payload, and that the flags are consistent with an FD frame: only
+    /// the known FD flags are set, and the ID doesn't mark it as a remote
+    /// frame, which FD doesn't support.
+    ///
+    /// A bad length is reported as `TooMuchData`, and bad flags as
+    /// `WrongFrameType`.
+    pub fn validate(&self) -> Result<(), ConstructionError> {
+        if !Self::is_valid_data_len(usize::from(self.0.len)) {
+            return Err(ConstructionError::TooMuchData);
+        }
+        if FdFlags::from_bits(self.0.flags).is_none() || self.id_flags().contains(IdFlags::RTR) {
+            return Err(ConstructionError::WrongFrameType);
+        }
+        Ok(())
+    }
+
     /// Checks whether a given length is a valid CANFD data length.
     ///
     /// Valid values are `0` - `8`, `12`, `16`, `20`, `24`, `32`, `48` or `64`.
@@ -1413,6 +2108,16 @@ impl Frame for CanFdFrame {
         self.0.len as usize
     }
 
+    /// Whether the frame uses a bit rate switch for the data phase.
+    fn is_brs(&self) -> bool {
+        self.flags().contains(FdFlags::BRS)
+    }
+
+    /// Whether the transmitting node was error passive.
+    fn is_esi(&self) -> bool {
+        self.flags().contains(FdFlags::ESI)
+    }
+
     /// Sets the CAN ID for the frame
     fn set_id(&mut self, id: impl Into<Id>) {
         self.0.can_id = id_to_canid_t(id);
@@ -1430,6 +2135,15 @@ impl Frame for CanFdFrame {
             _ => Err(ConstructionError::TooMuchData),
         }
     }
+
+    fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.0.data[..(self.0.len as usize)]
+    }
+
+    /// Converts the frame into a `CanAnyFrame`.
+    fn into_any(self) -> CanAnyFrame {
+        self.into()
+    }
 }
 
 impl Default for CanFdFrame {
@@ -1459,6 +2173,11 @@ impl fmt::UpperHex for CanFdFrame {
 }
 
 impl From<CanDataFrame> for CanFdFrame {
+    /// Converts a classic data frame into an FD frame.
+    ///
+    /// An FD frame has no field for a `len8_dlc`, so it is dropped. Get it
+    /// from the classic frame first, with `len8_dlc()`, to restore it on
+    /// the way back with [`CanDataFrame::try_from_fd_with_dlc()`].
     fn from(frame: CanDataFrame) -> Self {
         let n = frame.len();
 
@@ -1471,6 +2190,24 @@ impl From<CanDataFrame> for CanFdFrame {
     }
 }
 
+impl From<can_frame> for CanFdFrame {
+    /// Converts a raw classic frame into an FD frame.
+    ///
+    /// The DLC becomes the data length of the FD frame, limited to the 8
+    /// bytes that a classic frame can carry. As with a `CanDataFrame`, any
+    /// `len8_dlc` is dropped.
+    fn from(frame: can_frame) -> Self {
+        let n = usize::from(frame.can_dlc).min(CAN_MAX_DLEN);
+
+        let mut fdframe = canfd_frame_default();
+        fdframe.can_id = frame.can_id;
+        fdframe.flags = CANFD_FDF as u8;
+        fdframe.len = n as u8;
+        fdframe.data[..n].copy_from_slice(&frame.data[..n]);
+        Self(fdframe)
+    }
+}
+
 impl From<canfd_frame> for CanFdFrame {
     fn from(mut frame: canfd_frame) -> Self {
         frame.flags |= CANFD_FDF as u8;
@@ -1484,6 +2221,104 @@ impl AsRef<canfd_frame> for CanFdFrame {
     }
 }
 
+impl From<CanFdFrame> for canfd_frame {
+    /// Consumes the frame, returning the underlying C struct.
+    fn from(frame: CanFdFrame) -> Self {
+        frame.0
+    }
+}
+
+// ===== Ascii =====
+
+/// A display wrapper to show a frame in the style of `candump -a`.
+///
+/// This shows the ID, the data length, and the payload as both hex bytes
+/// and ASCII characters, with non-printable bytes shown as `.`, like:
+///
+/// ```text
+/// 123 [3] 41 42 43 'ABC'
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Ascii<'a, F: Frame>(pub &'a F);
+
+impl<F: Frame> fmt::Display for Ascii<'_, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let frame = self.0;
+        if frame.is_extended() {
+            write!(f, "{:08X}", frame.raw_id())?;
+        } else {
+            write!(f, "{:03X}", frame.raw_id())?;
+        }
+
+        let data = frame.data();
+        write!(f, " [{}]", data.len())?;
+
+        if !data.is_empty() {
+            for b in data {
+                write!(f, " {:02X}", b)?;
+            }
+            let ascii: String = data
+                .iter()
+                .map(|&b| match b {
+                    0x20..=0x7E => b as char,
+                    _ => '.',
+                })
+                .collect();
+            write!(f, " '{}'", ascii)?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats a frame in the style of `candump -a`, showing the payload as
+/// both hex and ASCII.
+///
+/// See [`Ascii`].
+pub fn format_candump_ascii(frame: &impl Frame) -> String {
+    Ascii(frame).to_string()
+}
+
+/// Splits a payload into a sequence of frames, all with the same ID.
+///
+/// The data is split into chunks of 8 bytes for classic data frames, or
+/// 64 bytes for FD frames when `fd` is set. The last frame holds whatever
+/// is left over. As with any FD frame, a final chunk that isn't a valid FD
+/// length is padded with zeros up to the next valid length. An empty
+/// payload gives no frames.
+///
+/// This does no sequencing, framing, or flow control, so the receiver has
+/// to know how to put the data back together. For a reliable transfer of
+/// large payloads, use an ISO-TP socket instead (with the `isotp` feature).
+pub fn chunk_payload(id: Id, data: &[u8], fd: bool) -> Vec<CanAnyFrame> {
+    let can_id = id_to_canid_t(id);
+    // Each chunk fits in its frame, so construction can't fail
+    if fd {
+        data.chunks(CANFD_MAX_DLEN)
+            .map(|chunk| {
+                CanFdFrame::init(can_id, chunk, FdFlags::empty())
+                    .unwrap()
+                    .into()
+            })
+            .collect()
+    } else {
+        data.chunks(CAN_MAX_DLEN)
+            .map(|chunk| CanDataFrame::init(can_id, chunk).unwrap().into())
+            .collect()
+    }
+}
+
+/// Compares a timestamped frame, as read from a socket, against an
+/// expected frame, ignoring the timestamp.
+///
+/// The frames are equal if they have the same ID and flags, DLC, and data.
+/// The timestamp can be of any type, such as a `SystemTime` or an
+/// `Option<SystemTime>`. This is mainly useful in tests, where the time at
+/// which a frame arrives can't be known in advance.
+pub fn frames_eq_ignoring_time<T>(a: &(CanFrame, T), b: &CanFrame) -> bool {
+    let frame = &a.0;
+    frame.id_word() == b.id_word() && frame.dlc() == b.dlc() && frame.data() == b.data()
+}
+
 /////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
@@ -1735,6 +2570,30 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "netlink")]
+    #[test]
+    fn test_error_frame_inferred_state() {
+        use crate::nl::CanState;
+
+        let frame = CanErrorFrame::new_error(libc::CAN_ERR_BUSOFF, &[]).unwrap();
+        assert_eq!(frame.inferred_state(), Some(CanState::BusOff));
+
+        let frame = CanErrorFrame::new_error(libc::CAN_ERR_RESTARTED, &[]).unwrap();
+        assert_eq!(frame.inferred_state(), Some(CanState::ErrorActive));
+
+        let data = [0, libc::CAN_ERR_CRTL_TX_PASSIVE as u8];
+        let frame = CanErrorFrame::new_error(libc::CAN_ERR_CRTL, &data).unwrap();
+        assert_eq!(frame.inferred_state(), Some(CanState::ErrorPassive));
+
+        let data = [0, libc::CAN_ERR_CRTL_RX_WARNING as u8];
+        let frame = CanErrorFrame::new_error(libc::CAN_ERR_CRTL, &data).unwrap();
+        assert_eq!(frame.inferred_state(), Some(CanState::ErrorWarning));
+
+        // Not a state change
+        let frame = CanErrorFrame::new_error(libc::CAN_ERR_ACK, &[]).unwrap();
+        assert_eq!(frame.inferred_state(), None);
+    }
+
     #[test]
     fn test_fd_frame() {
         let frame = CanFdFrame::new(STD_ID, DATA).unwrap();
@@ -1764,6 +2623,76 @@ mod tests {
         assert!(frame.is_extended());
     }
 
+    #[test]
+    fn test_decode_canid() {
+        let parts = decode_canid(0x123);
+        assert_eq!(0x123, parts.id);
+        assert!(!parts.is_extended);
+        assert!(!parts.is_remote);
+        assert!(!parts.is_error);
+        assert_eq!(parts.to_id(), StandardId::new(0x123).map(Id::from));
+
+        let parts = decode_canid(0x12345678 | CAN_EFF_FLAG | CAN_RTR_FLAG);
+        assert_eq!(0x12345678, parts.id);
+        assert!(parts.is_extended);
+        assert!(parts.is_remote);
+        assert!(!parts.is_error);
+        assert_eq!(parts.to_id(), ExtendedId::new(0x12345678).map(Id::from));
+
+        let parts = decode_canid(CAN_ERR_FLAG | libc::CAN_ERR_BUSOFF | libc::CAN_ERR_RESTARTED);
+        assert_eq!(libc::CAN_ERR_BUSOFF | libc::CAN_ERR_RESTARTED, parts.id);
+        assert!(!parts.is_extended);
+        assert!(!parts.is_remote);
+        assert!(parts.is_error);
+        assert_eq!(parts.to_id(), None);
+    }
+
+    #[test]
+    fn test_data_frame_with_len() {
+        let frame = CanDataFrame::with_len(STD_ID, &[1, 2, 3], 8).unwrap();
+        assert_eq!(STD_ID, frame.id());
+        assert_eq!(8, frame.dlc());
+        assert_eq!(8, frame.len());
+        assert_eq!(&[1, 2, 3, 0, 0, 0, 0, 0], frame.data());
+
+        let frame = CanDataFrame::with_len(EXT_ID, DATA, DATA_LEN).unwrap();
+        assert_eq!(DATA, frame.data());
+
+        assert!(CanDataFrame::with_len(STD_ID, &[1, 2, 3], 2).is_none());
+        assert!(CanDataFrame::with_len(STD_ID, &[1, 2, 3], 9).is_none());
+    }
+
+    #[test]
+    fn test_data_full() {
+        let frame = CanDataFrame::new(STD_ID, DATA).unwrap();
+        let data = frame.data_full();
+        assert_eq!(&data[..DATA_LEN], DATA);
+        assert!(data[DATA_LEN..].iter().all(|&b| b == 0));
+
+        let frame = CanFdFrame::new(STD_ID, EXT_DATA_INVALID_DLEN).unwrap();
+        let data = frame.data_full();
+        assert_eq!(&data[..EXT_DATA_INVALID_DLEN.len()], EXT_DATA_INVALID_DLEN);
+        assert!(data[EXT_DATA_INVALID_DLEN.len()..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_candump_ascii() {
+        let frame = CanDataFrame::from_raw_id(0x123, b"ABC").unwrap();
+        assert_eq!(format_candump_ascii(&frame), "123 [3] 41 42 43 'ABC'");
+
+        let frame = CanDataFrame::from_raw_id(0x42, &[0x00, 0x41, 0x7F, 0x20, 0xFF]).unwrap();
+        assert_eq!(Ascii(&frame).to_string(), "042 [5] 00 41 7F 20 FF '.A. .'");
+
+        let frame = CanDataFrame::new(EXT_ID, EMPTY_DATA).unwrap();
+        assert_eq!(format_candump_ascii(&frame), "1FFFFFFF [0]");
+
+        let frame = CanFdFrame::from_raw_id(0x7FF, b"Hello, world").unwrap();
+        assert_eq!(
+            format_candump_ascii(&frame),
+            "7FF [12] 48 65 6C 6C 6F 2C 20 77 6F 72 6C 64 'Hello, world'"
+        );
+    }
+
     #[test]
     fn test_fd_ext_data_len() {
         assert!(CanFdFrame::is_valid_data_len(8));
@@ -1852,4 +2781,512 @@ mod tests {
             .clone_from_slice(crate::as_bytes(&frame.0));
         assert_eq!(fdframe.flags, 0);
     }
+
+    #[test]
+    fn test_into_c_structs() {
+        let frame = CanDataFrame::new(EXT_ID, DATA).unwrap();
+        let cframe = can_frame::from(frame);
+        assert_eq!(id_to_raw(EXT_ID) | CAN_EFF_FLAG, cframe.can_id);
+        assert_eq!(DATA_LEN, cframe.can_dlc as usize);
+        assert_eq!(DATA, &cframe.data[..DATA_LEN]);
+
+        let frame = CanFrame::new_remote(STD_ID, 2).unwrap();
+        let cframe = can_frame::from(frame);
+        assert_eq!(id_to_raw(STD_ID) | CAN_RTR_FLAG, cframe.can_id);
+        assert_eq!(2, cframe.can_dlc);
+
+        let frame = CanFdFrame::new(STD_ID, EXT_DATA).unwrap();
+        let cframe = canfd_frame::from(frame);
+        assert_eq!(id_to_raw(STD_ID), cframe.can_id);
+        assert_eq!(EXT_DATA.len(), cframe.len as usize);
+        assert_eq!(EXT_DATA, &cframe.data[..EXT_DATA.len()]);
+        assert_ne!(0, cframe.flags & CANFD_FDF as u8);
+    }
+
+    #[test]
+    fn test_generic_fd_flags() {
+        fn brs_esi<F: Frame>(frame: &F) -> (bool, bool) {
+            (frame.is_brs(), frame.is_esi())
+        }
+
+        let frame = CanFrame::new(STD_ID, DATA).unwrap();
+        assert_eq!((false, false), brs_esi(&frame));
+        assert_eq!((false, false), brs_esi(&CanAnyFrame::from(frame)));
+
+        let mut fdframe = CanFdFrame::new(EXT_ID, DATA).unwrap();
+        fdframe.set_brs(true);
+        assert_eq!((true, false), brs_esi(&fdframe));
+        assert_eq!(FdFlags::FDF | FdFlags::BRS, fdframe.fd_flags());
+        assert_eq!(IdFlags::EFF, fdframe.id_flags());
+
+        fdframe.set_esi(true);
+        assert_eq!((true, true), brs_esi(&CanAnyFrame::from(fdframe)));
+    }
+
+    #[test]
+    fn test_into_any() {
+        fn any<F: Frame>(frame: F) -> CanAnyFrame {
+            frame.into_any()
+        }
+
+        let frame = CanDataFrame::new(STD_ID, DATA).unwrap();
+        assert!(matches!(any(frame), CanAnyFrame::Normal(f) if f.data() == DATA));
+
+        let frame = CanRemoteFrame::new_remote(EXT_ID, 2).unwrap();
+        assert!(matches!(any(frame), CanAnyFrame::Remote(f) if f.dlc() == 2));
+
+        let frame = CanErrorFrame::new_error(libc::CAN_ERR_BUSOFF, &[]).unwrap();
+        assert!(matches!(any(frame), CanAnyFrame::Error(_)));
+
+        let frame = CanFrame::new(EXT_ID, DATA).unwrap();
+        assert!(matches!(any(frame), CanAnyFrame::Normal(f) if f.id() == EXT_ID));
+
+        let frame = CanFdFrame::new(STD_ID, DATA).unwrap();
+        assert!(matches!(any(frame), CanAnyFrame::Fd(f) if f.data() == DATA));
+
+        let frame = CanAnyFrame::from(frame);
+        assert!(matches!(any(frame), CanAnyFrame::Fd(_)));
+    }
+
+    #[test]
+    fn test_with_id() {
+        let frame = CanDataFrame::new(STD_ID, DATA).unwrap();
+        let new_frame = frame.with_id(EXT_ID);
+        assert_eq!(EXT_ID, new_frame.id());
+        assert_eq!(DATA, new_frame.data());
+        assert_eq!(STD_ID, frame.id());
+
+        let frame = CanRemoteFrame::new_remote(STD_ID, 3).unwrap();
+        let new_frame = frame.with_id(EXT_ID);
+        assert_eq!(EXT_ID, new_frame.id());
+        assert!(new_frame.is_remote_frame());
+        assert_eq!(3, new_frame.dlc());
+
+        let frame = CanErrorFrame::new_error(libc::CAN_ERR_BUSOFF, &[]).unwrap();
+        let new_frame = frame.with_id(EXT_ID);
+        assert_eq!(frame.id_word(), new_frame.id_word());
+
+        let frame = CanFdFrame::new(STD_ID, EXT_DATA).unwrap();
+        let new_frame = frame.with_id(EXT_ID);
+        assert_eq!(EXT_ID, new_frame.id());
+        assert_eq!(EXT_DATA, new_frame.data());
+    }
+
+    #[test]
+    fn test_payload_reads() {
+        let frame = CanDataFrame::new(STD_ID, &[0x01, 0x02, 0x03, 0x04, 0xFF, 0xFE]).unwrap();
+
+        assert_eq!(Some(0x01), frame.read_u8(0));
+        assert_eq!(Some(-1), frame.read_i8(4));
+        assert_eq!(None, frame.read_u8(6));
+
+        assert_eq!(Some(0x0102), frame.read_u16_be(0));
+        assert_eq!(Some(0x0201), frame.read_u16_le(0));
+        assert_eq!(Some(0x0304), frame.read_u16_be(2));
+        assert_eq!(Some(-2), frame.read_i16_be(4));
+        assert_eq!(Some(-257), frame.read_i16_le(4));
+        assert_eq!(None, frame.read_u16_be(5));
+
+        assert_eq!(Some(0x01020304), frame.read_u32_be(0));
+        assert_eq!(Some(0x04030201), frame.read_u32_le(0));
+        assert_eq!(Some(0x0304FFFE), frame.read_u32_be(2));
+        assert_eq!(None, frame.read_u32_le(3));
+        assert_eq!(None, frame.read_u64_be(0));
+        assert_eq!(None, frame.read_u32_be(usize::MAX));
+
+        let frame = CanFdFrame::new(STD_ID, &[0xFF; 12]).unwrap();
+        assert_eq!(Some(u64::MAX), frame.read_u64_le(4));
+        assert_eq!(Some(-1), frame.read_i64_be(4));
+        assert_eq!(None, frame.read_i64_be(5));
+    }
+
+    #[test]
+    fn test_fd_from_array() {
+        let frame = CanFdFrame::from_array(STD_ID, [0x11u8; 8]).unwrap();
+        assert_eq!(STD_ID, frame.id());
+        assert_eq!(&[0x11u8; 8], frame.data());
+        assert!(frame.flags().contains(FdFlags::FDF));
+
+        let frame = CanFdFrame::from_array(EXT_ID, [0x22u8; 64]).unwrap();
+        assert_eq!(EXT_ID, frame.id());
+        assert_eq!(&[0x22u8; 64], frame.data());
+
+        // Padded up to the next valid length
+        let frame = CanFdFrame::from_array(STD_ID, [0x33u8; 9]).unwrap();
+        assert_eq!(12, frame.len());
+    }
+
+    #[test]
+    fn test_error_frame_berr_counters() {
+        let frame = CanErrorFrame::from(CanError::BusError);
+        assert_eq!(None, frame.berr_counters());
+
+        let frame = CanErrorFrame::with_berr_counters(CanError::BusError, 96, 128);
+        assert_eq!(Some((96, 128)), frame.berr_counters());
+        assert_eq!(libc::CAN_ERR_BUSERROR | CAN_ERR_CNT, frame.error_bits());
+
+        // The counters don't hide the original error
+        assert!(matches!(frame.into_error(), CanError::BusError));
+    }
+
+    #[test]
+    fn test_frames_eq_ignoring_time() {
+        use std::time::{Duration, SystemTime};
+
+        let frame = CanFrame::new(STD_ID, DATA).unwrap();
+        let now = SystemTime::now();
+
+        let rx = (CanFrame::new(STD_ID, DATA).unwrap(), Some(now));
+        assert!(frames_eq_ignoring_time(&rx, &frame));
+
+        let rx = (rx.0, Some(now + Duration::from_secs(1)));
+        assert!(frames_eq_ignoring_time(&rx, &frame));
+
+        let rx = (rx.0, None::<SystemTime>);
+        assert!(frames_eq_ignoring_time(&rx, &frame));
+
+        let rx = (CanFrame::new(EXT_LOW_ID, DATA).unwrap(), Some(now));
+        assert!(!frames_eq_ignoring_time(&rx, &frame));
+
+        let rx = (CanFrame::new(STD_ID, &DATA[..2]).unwrap(), Some(now));
+        assert!(!frames_eq_ignoring_time(&rx, &frame));
+    }
+
+    #[test]
+    fn test_fd_validate() {
+        let frame = CanFdFrame::new(STD_ID, EXT_DATA).unwrap();
+        assert!(frame.validate().is_ok());
+
+        let mut raw = canfd_frame_default();
+        raw.can_id = 0x123;
+        raw.len = 13;
+        let frame = CanFdFrame::from(raw);
+        assert_eq!(Err(ConstructionError::TooMuchData), frame.validate());
+
+        raw.len = 65;
+        let frame = CanFdFrame::from(raw);
+        assert_eq!(Err(ConstructionError::TooMuchData), frame.validate());
+
+        raw.len = 12;
+        assert!(CanFdFrame::from(raw).validate().is_ok());
+
+        raw.flags = 0x80;
+        let frame = CanFdFrame::from(raw);
+        assert_eq!(Err(ConstructionError::WrongFrameType), frame.validate());
+
+        raw.flags = CANFD_BRS as u8;
+        raw.can_id |= CAN_RTR_FLAG;
+        let frame = CanFdFrame::from(raw);
+        assert_eq!(Err(ConstructionError::WrongFrameType), frame.validate());
+    }
+
+    #[test]
+    fn test_len8_dlc() {
+        let mut frame = CanDataFrame::new(STD_ID, &[0u8; 8]).unwrap();
+        assert_eq!(None, frame.len8_dlc());
+
+        assert!(frame.set_len8_dlc(16).is_err());
+        assert!(frame.set_len8_dlc(7).is_err());
+        frame.set_len8_dlc(0x0D).unwrap();
+        assert_eq!(Some(0x0D), frame.len8_dlc());
+        assert_eq!(8, frame.len());
+
+        // Only a frame with 8 data bytes can have a len8_dlc
+        let mut short = CanDataFrame::new(STD_ID, DATA).unwrap();
+        assert!(short.set_len8_dlc(0x0D).is_err());
+
+        // The DLC is dropped going through an FD frame...
+        let fdframe = CanFdFrame::from(frame);
+        assert_eq!(8, fdframe.len());
+        assert!(fdframe.validate().is_ok());
+        assert_eq!(
+            CanFdFrame::new(STD_ID, &[0u8; 8]).unwrap().as_bytes(),
+            fdframe.as_bytes()
+        );
+
+        let classic = CanDataFrame::try_from(fdframe).unwrap();
+        assert_eq!(None, classic.len8_dlc());
+
+        // ...unless it's carried along explicitly
+        let classic = CanDataFrame::try_from_fd_with_dlc(fdframe, 0x0D).unwrap();
+        assert_eq!(Some(0x0D), classic.len8_dlc());
+        assert_eq!(&[0u8; 8], classic.data());
+
+        let classic = CanDataFrame::try_from_fd_with_dlc(fdframe, 8).unwrap();
+        assert_eq!(None, classic.len8_dlc());
+
+        assert!(CanDataFrame::try_from_fd_with_dlc(fdframe, 16).is_err());
+        assert!(CanDataFrame::try_from_fd_with_dlc(fdframe, 7).is_err());
+        let short = CanFdFrame::new(STD_ID, DATA).unwrap();
+        assert!(CanDataFrame::try_from_fd_with_dlc(short, 0x0D).is_err());
+        assert_eq!(
+            DATA.len(),
+            CanDataFrame::try_from_fd_with_dlc(short, DATA.len() as u8)
+                .unwrap()
+                .len()
+        );
+
+        // Data in the reserved bytes of a raw FD frame is not a DLC
+        let mut raw = canfd_frame_default();
+        raw.len = 8;
+        // The second reserved byte, where a can_frame has its len8_dlc
+        crate::as_bytes_mut(&mut raw)[7] = 0x0D;
+        let classic = CanDataFrame::try_from(CanFdFrame::from(raw)).unwrap();
+        assert_eq!(None, classic.len8_dlc());
+
+        // Clearing it
+        frame.set_len8_dlc(8).unwrap();
+        assert_eq!(None, frame.len8_dlc());
+    }
+
+    #[test]
+    fn test_fd_from_raw_classic() {
+        let mut raw = can_frame_default();
+        raw.can_id = 0x123;
+        raw.can_dlc = 5;
+        raw.data[..5].copy_from_slice(&[1, 2, 3, 4, 5]);
+
+        let frame = CanFdFrame::from(raw);
+        assert_eq!(0x123, frame.raw_id());
+        assert_eq!(5, frame.len());
+        assert_eq!(&[1, 2, 3, 4, 5], frame.data());
+        assert!(frame.validate().is_ok());
+
+        // A len8_dlc can't be carried by the FD frame
+        raw.can_dlc = 8;
+        raw.len8_dlc = 0x0D;
+        let frame = CanFdFrame::from(raw);
+        assert_eq!(8, frame.len());
+        let expected = CanFdFrame::new(StandardId::new(0x123).unwrap(), &raw.data).unwrap();
+        assert_eq!(expected.as_bytes(), frame.as_bytes());
+    }
+
+    #[cfg(feature = "dump")]
+    #[test]
+    fn test_wire_round_trip() {
+        use crate::dump::ParseError;
+
+        let mut len8 = CanDataFrame::new(EXT_ID, &[0xAA; 8]).unwrap();
+        len8.set_len8_dlc(0x0F).unwrap();
+
+        let frames = [
+            CanAnyFrame::from(CanDataFrame::new(STD_ID, DATA).unwrap()),
+            CanAnyFrame::from(CanDataFrame::new(EXT_ID, &[]).unwrap()),
+            CanAnyFrame::from(len8),
+            CanAnyFrame::from(CanRemoteFrame::new_remote(EXT_LOW_ID, 6).unwrap()),
+            CanAnyFrame::from(CanErrorFrame::new_error(0x0004, &[0, 1, 2, 3, 4, 5, 6, 7]).unwrap()),
+            CanAnyFrame::from(
+                CanFdFrame::with_flags(STD_ID, EXT_DATA, FdFlags::BRS | FdFlags::ESI).unwrap(),
+            ),
+        ];
+
+        // Encode them all into one stream, then decode them back out
+        let buf: Vec<u8> = frames.iter().flat_map(|f| f.to_wire()).collect();
+        let mut rest = &buf[..];
+
+        for frame in &frames {
+            let (rx, n) = CanAnyFrame::from_wire(rest).unwrap();
+            rest = &rest[n..];
+
+            assert_eq!(frame.id_word(), rx.id_word());
+            assert_eq!(frame.dlc(), rx.dlc());
+            assert_eq!(frame.data(), rx.data());
+            match (frame, rx) {
+                (CanAnyFrame::Normal(f), CanAnyFrame::Normal(rx)) => {
+                    assert_eq!(f.len8_dlc(), rx.len8_dlc())
+                }
+                (CanAnyFrame::Remote(_), CanAnyFrame::Remote(_)) => (),
+                (CanAnyFrame::Error(_), CanAnyFrame::Error(_)) => (),
+                (CanAnyFrame::Fd(f), CanAnyFrame::Fd(rx)) => assert_eq!(f.flags(), rx.flags()),
+                _ => panic!("Wrong frame type"),
+            }
+        }
+        assert!(rest.is_empty());
+
+        // A partial record asks for more data
+        let wire = frames[0].to_wire();
+        assert!(matches!(
+            CanAnyFrame::from_wire(&wire[..wire.len() - 1]),
+            Err(ParseError::Incomplete)
+        ));
+        assert!(matches!(
+            CanAnyFrame::from_wire(&[]),
+            Err(ParseError::Incomplete)
+        ));
+
+        // An unknown kind can be skipped
+        assert!(matches!(
+            CanAnyFrame::from_wire(&[0x7F, 3, 1, 2, 3]),
+            Err(ParseError::UnknownFrameKind(5))
+        ));
+
+        // A data record with the RTR flag is rejected
+        let mut wire = frames[0].to_wire();
+        wire[2] |= 0x40;
+        assert!(matches!(
+            CanAnyFrame::from_wire(&wire),
+            Err(ParseError::InvalidCanFrame)
+        ));
+    }
+
+    #[test]
+    fn test_display_any_frame() {
+        let frame = CanAnyFrame::from(
+            CanDataFrame::new(StandardId::new(0x123).unwrap(), &[0xDE, 0xAD]).unwrap(),
+        );
+        assert_eq!("[DATA] 123#DE AD", frame.to_string());
+
+        let frame = CanAnyFrame::from(
+            CanRemoteFrame::new_remote(StandardId::new(0x123).unwrap(), 2).unwrap(),
+        );
+        assert!(frame.to_string().starts_with("[RTR] 40000123#"));
+
+        let frame = CanAnyFrame::from(CanErrorFrame::new_error(0x0004, &[0; 8]).unwrap());
+        assert!(frame.to_string().starts_with("[ERR] 20000004#"));
+
+        let fdframe =
+            CanFdFrame::with_flags(StandardId::new(0x123).unwrap(), &[0xDE, 0xAD], FdFlags::BRS)
+                .unwrap();
+        let frame = CanAnyFrame::from(fdframe);
+        assert_eq!("[FD] 123##5 DE AD", frame.to_string());
+    }
+
+    #[test]
+    fn test_chunk_payload() {
+        let data: Vec<u8> = (0..200).map(|i| i as u8).collect();
+
+        let frames = chunk_payload(STD_ID, &data, false);
+        assert_eq!(25, frames.len());
+        assert!(frames
+            .iter()
+            .all(|f| matches!(f, CanAnyFrame::Normal(_)) && f.id() == STD_ID && f.len() == 8));
+        let joined: Vec<u8> = frames.iter().flat_map(|f| f.data().to_vec()).collect();
+        assert_eq!(data, joined);
+
+        // 3 full frames, then 8 bytes left over
+        let frames = chunk_payload(EXT_ID, &data, true);
+        assert_eq!(4, frames.len());
+        assert!(frames
+            .iter()
+            .all(|f| matches!(f, CanAnyFrame::Fd(_)) && f.id() == EXT_ID));
+        assert_eq!(64, frames[2].data().len());
+        assert_eq!(&data[192..], frames[3].data());
+
+        // The last chunk is padded up to a valid FD length
+        let frames = chunk_payload(STD_ID, &data[..70], true);
+        assert_eq!(2, frames.len());
+        assert_eq!(6, frames[1].data().len());
+        let frames = chunk_payload(STD_ID, &data[..75], true);
+        assert_eq!(12, frames[1].data().len());
+        assert_eq!(&data[64..75], &frames[1].data()[..11]);
+
+        assert!(chunk_payload(STD_ID, &[], false).is_empty());
+    }
+
+    #[test]
+    fn test_arbitration_cmp() {
+        let std_frame = |id: u16| CanFrame::new(StandardId::new(id).unwrap(), &[]).unwrap();
+        let ext_frame = |id: u32| CanFrame::new(ExtendedId::new(id).unwrap(), &[]).unwrap();
+
+        // Standard vs standard: the lower ID wins
+        assert_eq!(
+            Ordering::Less,
+            std_frame(0x100).arbitration_cmp(&std_frame(0x101))
+        );
+        assert_eq!(
+            Ordering::Greater,
+            std_frame(0x7FF).arbitration_cmp(&std_frame(0))
+        );
+        assert_eq!(
+            Ordering::Equal,
+            std_frame(0x123).arbitration_cmp(&std_frame(0x123))
+        );
+
+        // Standard vs extended: decided by the base ID, then the SRR/IDE bits
+        assert_eq!(
+            Ordering::Less,
+            std_frame(0x123).arbitration_cmp(&ext_frame(0x123 << 18))
+        );
+        assert_eq!(
+            Ordering::Greater,
+            std_frame(0x124).arbitration_cmp(&ext_frame(0x123 << 18 | 0x3FFFF))
+        );
+        assert_eq!(
+            Ordering::Less,
+            ext_frame(0x7FF).arbitration_cmp(&std_frame(1))
+        );
+        assert_eq!(
+            Ordering::Greater,
+            ext_frame(0x123 << 18).arbitration_cmp(&std_frame(0x123))
+        );
+
+        // A standard remote frame still beats an extended frame, on IDE
+        let std_remote = CanFrame::new_remote(StandardId::new(0x123).unwrap(), 0).unwrap();
+        assert_eq!(
+            Ordering::Less,
+            std_remote.arbitration_cmp(&ext_frame(0x123 << 18))
+        );
+
+        // Data vs remote at the same ID: data wins
+        assert_eq!(
+            Ordering::Less,
+            std_frame(0x123).arbitration_cmp(&std_remote)
+        );
+        let ext_remote = CanFrame::new_remote(ExtendedId::new(0x12345).unwrap(), 0).unwrap();
+        assert_eq!(
+            Ordering::Less,
+            ext_frame(0x12345).arbitration_cmp(&ext_remote)
+        );
+        assert_eq!(
+            Ordering::Greater,
+            ext_remote.arbitration_cmp(&ext_frame(0x12345))
+        );
+
+        // Different frame types can be compared
+        let fd_frame = CanFdFrame::new(StandardId::new(0x100).unwrap(), &[]).unwrap();
+        assert_eq!(Ordering::Less, fd_frame.arbitration_cmp(&std_frame(0x101)));
+    }
+
+    #[test]
+    fn test_try_from_raw_id() {
+        let frame = CanFrame::try_from_raw_id(0x1FFFFFFF, DATA).unwrap();
+        assert!(frame.is_extended());
+        assert_eq!(0x1FFFFFFF, frame.raw_id());
+
+        assert!(matches!(
+            CanFrame::try_from_raw_id(0x20000000, DATA),
+            Err(ConstructionError::IDTooLarge)
+        ));
+        assert!(matches!(
+            CanFdFrame::try_from_raw_id(u32::MAX, DATA),
+            Err(ConstructionError::IDTooLarge)
+        ));
+        assert!(matches!(
+            CanFrame::try_from_raw_id(0x100, &[0u8; 9]),
+            Err(ConstructionError::TooMuchData)
+        ));
+    }
+
+    #[test]
+    fn test_data_mut() {
+        let mut frame = CanDataFrame::new(STD_ID, DATA).unwrap();
+        frame.data_mut()[1] += 1;
+        assert_eq!(&[0, 2, 2, 3], frame.data());
+        assert_eq!(DATA_LEN, frame.dlc());
+
+        let mut frame = CanFdFrame::new(STD_ID, &[0u8; 20]).unwrap();
+        let len = frame.data().len();
+        assert_eq!(len, frame.data_mut().len());
+        frame.data_mut()[19] = 0xFF;
+        assert_eq!(0xFF, frame.data()[19]);
+        assert_eq!(len, frame.data().len());
+
+        let mut frame = CanAnyFrame::from(CanFrame::new(STD_ID, DATA).unwrap());
+        frame.data_mut()[0] = 0x10;
+        assert_eq!(&[0x10, 1, 2, 3], frame.data());
+
+        let mut frame = CanFrame::new_remote(STD_ID, 4).unwrap();
+        assert!(frame.data_mut().is_empty());
+        assert_eq!(4, frame.dlc());
+    }
 }
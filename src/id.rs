@@ -26,6 +26,16 @@ pub use libc::{
 /// Mark CAN FD for dual use of struct canfd_frame
 pub const CANFD_FDF: libc::c_int = 0x04;
 
+/// The maximum raw DLC of a classic CAN frame.
+///
+/// With the `CC_LEN8_DLC` control mode, a classic frame with 8 data bytes
+/// can carry a DLC of 9 to 15.
+pub const CAN_MAX_RAW_DLC: usize = 15;
+
+/// Error class bit indicating that the controller's TX and RX error
+/// counters are in bytes 6 and 7 of the error frame data.
+pub const CAN_ERR_CNT: u32 = 0x0200;
+
 /// An error mask that will cause SocketCAN to report all errors
 pub const ERR_MASK_ALL: u32 = CAN_ERR_MASK;
 
@@ -94,6 +104,60 @@ pub fn id_from_raw(id: u32) -> Option<Id> {
     Some(id)
 }
 
+/// Extension methods for [`embedded_can::Id`].
+///
+/// These wrap the free functions in this module to avoid having to match
+/// on the standard and extended variants everywhere.
+pub trait IdExt {
+    /// Gets the raw numeric value of the ID, without any flag bits.
+    fn raw_value(&self) -> u32;
+
+    /// Gets the SocketCAN `canid_t` value of the ID.
+    ///
+    /// If it's an extended ID, the `CAN_EFF_FLAG` bit is also set.
+    fn to_canid_t(&self) -> canid_t;
+
+    /// Determines if the ID is a standard, 11-bit, ID.
+    fn is_standard(&self) -> bool;
+
+    /// Determines if the ID is an extended, 29-bit, ID.
+    fn is_extended(&self) -> bool;
+
+    /// Gets the mask for the valid bits of the ID, depending on its width.
+    ///
+    /// This is `CAN_SFF_MASK` for standard IDs and `CAN_EFF_MASK` for
+    /// extended IDs.
+    fn width_mask(&self) -> u32;
+}
+
+impl IdExt for Id {
+    fn raw_value(&self) -> u32 {
+        match self {
+            Id::Standard(id) => id.as_raw() as u32,
+            Id::Extended(id) => id.as_raw(),
+        }
+    }
+
+    fn to_canid_t(&self) -> canid_t {
+        id_to_canid_t(*self)
+    }
+
+    fn is_standard(&self) -> bool {
+        id_is_standard(self)
+    }
+
+    fn is_extended(&self) -> bool {
+        id_is_extended(self)
+    }
+
+    fn width_mask(&self) -> u32 {
+        match self {
+            Id::Standard(_) => CAN_SFF_MASK,
+            Id::Extended(_) => CAN_EFF_MASK,
+        }
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////
 /// A CAN identifier that can be standard or extended.
 ///
@@ -304,4 +368,25 @@ mod tests {
         assert!(matches!(id, CanId::Standard(_)));
         assert_eq!(id.as_raw(), ID + 1);
     }
+
+    #[test]
+    fn test_id_ext() {
+        let id = Id::from(StandardId::new(0x123).unwrap());
+        assert_eq!(id.raw_value(), 0x123);
+        assert_eq!(id.to_canid_t(), 0x123);
+        assert!(id.is_standard());
+        assert!(!id.is_extended());
+        assert_eq!(id.width_mask(), CAN_SFF_MASK);
+
+        let id = Id::from(ExtendedId::new(0x123).unwrap());
+        assert_eq!(id.raw_value(), 0x123);
+        assert_eq!(id.to_canid_t(), 0x123 | CAN_EFF_FLAG);
+        assert!(!id.is_standard());
+        assert!(id.is_extended());
+        assert_eq!(id.width_mask(), CAN_EFF_MASK);
+
+        let id = Id::from(ExtendedId::MAX);
+        assert_eq!(id.raw_value(), CAN_EFF_MASK);
+        assert_eq!(id.raw_value() & id.width_mask(), id.raw_value());
+    }
 }
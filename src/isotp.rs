@@ -0,0 +1,201 @@
+// socketcan/src/isotp.rs
+//
+// Implements ISO-TP (ISO 15765-2) sockets for SocketCAN on Linux.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! ISO-TP (ISO 15765-2) transport protocol sockets.
+//!
+//! ISO-TP is a transport protocol that sends payloads which are larger
+//! than a single CAN frame by segmenting them into a series of frames, with
+//! flow control from the receiver. It is the transport used by diagnostic
+//! protocols like UDS (ISO 14229) and OBD-II.
+//!
+//! The Linux kernel implements the protocol with the `CAN_ISOTP` socket
+//! type. A socket is bound to an interface along with a pair of CAN IDs:
+//! one to receive frames from the peer, and another to send frames to it.
+//! The kernel then handles all the segmentation and flow control, so each
+//! read or write on the socket transfers a complete payload.
+//!
+//! The kernel documentation is here:
+//!
+//! <https://docs.kernel.org/networking/iso15765-2.html>
+
+use crate::{CanAddr, IoError, IoErrorKind, IoResult, ProtocolSocket};
+use embedded_can::Id;
+use libc::{c_int, c_void, AF_CAN, CAN_ISOTP};
+use socket2::SockAddr;
+use std::{
+    io::{Read, Write},
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd},
+};
+
+/// The default maximum size of a payload that can be read from an ISO-TP
+/// socket.
+///
+/// The ISO-TP standard allows up to 4095 bytes for a classic CAN link, but
+/// the kernel accepts larger payloads, up to this size, by default. The
+/// limit can be raised with the `max_pdu_size` parameter of the kernel
+/// module.
+pub const ISOTP_MAX_PDU_SIZE: usize = 8300;
+
+// ===== CanIsoTpSocket =====
+
+/// A socket for ISO-TP (ISO 15765-2) transport over CAN.
+///
+/// Each read or write on the socket transfers a complete payload, of
+/// arbitrary length, which the kernel segments into, or reassembles from,
+/// individual CAN frames.
+///
+/// The socket is automatically closed when the object is dropped.
+#[allow(missing_copy_implementations)]
+#[derive(Debug)]
+pub struct CanIsoTpSocket(socket2::Socket);
+
+impl CanIsoTpSocket {
+    /// Opens an ISO-TP socket on the named CAN device.
+    ///
+    /// The socket receives frames with the `rx_id` and sends frames with
+    /// the `tx_id`.
+    pub fn open<R, T>(ifname: &str, rx_id: R, tx_id: T) -> IoResult<Self>
+    where
+        R: Into<Id>,
+        T: Into<Id>,
+    {
+        let addr = CanAddr::from_iface_isotp(ifname, rx_id, tx_id)?;
+        Self::open_addr(&addr)
+    }
+
+    /// Opens an ISO-TP socket on the CAN device with the interface index.
+    pub fn open_iface<R, T>(ifindex: u32, rx_id: R, tx_id: T) -> IoResult<Self>
+    where
+        R: Into<Id>,
+        T: Into<Id>,
+    {
+        let addr = CanAddr::new_isotp(ifindex, rx_id, tx_id);
+        Self::open_addr(&addr)
+    }
+
+    /// Opens an ISO-TP socket by address.
+    ///
+    /// The address must have the ISO-TP receive and transmit IDs set, as
+    /// with `CanAddr::new_isotp()`.
+    pub fn open_addr(addr: &CanAddr) -> IoResult<Self> {
+        let af_can = socket2::Domain::from(AF_CAN);
+        let can_isotp = socket2::Protocol::from(CAN_ISOTP);
+
+        let sock = socket2::Socket::new_raw(af_can, socket2::Type::DGRAM, Some(can_isotp))?;
+        sock.bind(&SockAddr::from(*addr))?;
+        Ok(Self(sock))
+    }
+
+    /// Reads a single, complete payload from the socket into the buffer.
+    ///
+    /// Returns the size of the payload.
+    pub fn read_to(&self, buf: &mut [u8]) -> IoResult<usize> {
+        (&self.0).read(buf)
+    }
+
+    /// Reads a single, complete payload from the socket.
+    ///
+    /// The payload is peeked first to get its full size, so it is never
+    /// truncated, even if it is larger than `ISOTP_MAX_PDU_SIZE`.
+    pub fn read_payload(&self) -> IoResult<Vec<u8>> {
+        let mut buf = vec![0u8; ISOTP_MAX_PDU_SIZE];
+        let n = self.recv_with_flags(&mut buf, libc::MSG_PEEK | libc::MSG_TRUNC)?;
+        if n > buf.len() {
+            buf.resize(n, 0);
+        }
+
+        match self.recv_with_flags(&mut buf, libc::MSG_TRUNC)? {
+            n if n > buf.len() => Err(IoError::new(
+                IoErrorKind::InvalidData,
+                "ISO-TP payload too large for the buffer",
+            )),
+            n => {
+                buf.truncate(n);
+                Ok(buf)
+            }
+        }
+    }
+
+    // Receives a payload into the buffer with the flags. With `MSG_TRUNC`,
+    // this returns the full size of the payload, even if it didn't fit.
+    fn recv_with_flags(&self, buf: &mut [u8], flags: c_int) -> IoResult<usize> {
+        let ret = unsafe {
+            libc::recv(
+                self.as_raw_fd(),
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len(),
+                flags,
+            )
+        };
+
+        match ret {
+            n if n < 0 => Err(IoError::last_os_error()),
+            n => Ok(n as usize),
+        }
+    }
+
+    /// Writes a complete payload to the socket.
+    ///
+    /// The kernel sends the payload to the peer, in as many frames as
+    /// necessary.
+    pub fn write_payload(&self, data: &[u8]) -> IoResult<()> {
+        match (&self.0).write(data)? {
+            n if n == data.len() => Ok(()),
+            _ => Err(IoError::from(IoErrorKind::WriteZero)),
+        }
+    }
+}
+
+impl ProtocolSocket for CanIsoTpSocket {
+    fn as_raw_socket(&self) -> &socket2::Socket {
+        &self.0
+    }
+}
+
+impl AsRawFd for CanIsoTpSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl From<OwnedFd> for CanIsoTpSocket {
+    fn from(fd: OwnedFd) -> Self {
+        Self(socket2::Socket::from(fd))
+    }
+}
+
+impl IntoRawFd for CanIsoTpSocket {
+    fn into_raw_fd(self) -> RawFd {
+        self.0.into_raw_fd()
+    }
+}
+
+impl AsFd for CanIsoTpSocket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl Read for CanIsoTpSocket {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for CanIsoTpSocket {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.0.flush()
+    }
+}
@@ -0,0 +1,265 @@
+// socketcan/src/j1939.rs
+//
+// Implements SAE J1939 sockets for SocketCAN on Linux.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! SAE J1939 protocol sockets.
+//!
+//! J1939 is a higher-layer protocol on top of CAN, which is widely used in
+//! heavy-duty vehicles, agricultural and construction equipment, and the
+//! like. Messages are identified by a Parameter Group Number (PGN) and
+//! sent between 8-bit node addresses, which can also be claimed
+//! dynamically by nodes with a 64-bit NAME.
+//!
+//! The Linux kernel implements the protocol with the `CAN_J1939` socket
+//! type. A socket is bound to an interface with the local NAME, PGN, and
+//! address. The kernel handles the transport protocols for messages larger
+//! than a single frame, so each send or receive on the socket transfers a
+//! complete message.
+//!
+//! The kernel documentation is here:
+//!
+//! <https://docs.kernel.org/networking/j1939.html>
+
+use crate::{CanAddr, IoError, IoErrorKind, IoResult, ProtocolSocket};
+use libc::{c_int, c_void, sockaddr_can, socklen_t, AF_CAN, CAN_J1939, SOL_CAN_BASE};
+use socket2::SockAddr;
+use std::{
+    mem::{self, size_of},
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd},
+};
+
+/// The socket option level for J1939 sockets
+pub const SOL_CAN_J1939: c_int = SOL_CAN_BASE + CAN_J1939;
+
+/// Socket option to set the PGN filters
+pub const SO_J1939_FILTER: c_int = 1;
+/// Socket option to receive all packets, regardless of the destination
+pub const SO_J1939_PROMISC: c_int = 2;
+/// Socket option to set the default send priority
+pub const SO_J1939_SEND_PRIO: c_int = 3;
+/// Socket option to enable the error queue
+pub const SO_J1939_ERRQUEUE: c_int = 4;
+
+/// No address. Used to bind to any address, or to broadcast.
+pub const J1939_NO_ADDR: u8 = 0xFF;
+/// The null address, used by a node that can't claim an address.
+pub const J1939_IDLE_ADDR: u8 = 0xFE;
+/// No NAME
+pub const J1939_NO_NAME: u64 = 0;
+/// No PGN. Used to bind to receive any PGN.
+pub const J1939_NO_PGN: u32 = 0x40000;
+/// The maximum size of a message using the Transport Protocol (TP)
+pub const J1939_MAX_TP_PACKET_SIZE: usize = 7 * 255;
+/// The maximum size of a message using the Extended Transport Protocol (ETP)
+pub const J1939_MAX_ETP_PACKET_SIZE: usize = 7 * 0x00FF_FFFF;
+
+// ===== J1939Socket =====
+
+/// A socket for SAE J1939 messages.
+///
+/// Each send or receive on the socket transfers a complete message, which
+/// the kernel segments into, or reassembles from, individual CAN frames.
+///
+/// The socket is automatically closed when the object is dropped.
+#[allow(missing_copy_implementations)]
+#[derive(Debug)]
+pub struct J1939Socket(socket2::Socket);
+
+impl J1939Socket {
+    /// Opens a J1939 socket on the named CAN device.
+    ///
+    /// The socket is bound to the local `name`, `pgn`, and address,
+    /// `jaddr`. Use `J1939_NO_NAME`, `J1939_NO_PGN`, and `J1939_NO_ADDR`
+    /// to leave any of them unspecified.
+    pub fn open(ifname: &str, name: u64, pgn: u32, jaddr: u8) -> IoResult<Self> {
+        let addr = CanAddr::from_iface_j1939(ifname, name, pgn, jaddr)?;
+        Self::open_addr(&addr)
+    }
+
+    /// Opens a J1939 socket on the CAN device with the interface index.
+    pub fn open_iface(ifindex: u32, name: u64, pgn: u32, jaddr: u8) -> IoResult<Self> {
+        let addr = CanAddr::new_j1939(ifindex, name, pgn, jaddr);
+        Self::open_addr(&addr)
+    }
+
+    /// Opens a J1939 socket bound to the address.
+    ///
+    /// The address should have the J1939 fields set, as with
+    /// `CanAddr::new_j1939()`.
+    pub fn open_addr(addr: &CanAddr) -> IoResult<Self> {
+        let af_can = socket2::Domain::from(AF_CAN);
+        let can_j1939 = socket2::Protocol::from(CAN_J1939);
+
+        let sock = socket2::Socket::new_raw(af_can, socket2::Type::DGRAM, Some(can_j1939))?;
+        sock.bind(&SockAddr::from(*addr))?;
+        Ok(Self(sock))
+    }
+
+    /// Sets the default destination for the socket.
+    ///
+    /// After this, messages can be sent with `send()`.
+    pub fn connect(&self, addr: &CanAddr) -> IoResult<()> {
+        self.0.connect(&SockAddr::from(*addr))
+    }
+
+    /// Sends a message to the default destination set with `connect()`.
+    pub fn send(&self, data: &[u8]) -> IoResult<()> {
+        match self.0.send(data)? {
+            n if n == data.len() => Ok(()),
+            _ => Err(IoError::from(IoErrorKind::WriteZero)),
+        }
+    }
+
+    /// Sends a message to the destination address.
+    ///
+    /// The address should have the PGN of the message and the destination
+    /// address (or `J1939_NO_ADDR` to broadcast) or NAME.
+    pub fn send_to(&self, data: &[u8], addr: &CanAddr) -> IoResult<()> {
+        match self.0.send_to(data, &SockAddr::from(*addr))? {
+            n if n == data.len() => Ok(()),
+            _ => Err(IoError::from(IoErrorKind::WriteZero)),
+        }
+    }
+
+    /// Receives a message into the buffer.
+    ///
+    /// Returns the size of the message along with the address of the
+    /// sender, which includes the PGN of the message. If the message is
+    /// larger than the buffer, it is truncated to fit.
+    pub fn recv_from(&self, buf: &mut [u8]) -> IoResult<(usize, CanAddr)> {
+        let (n, addr, _) = self.recv_with_flags(buf, 0)?;
+        Ok((n, addr))
+    }
+
+    /// Receives a complete message, along with the address of the sender.
+    ///
+    /// The message is first peeked, growing the buffer as needed, up to
+    /// the Extended Transport Protocol limit, so that a large message is
+    /// never truncated.
+    pub fn recv_msg(&self) -> IoResult<(Vec<u8>, CanAddr)> {
+        let mut buf = vec![0u8; J1939_MAX_TP_PACKET_SIZE];
+        while let (_, _, true) = self.recv_with_flags(&mut buf, libc::MSG_PEEK)? {
+            if buf.len() >= J1939_MAX_ETP_PACKET_SIZE {
+                break;
+            }
+            let len = (buf.len() * 4).min(J1939_MAX_ETP_PACKET_SIZE);
+            buf.resize(len, 0);
+        }
+
+        match self.recv_with_flags(&mut buf, 0)? {
+            (_, _, true) => Err(IoError::new(
+                IoErrorKind::InvalidData,
+                "J1939 message too large for the buffer",
+            )),
+            (n, addr, false) => {
+                buf.truncate(n);
+                Ok((buf, addr))
+            }
+        }
+    }
+
+    // Receives a message into the buffer with `recvmsg()`, returning the
+    // size, the address of the sender, and whether the message was
+    // truncated.
+    fn recv_with_flags(&self, buf: &mut [u8], flags: c_int) -> IoResult<(usize, CanAddr, bool)> {
+        let mut addr: sockaddr_can = unsafe { mem::zeroed() };
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut c_void,
+            iov_len: buf.len(),
+        };
+
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_name = &mut addr as *mut _ as *mut c_void;
+        msg.msg_namelen = size_of::<sockaddr_can>() as socklen_t;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+
+        match unsafe { libc::recvmsg(self.as_raw_fd(), &mut msg, flags) } {
+            n if n < 0 => Err(IoError::last_os_error()),
+            n => Ok((
+                n as usize,
+                CanAddr::from(addr),
+                msg.msg_flags & libc::MSG_TRUNC != 0,
+            )),
+        }
+    }
+
+    /// Enables or disables sending broadcast messages.
+    ///
+    /// This is required to send to the broadcast address, `J1939_NO_ADDR`.
+    pub fn set_broadcast(&self, enabled: bool) -> IoResult<()> {
+        self.0.set_broadcast(enabled)
+    }
+
+    /// Enables or disables promiscuous mode.
+    ///
+    /// In promiscuous mode, the socket receives all messages on the bus,
+    /// not just the ones addressed to it.
+    pub fn set_promisc(&self, enabled: bool) -> IoResult<()> {
+        let promisc = c_int::from(enabled);
+        self.set_socket_option(SO_J1939_PROMISC, &promisc)
+    }
+
+    /// Sets the default priority for messages sent on the socket.
+    ///
+    /// The priority is 0 (highest) to 7 (lowest).
+    pub fn set_send_priority(&self, prio: u8) -> IoResult<()> {
+        let prio = c_int::from(prio);
+        self.set_socket_option(SO_J1939_SEND_PRIO, &prio)
+    }
+
+    // Sets a J1939 option on the socket.
+    fn set_socket_option<T>(&self, name: c_int, val: &T) -> IoResult<()> {
+        let ret = unsafe {
+            libc::setsockopt(
+                self.as_raw_fd(),
+                SOL_CAN_J1939,
+                name,
+                val as *const _ as *const c_void,
+                size_of::<T>() as socklen_t,
+            )
+        };
+
+        match ret {
+            0 => Ok(()),
+            _ => Err(IoError::last_os_error()),
+        }
+    }
+}
+
+impl ProtocolSocket for J1939Socket {
+    fn as_raw_socket(&self) -> &socket2::Socket {
+        &self.0
+    }
+}
+
+impl AsRawFd for J1939Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl From<OwnedFd> for J1939Socket {
+    fn from(fd: OwnedFd) -> Self {
+        Self(socket2::Socket::from(fd))
+    }
+}
+
+impl IntoRawFd for J1939Socket {
+    fn into_raw_fd(self) -> RawFd {
+        self.0.into_raw_fd()
+    }
+}
+
+impl AsFd for J1939Socket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
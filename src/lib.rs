@@ -77,6 +77,14 @@
 //!   network interfaces attached to the host. This brings in the dependency for
 //!   [libudev](https://crates.io/crates/libudev)
 //!
+//! * **isotp** -
+//!   Include the `isotp` module with sockets for the ISO-TP (ISO 15765-2)
+//!   transport protocol, which is used for diagnostics like UDS.
+//!
+//! * **j1939** -
+//!   Include the `j1939` module with sockets for the SAE J1939 protocol,
+//!   which is used in heavy-duty vehicles.
+//!
 //! * **utils** -
 //!   Whether to build command-line utilities. This brings in additional
 //!   dependencies like [anyhow](https://docs.rs/anyhow/latest/anyhow/) and
@@ -136,15 +144,15 @@ pub use embedded_can::{
 
 pub mod errors;
 pub use errors::{
-    CanError, CanErrorDecodingFailure, ConstructionError, Error, IoError, IoErrorKind, IoResult,
-    Result,
+    CanError, CanErrorDecodingFailure, ConstructionError, Error, ErrorClass, ErrorMask, IoError,
+    IoErrorKind, IoResult, Result,
 };
 
 pub mod addr;
-pub use addr::CanAddr;
+pub use addr::{AddrSpec, CanAddr};
 
 pub mod id;
-pub use id::CanId;
+pub use id::{CanId, IdExt};
 
 pub mod frame;
 pub use frame::{
@@ -156,13 +164,34 @@ pub use frame::{
 pub mod dump;
 
 pub mod socket;
-pub use socket::{CanFdSocket, CanFilter, CanSocket, ShouldRetry, Socket, SocketOptions};
+pub use socket::{
+    CanFdSocket, CanFilter, CanSocket, ErrorQueueEntry, FrameKind, KernelFeature, MsgFlags,
+    ProtocolSocket, SendFlags, ShouldRetry, Socket, SocketBuilder, SocketOptions,
+};
+
+#[cfg(feature = "netlink")]
+pub use socket::CanSocketPair;
+
+pub mod util;
+
+#[cfg(feature = "isotp")]
+pub mod isotp;
+#[cfg(feature = "isotp")]
+pub use isotp::CanIsoTpSocket;
+
+#[cfg(feature = "j1939")]
+pub mod j1939;
+#[cfg(feature = "j1939")]
+pub use j1939::J1939Socket;
 
 #[cfg(feature = "netlink")]
 pub mod nl;
 
 #[cfg(feature = "netlink")]
-pub use nl::{CanCtrlMode, CanInterface, InterfaceCanParams};
+pub use nl::{
+    BitTimingTable, CanConfig, CanCtrlMode, CanInterface, CanTdc, InterfaceCanParams,
+    InterfaceKind, InterfaceSnapshot, ProtocolMode,
+};
 
 /// Optional tokio support
 #[cfg(feature = "tokio")]
@@ -65,21 +65,25 @@ use neli::{
     rtnl::{Ifinfomsg, Rtattr},
     socket::NlSocketHandle,
     types::{Buffer, RtBuffer},
-    FromBytes, ToBytes,
+    FromBytes, FromBytesWithInput, ToBytes,
 };
 use nix::{self, net::if_::if_nametoindex, unistd};
-use rt::IflaCan;
+use rt::{IflaCan, IflaCanCtrlModeExt, IflaCanTdc, IflaVxcan};
 use std::{
     ffi::CStr,
-    fmt::Debug,
-    os::raw::{c_int, c_uint},
+    fmt::{self, Debug},
+    io::Cursor,
+    os::{
+        raw::{c_int, c_uint},
+        unix::io::AsRawFd,
+    },
 };
 
 /// Low-level Netlink CAN struct bindings.
 mod rt;
 
 use rt::can_ctrlmode;
-pub use rt::CanState;
+pub use rt::{CanState, TimingError};
 
 /// A result for Netlink errors.
 type NlResult<T> = Result<T, NlError>;
@@ -95,6 +99,63 @@ pub type CanBitTimingConst = rt::can_bittiming_const;
 pub type CanClock = rt::can_clock;
 /// CAN bus error counters
 pub type CanBerrCounter = rt::can_berr_counter;
+/// CAN device statistics
+pub type CanDeviceStats = rt::can_device_stats;
+/// The 64-bit interface statistics
+pub type LinkStats64 = rt::rtnl_link_stats64;
+
+// ===== Netlink tracing =====
+
+/// Logs a netlink request, with its serialized bytes, at the trace level.
+///
+/// This is only active with the `netlink-trace` feature.
+#[cfg(feature = "netlink-trace")]
+fn trace_request<T, P>(msg: &Nlmsghdr<T, P>)
+where
+    T: NlType + Debug,
+    P: ToBytes + Debug,
+{
+    let mut buf = Cursor::new(Vec::new());
+    match msg.to_bytes(&mut buf) {
+        Ok(()) => log::trace!(
+            "netlink request: {:?} [{}]",
+            msg,
+            hex::encode(buf.into_inner())
+        ),
+        Err(err) => log::trace!("netlink request: {:?} [unserializable: {}]", msg, err),
+    }
+}
+
+#[cfg(not(feature = "netlink-trace"))]
+#[inline(always)]
+fn trace_request<T, P>(_msg: &Nlmsghdr<T, P>) {}
+
+/// Logs a netlink response, with its serialized bytes, at the trace level.
+///
+/// This is only active with the `netlink-trace` feature.
+#[cfg(feature = "netlink-trace")]
+fn trace_response<T, P>(resp: &Result<Option<Nlmsghdr<T, P>>, NlError<T, P>>)
+where
+    T: NlType + Debug,
+    P: ToBytes + Debug,
+{
+    match resp {
+        Ok(Some(msg)) => {
+            let mut buf = Cursor::new(Vec::new());
+            let bytes = match msg.to_bytes(&mut buf) {
+                Ok(()) => hex::encode(buf.into_inner()),
+                Err(_) => "unserializable".into(),
+            };
+            log::trace!("netlink response: {:?} [{}]", msg, bytes);
+        }
+        Ok(None) => log::trace!("netlink response: none"),
+        Err(err) => log::trace!("netlink response error: {:?}", err),
+    }
+}
+
+#[cfg(not(feature = "netlink-trace"))]
+#[inline(always)]
+fn trace_response<T, P>(_resp: &Result<Option<Nlmsghdr<T, P>>, NlError<T, P>>) {}
 
 /// The details of the interface which can be obtained with the
 /// `CanInterface::details()` function.
@@ -123,6 +184,29 @@ impl InterfaceDetails {
     }
 }
 
+/// A snapshot of the details and statistics of an interface.
+///
+/// This is obtained with the `CanInterface::snapshot()` function, which
+/// gets everything from a single netlink query, rather than one query for
+/// each value.
+#[allow(missing_copy_implementations)]
+#[derive(Debug, Default, Clone)]
+pub struct InterfaceSnapshot {
+    /// The details of the interface, including the CAN parameters
+    pub details: InterfaceDetails,
+    /// The CAN device statistics, if reported by the driver
+    pub stats: Option<CanDeviceStats>,
+}
+
+impl InterfaceSnapshot {
+    /// Gets the bus error counters, if reported by the driver.
+    ///
+    /// These are part of the CAN parameters in the details.
+    pub fn berr_counter(&self) -> Option<CanBerrCounter> {
+        self.details.can.berr_counter
+    }
+}
+
 /// The MTU size for the interface
 ///
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -146,9 +230,126 @@ impl TryFrom<u32> for Mtu {
     }
 }
 
+/// The CAN protocol that an interface is configured to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProtocolMode {
+    /// Classic CAN 2.0, with up to 8 bytes of data
+    Classic,
+    /// ISO CAN FD, with up to 64 bytes of data
+    Fd,
+    /// The original, non-ISO (Bosch) version of CAN FD
+    FdNonIso,
+}
+
+/// The kind of a network interface, as given to the kernel when creating
+/// one.
+///
+/// This can be created from the name of the kind, like "vcan", so that a
+/// `&str` can be used wherever an `InterfaceKind` is expected.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InterfaceKind {
+    /// A virtual CAN interface, "vcan"
+    Vcan,
+    /// A virtual CAN tunnel, "vxcan", which is created as a pair of peers
+    Vxcan,
+    /// A CAN controller, "can"
+    Can,
+    /// Any other kind of interface, by name
+    Other(String),
+}
+
+impl InterfaceKind {
+    /// Gets the name of the kind, as used by the kernel.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Vcan => "vcan",
+            Self::Vxcan => "vxcan",
+            Self::Can => "can",
+            Self::Other(kind) => kind,
+        }
+    }
+}
+
+impl From<&str> for InterfaceKind {
+    fn from(kind: &str) -> Self {
+        match kind {
+            "vcan" => Self::Vcan,
+            "vxcan" => Self::Vxcan,
+            "can" => Self::Can,
+            kind => Self::Other(kind.into()),
+        }
+    }
+}
+
+impl From<String> for InterfaceKind {
+    fn from(kind: String) -> Self {
+        Self::from(kind.as_str())
+    }
+}
+
+impl From<&String> for InterfaceKind {
+    fn from(kind: &String) -> Self {
+        Self::from(kind.as_str())
+    }
+}
+
+impl fmt::Display for InterfaceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// CAN FD Transmitter Delay Compensation (TDC) parameters.
+///
+/// At high data bit rates, the delay through the transceiver can be
+/// longer than a bit time, so FD controllers compensate for it when
+/// checking the transmitted bits. All values are in time quanta of the
+/// controller clock.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CanTdc {
+    /// Transmitter Delay Compensation Value
+    pub tdcv: u32,
+    /// Transmitter Delay Compensation Offset
+    pub tdco: u32,
+    /// Transmitter Delay Compensation Filter window
+    pub tdcf: u32,
+}
+
+impl TryFrom<&Rtattr<IflaCan, Buffer>> for CanTdc {
+    type Error = NlInfoError;
+
+    /// Try to parse the TDC parameters out of a nested Tdc attribute
+    fn try_from(attr: &Rtattr<IflaCan, Buffer>) -> Result<Self, Self::Error> {
+        let mut tdc = Self::default();
+
+        for tdc_attr in attr.get_attr_handle::<IflaCanTdc>()?.get_attrs() {
+            match tdc_attr.rta_type {
+                IflaCanTdc::Tdcv => tdc.tdcv = tdc_attr.get_payload_as::<u32>()?,
+                IflaCanTdc::Tdco => tdc.tdco = tdc_attr.get_payload_as::<u32>()?,
+                IflaCanTdc::Tdcf => tdc.tdcf = tdc_attr.get_payload_as::<u32>()?,
+                _ => (),
+            }
+        }
+        Ok(tdc)
+    }
+}
+
+impl TryFrom<&CanTdc> for Rtattr<IflaCan, Buffer> {
+    type Error = NlError;
+
+    /// Try to build a nested Tdc attribute from the TDC parameters
+    fn try_from(tdc: &CanTdc) -> Result<Self, Self::Error> {
+        let mut attr = Rtattr::new(None, IflaCan::Tdc, Buffer::new())?;
+        attr.add_nested_attribute(&Rtattr::new(None, IflaCanTdc::Tdcv, tdc.tdcv)?)?;
+        attr.add_nested_attribute(&Rtattr::new(None, IflaCanTdc::Tdco, tdc.tdco)?)?;
+        attr.add_nested_attribute(&Rtattr::new(None, IflaCanTdc::Tdcf, tdc.tdcf)?)?;
+        Ok(attr)
+    }
+}
+
 /// The CAN-specific parameters for the interface.
 #[allow(missing_copy_implementations)]
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct InterfaceCanParams {
     /// The CAN bit timing parameters
     pub bit_timing: Option<CanBitTiming>,
@@ -171,6 +372,134 @@ pub struct InterfaceCanParams {
     pub data_bit_timing_const: Option<CanBitTimingConst>,
     /// The CANbus termination resistance
     pub termination: Option<u16>,
+    /// The FD transmitter delay compensation
+    pub tdc: Option<CanTdc>,
+}
+
+impl InterfaceCanParams {
+    /// Gets the bit rate, in bits per second.
+    pub fn bit_rate(&self) -> Option<u32> {
+        self.bit_timing.map(|timing| timing.bitrate)
+    }
+
+    /// Gets the sample point, in tenths of a percent.
+    pub fn sample_point(&self) -> Option<u32> {
+        self.bit_timing.map(|timing| timing.sample_point)
+    }
+
+    /// Gets the FD data bit rate, in bits per second.
+    pub fn data_bit_rate(&self) -> Option<u32> {
+        self.data_bit_timing.map(|timing| timing.bitrate)
+    }
+
+    /// Gets the frequency of the CAN controller clock, in Hz.
+    pub fn clock_freq(&self) -> Option<u32> {
+        self.clock.map(|clk| clk.freq)
+    }
+
+    /// Gets the automatic restart time, in milliseconds.
+    ///
+    /// This returns `None` if automatic restart is disabled.
+    pub fn auto_restart(&self) -> Option<u32> {
+        self.restart_ms.filter(|&ms| ms != 0)
+    }
+
+    /// Gets the bus error counters as a `(txerr, rxerr)` tuple.
+    ///
+    /// This is `None` for interfaces, like vcan, that don't report them.
+    pub fn error_counters(&self) -> Option<(u16, u16)> {
+        self.berr_counter.map(|cnt| (cnt.txerr, cnt.rxerr))
+    }
+
+    /// Compares these parameters against `other` and reports each field
+    /// that differs.
+    ///
+    /// This treats `self` as the current settings and `other` as the
+    /// desired ones, so it can be used to show what would change before
+    /// actually applying a new configuration to an interface. Fields that
+    /// are the same in both are not reported.
+    pub fn diff(&self, other: &Self) -> Vec<ParamDiff> {
+        let mut diffs = Vec::new();
+        ParamDiff::push(
+            &mut diffs,
+            "bit_timing",
+            &self.bit_timing,
+            &other.bit_timing,
+        );
+        ParamDiff::push(
+            &mut diffs,
+            "bit_timing_const",
+            &self.bit_timing_const,
+            &other.bit_timing_const,
+        );
+        ParamDiff::push(&mut diffs, "clock", &self.clock, &other.clock);
+        ParamDiff::push(&mut diffs, "state", &self.state, &other.state);
+        ParamDiff::push(
+            &mut diffs,
+            "restart_ms",
+            &self.restart_ms,
+            &other.restart_ms,
+        );
+        ParamDiff::push(
+            &mut diffs,
+            "berr_counter",
+            &self.berr_counter,
+            &other.berr_counter,
+        );
+        ParamDiff::push(&mut diffs, "ctrl_mode", &self.ctrl_mode, &other.ctrl_mode);
+        ParamDiff::push(
+            &mut diffs,
+            "data_bit_timing",
+            &self.data_bit_timing,
+            &other.data_bit_timing,
+        );
+        ParamDiff::push(
+            &mut diffs,
+            "data_bit_timing_const",
+            &self.data_bit_timing_const,
+            &other.data_bit_timing_const,
+        );
+        ParamDiff::push(
+            &mut diffs,
+            "termination",
+            &self.termination,
+            &other.termination,
+        );
+        ParamDiff::push(&mut diffs, "tdc", &self.tdc, &other.tdc);
+        diffs
+    }
+}
+
+/// A single field that differs between two sets of [InterfaceCanParams].
+///
+/// The values are the debug representation of the field, or `None` if
+/// the field was not set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParamDiff {
+    /// The name of the field that differs
+    pub field: &'static str,
+    /// The old (current) value
+    pub old: Option<String>,
+    /// The new (desired) value
+    pub new: Option<String>,
+}
+
+impl ParamDiff {
+    // Adds a diff for the field to the list, if the values differ.
+    fn push<T: PartialEq + Debug>(
+        diffs: &mut Vec<Self>,
+        field: &'static str,
+        old: &Option<T>,
+        new: &Option<T>,
+    ) {
+        if old != new {
+            diffs.push(Self {
+                field,
+                old: old.as_ref().map(|v| format!("{:?}", v)),
+                new: new.as_ref().map(|v| format!("{:?}", v)),
+            });
+        }
+    }
 }
 
 impl TryFrom<&Rtattr<Ifla, Buffer>> for InterfaceCanParams {
@@ -217,6 +546,9 @@ impl TryFrom<&Rtattr<Ifla, Buffer>> for InterfaceCanParams {
                         IflaCan::Termination => {
                             params.termination = Some(attr.get_payload_as::<u16>()?);
                         }
+                        IflaCan::Tdc => {
+                            params.tdc = Some(CanTdc::try_from(attr)?);
+                        }
                         _ => (),
                     }
                 }
@@ -305,7 +637,7 @@ impl CanCtrlMode {
 }
 
 /// The collection of control modes
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct CanCtrlModes(can_ctrlmode);
 
 impl CanCtrlModes {
@@ -384,8 +716,11 @@ impl From<CanCtrlModes> for can_ctrlmode {
 /// Some actions possible on this interface require the process/user to have
 /// the `CAP_NET_ADMIN` capability, like the root user does. This is
 /// indicated by their documentation starting with "PRIVILEGED:".
-#[allow(missing_copy_implementations)]
-#[derive(Debug)]
+///
+/// The interface object is just a lightweight handle, holding the index of
+/// the interface. It can be freely cloned or copied to share between
+/// threads or tasks, as that doesn't duplicate any kernel resource.
+#[derive(Debug, Clone, Copy)]
 pub struct CanInterface {
     if_index: c_uint,
 }
@@ -454,14 +789,18 @@ impl CanInterface {
         T: NlType + Debug,
         P: ToBytes + Debug,
     {
+        trace_request(&msg);
         sock.send(msg)?;
 
+        let resp = sock.recv();
+        trace_response(&resp);
+
         // This will actually produce an Err if the response is a netlink error,
         // no need to match.
         if let Some(Nlmsghdr {
             nl_payload: NlPayload::Ack(_),
             ..
-        }) = sock.recv()?
+        }) = resp?
         {
             Ok(())
         } else {
@@ -502,8 +841,12 @@ impl CanInterface {
             NlPayload::Payload(info),
         );
 
+        trace_request(&hdr);
         sock.send(hdr)?;
-        sock.recv::<'_, Rtm, Ifinfomsg>()
+
+        let resp = sock.recv::<'_, Rtm, Ifinfomsg>();
+        trace_response(&resp);
+        resp
     }
 
     /// Bring down this interface.
@@ -544,30 +887,57 @@ impl CanInterface {
     /// PRIVILEGED: This requires root privilege.
     ///
     pub fn create_vcan(name: &str, index: Option<u32>) -> NlResult<Self> {
-        Self::create(name, index, "vcan")
+        Self::create(name, index, InterfaceKind::Vcan)
     }
 
     /// Create an interface of the given kind.
     ///
+    /// The kind can be given as an [`InterfaceKind`], or by name, like
+    /// "vcan".
+    ///
     /// Note that the length of the name is capped by ```libc::IFNAMSIZ```.
     ///
     /// PRIVILEGED: This requires root privilege.
     ///
-    pub fn create<I>(name: &str, index: I, kind: &str) -> NlResult<Self>
+    pub fn create<I, K>(name: &str, index: I, kind: K) -> NlResult<Self>
     where
         I: Into<Option<u32>>,
+        K: Into<InterfaceKind>,
     {
+        Self::create_with_flags(name, index.into(), kind.into().as_str(), &[])
+    }
+
+    /// Create a virtual CAN (VCAN) interface that is already up.
+    ///
+    /// The interface is created and brought up with a single netlink
+    /// request, so it is never seen in the down state, and there is
+    /// nothing to clean up if the request fails.
+    ///
+    /// Note that the length of the name is capped by ```libc::IFNAMSIZ```.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub fn create_vcan_up(name: &str, index: Option<u32>) -> NlResult<Self> {
+        Self::create_with_flags(name, index, InterfaceKind::Vcan.as_str(), &[Iff::Up])
+    }
+
+    // Create an interface of the given kind, with the specified interface
+    // flags applied when it is created.
+    fn create_with_flags(
+        name: &str,
+        index: Option<u32>,
+        kind: &str,
+        flags: &[Iff],
+    ) -> NlResult<Self> {
         if name.len() > libc::IFNAMSIZ {
             return Err(NlError::Msg("Interface name too long".into()));
         }
-        let index = index.into();
-
         let info = Ifinfomsg::new(
             RtAddrFamily::Unspecified,
             Arphrd::Netrom,
             index.unwrap_or(0) as c_int,
-            IffFlags::empty(),
-            IffFlags::empty(),
+            IffFlags::new(flags),
+            IffFlags::new(flags),
             {
                 let mut buffer = RtBuffer::new();
                 buffer.push(Rtattr::new(None, Ifla::Ifname, name)?);
@@ -577,12 +947,13 @@ impl CanInterface {
                 buffer
             },
         );
-        Self::send_info_msg(Rtm::Newlink, info, &[NlmF::Create, NlmF::Excl])?;
+        let echo_index = Self::send_create_msg(info)?;
 
-        if let Some(if_index) = index {
+        if let Some(if_index) = index.or(echo_index) {
             Ok(Self { if_index })
         } else {
-            // Unfortunately netlink does not return the the if_index assigned to the interface.
+            // Older kernels don't echo the new link, so the if_index assigned
+            // to the interface has to be looked up by name.
             if let Ok(if_index) = if_nametoindex(name) {
                 Ok(Self { if_index })
             } else {
@@ -594,6 +965,123 @@ impl CanInterface {
         }
     }
 
+    // Sends a request to create a new link, asking the kernel to echo the
+    // new link back to us. This returns the index assigned to the link, if
+    // the kernel sent the echo before the ACK.
+    fn send_create_msg(info: Ifinfomsg) -> NlResult<Option<u32>> {
+        let mut nl = Self::open_route_socket()?;
+
+        let hdr = Nlmsghdr::new(
+            None,
+            Rtm::Newlink,
+            NlmFFlags::new(&[
+                NlmF::Request,
+                NlmF::Ack,
+                NlmF::Create,
+                NlmF::Excl,
+                NlmF::Echo,
+            ]),
+            None,
+            None,
+            NlPayload::Payload(info),
+        );
+
+        trace_request(&hdr);
+        nl.send(hdr)?;
+
+        let mut if_index = None;
+        loop {
+            let resp = nl.recv::<'_, u16, Buffer>();
+            trace_response(&resp);
+
+            match resp? {
+                Some(Nlmsghdr {
+                    nl_type,
+                    nl_payload: NlPayload::Payload(buf),
+                    ..
+                }) if nl_type == u16::from(Rtm::Newlink) => {
+                    let info = Ifinfomsg::from_bytes_with_input(
+                        &mut Cursor::new(buf.as_ref()),
+                        buf.len(),
+                    )?;
+                    if_index = u32::try_from(info.ifi_index).ok().filter(|&i| i != 0);
+                }
+                Some(Nlmsghdr {
+                    nl_payload: NlPayload::Ack(_),
+                    ..
+                }) => return Ok(if_index),
+                _ => return Err(NlError::NoAck),
+            }
+        }
+    }
+
+    /// Create a pair of virtual CAN tunnel (vxcan) interfaces.
+    ///
+    /// The two interfaces are connected to each other, so that frames
+    /// sent on one are received on the other. This is useful to connect
+    /// applications in different network namespaces. Deleting either of
+    /// the interfaces removes both of them.
+    ///
+    /// Note that the length of the names is capped by ```libc::IFNAMSIZ```.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub fn create_vxcan_pair(name_a: &str, name_b: &str) -> NlResult<(Self, Self)> {
+        if name_a.len() > libc::IFNAMSIZ || name_b.len() > libc::IFNAMSIZ {
+            return Err(NlError::Msg("Interface name too long".into()));
+        }
+
+        // The peer is described by a full ifinfomsg, nested in the info data
+        let peer_info = Ifinfomsg::new(
+            RtAddrFamily::Unspecified,
+            Arphrd::Netrom,
+            0,
+            IffFlags::empty(),
+            IffFlags::empty(),
+            {
+                let mut buffer = RtBuffer::new();
+                buffer.push(Rtattr::new(None, Ifla::Ifname, name_b)?);
+                buffer
+            },
+        );
+        let mut peer_buf = Cursor::new(Vec::new());
+        peer_info.to_bytes(&mut peer_buf)?;
+
+        let info = Ifinfomsg::new(
+            RtAddrFamily::Unspecified,
+            Arphrd::Netrom,
+            0,
+            IffFlags::empty(),
+            IffFlags::empty(),
+            {
+                let mut buffer = RtBuffer::new();
+                buffer.push(Rtattr::new(None, Ifla::Ifname, name_a)?);
+                let mut linkinfo = Rtattr::new(None, Ifla::Linkinfo, Vec::<u8>::new())?;
+                linkinfo.add_nested_attribute(&Rtattr::new(None, IflaInfo::Kind, "vxcan")?)?;
+                let mut data = Rtattr::new(None, IflaInfo::Data, Buffer::new())?;
+                data.add_nested_attribute(&Rtattr::new(
+                    None,
+                    IflaVxcan::Peer,
+                    Buffer::from(peer_buf.into_inner()),
+                )?)?;
+                linkinfo.add_nested_attribute(&data)?;
+                buffer.push(linkinfo);
+                buffer
+            },
+        );
+        Self::send_info_msg(Rtm::Newlink, info, &[NlmF::Create, NlmF::Excl])?;
+
+        // Netlink doesn't return the indexes assigned to the interfaces.
+        match (if_nametoindex(name_a), if_nametoindex(name_b)) {
+            (Ok(index_a), Ok(index_b)) => {
+                Ok((Self::open_iface(index_a), Self::open_iface(index_b)))
+            }
+            _ => Err(NlError::Msg(
+                "Interfaces must have been deleted between request and this if_nametoindex".into(),
+            )),
+        }
+    }
+
     /// Delete the interface.
     ///
     /// PRIVILEGED: This requires root privilege.
@@ -608,9 +1096,32 @@ impl CanInterface {
 
     /// Attempt to query detailed information on the interface.
     pub fn details(&self) -> Result<InterfaceDetails, NlInfoError> {
+        self.snapshot().map(|snapshot| snapshot.details)
+    }
+
+    /// Gets all the CAN parameters of the interface in a single netlink
+    /// query.
+    ///
+    /// Each of the individual getters, like `bit_rate()` or `state()`,
+    /// does its own query. When reading several parameters, it's more
+    /// efficient to get them all at once with this, and then read the
+    /// values from the returned struct.
+    pub fn params(&self) -> Result<InterfaceCanParams, NlInfoError> {
+        self.details().map(|details| details.can)
+    }
+
+    /// Gets the details and statistics of the interface in a single
+    /// netlink query.
+    ///
+    /// This is more efficient than calling the individual getters, each of
+    /// which does its own query, when monitoring many values or many
+    /// interfaces.
+    pub fn snapshot(&self) -> Result<InterfaceSnapshot, NlInfoError> {
         match self.query_details()? {
             Some(msg_hdr) => {
-                let mut info = InterfaceDetails::new(self.if_index);
+                let mut snapshot = InterfaceSnapshot::default();
+                let info = &mut snapshot.details;
+                info.index = self.if_index;
 
                 if let Ok(payload) = msg_hdr.get_payload() {
                     info.is_up = payload.ifi_flags.contains(&Iff::Up);
@@ -631,18 +1142,110 @@ impl CanInterface {
                             }
                             Ifla::Linkinfo => {
                                 info.can = InterfaceCanParams::try_from(attr)?;
+                                for info_attr in attr.get_attr_handle::<IflaInfo>()?.get_attrs() {
+                                    if info_attr.rta_type == IflaInfo::Xstats {
+                                        snapshot.stats =
+                                            Some(info_attr.get_payload_as::<CanDeviceStats>()?);
+                                    }
+                                }
                             }
                             _ => (),
                         }
                     }
                 }
 
-                Ok(info)
+                Ok(snapshot)
             }
             None => Err(NlError::NoAck),
         }
     }
 
+    /// Gets the CAN device statistics for the interface.
+    ///
+    /// These are the counts of the bus errors and state changes reported
+    /// by the driver. This returns `None` for interfaces, like vcan, that
+    /// don't report them.
+    pub fn device_stats(&self) -> Result<Option<CanDeviceStats>, NlInfoError> {
+        Ok(self.snapshot()?.stats)
+    }
+
+    /// Gets the 64-bit statistics for the interface.
+    ///
+    /// These are the general network counters, such as the number of
+    /// frames and bytes sent and received, and the number dropped. Unlike
+    /// the 32-bit counters, these won't wrap on a busy bus in a
+    /// long-running application. This returns `None` if the kernel doesn't
+    /// report them.
+    pub fn link_stats64(&self) -> Result<Option<LinkStats64>, NlInfoError> {
+        match self.query_details()? {
+            Some(msg_hdr) => Ok(msg_hdr.get_payload().ok().and_then(|payload| {
+                payload
+                    .rtattrs
+                    .iter()
+                    .find(|attr| attr.rta_type == Ifla::Stats64)
+                    .map(|attr| LinkStats64::from_payload(attr.rta_payload.as_ref()))
+            })),
+            None => Err(NlError::NoAck),
+        }
+    }
+
+    /// Determines if the interface is configured for CAN FD frames.
+    ///
+    /// This is true if the interface has the FD MTU, or if the FD control
+    /// mode is enabled on a CAN controller. A `CanFdSocket` can be opened
+    /// on any interface, but FD frames can only be sent on one that
+    /// supports them.
+    pub fn supports_fd(&self) -> Result<bool, NlInfoError> {
+        Ok(self.protocol_mode()? != ProtocolMode::Classic)
+    }
+
+    /// Gets the CAN protocol that the interface is configured to use.
+    ///
+    /// For a CAN controller, this comes from the FD and non-ISO control
+    /// modes. An interface without control modes, like a vcan, is in FD
+    /// mode if it has the FD MTU.
+    pub fn protocol_mode(&self) -> Result<ProtocolMode, NlInfoError> {
+        let details = self.details()?;
+        let modes = details.can.ctrl_mode.unwrap_or_default();
+
+        let mode = if modes.has_mode(CanCtrlMode::Fd) {
+            match modes.has_mode(CanCtrlMode::NonIso) {
+                true => ProtocolMode::FdNonIso,
+                false => ProtocolMode::Fd,
+            }
+        } else if details.mtu == Some(Mtu::Fd) {
+            ProtocolMode::Fd
+        } else {
+            ProtocolMode::Classic
+        };
+        Ok(mode)
+    }
+
+    /// Gets the set of control modes that the controller supports.
+    ///
+    /// This is reported by the kernel in the extended control mode
+    /// attribute, and can be used to determine which modes can be turned
+    /// on before trying, rather than getting an `EOPNOTSUPP` error from
+    /// the attempt. Each supported mode is on in the returned set, so it
+    /// can be checked with [`CanCtrlModes::has_mode`].
+    ///
+    /// This returns `None` for an interface that doesn't report it, such
+    /// as a virtual vcan interface, or with older kernels.
+    pub fn supported_ctrlmodes(&self) -> Result<Option<CanCtrlModes>, NlInfoError> {
+        let supported = self.can_attr(IflaCan::CtrlModeExt, |attr| {
+            let mut supported = None;
+            for ext_attr in attr.get_attr_handle::<IflaCanCtrlModeExt>()?.get_attrs() {
+                if ext_attr.rta_type == IflaCanCtrlModeExt::Supported {
+                    supported = Some(ext_attr.get_payload_as::<u32>()?);
+                }
+            }
+            Ok(supported)
+        })?;
+        Ok(supported
+            .flatten()
+            .map(|modes| CanCtrlModes::new(modes, modes)))
+    }
+
     /// Set the MTU of this interface.
     ///
     /// PRIVILEGED: This requires root privilege.
@@ -657,6 +1260,43 @@ impl CanInterface {
         Self::send_info_msg(Rtm::Newlink, info, &[])
     }
 
+    /// Gets the alias of the interface, if one is set.
+    ///
+    /// The alias is a free-form label, such as "engine-bus", that can be
+    /// attached to any network interface for documentation.
+    pub fn alias(&self) -> Result<Option<String>, NlInfoError> {
+        match self.query_details()? {
+            Some(msg_hdr) => Ok(msg_hdr.get_payload().ok().and_then(|payload| {
+                payload
+                    .rtattrs
+                    .iter()
+                    .find(|attr| attr.rta_type == Ifla::Ifalias)
+                    .map(|attr| {
+                        let alias = attr.rta_payload.as_ref();
+                        let len = alias.iter().position(|&b| b == 0).unwrap_or(alias.len());
+                        String::from_utf8_lossy(&alias[..len]).into_owned()
+                    })
+                    .filter(|alias| !alias.is_empty())
+            })),
+            None => Err(NlError::NoAck),
+        }
+    }
+
+    /// Sets the alias of the interface.
+    ///
+    /// The alias can be up to 255 bytes. An empty string clears it.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub fn set_alias(&self, alias: &str) -> NlResult<()> {
+        let info = self.info_msg({
+            let mut buffer = RtBuffer::new();
+            buffer.push(Rtattr::new(None, Ifla::Ifalias, alias)?);
+            buffer
+        });
+        Self::send_info_msg(Rtm::Newlink, info, &[])
+    }
+
     /// Set a CAN-specific parameter.
     ///
     /// This send a netlink message down to the kernel to set an attribute
@@ -743,6 +1383,15 @@ impl CanInterface {
     pub fn can_param<P>(&self, param: IflaCan) -> Result<Option<P>, NlInfoError>
     where
         P: for<'a> FromBytes<'a> + Clone,
+    {
+        self.can_attr(param, |attr| Ok(attr.get_payload_as::<P>()?))
+    }
+
+    // Queries an individual CAN attribute on the interface, and decodes it
+    // with the function, `f`. This is used directly for nested attributes.
+    fn can_attr<T, F>(&self, param: IflaCan, f: F) -> Result<Option<T>, NlInfoError>
+    where
+        F: FnOnce(&Rtattr<IflaCan, Buffer>) -> Result<T, NlInfoError>,
     {
         if let Some(hdr) = self.query_details()? {
             if let Ok(payload) = hdr.get_payload() {
@@ -752,7 +1401,7 @@ impl CanInterface {
                             if info.rta_type == IflaInfo::Data {
                                 for attr in info.get_attr_handle::<IflaCan>()?.get_attrs() {
                                     if attr.rta_type == param {
-                                        return Ok(Some(attr.get_payload_as::<P>()?));
+                                        return f(attr).map(Some);
                                     }
                                 }
                             }
@@ -816,6 +1465,97 @@ impl CanInterface {
         self.set_can_param(IflaCan::BitTiming, timing)
     }
 
+    /// Sets the sample point for the interface, keeping the current
+    /// bitrate.
+    ///
+    /// The sample point is in tenths of a percent, so 875 is 87.5%. This
+    /// reads the current bit timing and sends back the same bitrate with
+    /// the new sample point, letting the kernel recompute the bit segments
+    /// to match. The bitrate must already have been set.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub fn set_sample_point(&self, sample_point: u32) -> NlResult<()> {
+        if sample_point >= 1000 {
+            return Err(NlError::Msg(format!(
+                "Sample point must be within 0..1000, received {}.",
+                sample_point
+            )));
+        }
+
+        let bitrate = self
+            .bit_timing()
+            .map_err(|err| NlError::Msg(err.to_string()))?
+            .map(|bt| bt.bitrate)
+            .filter(|&bitrate| bitrate != 0)
+            .ok_or_else(|| NlError::Msg("The interface has no bitrate set".into()))?;
+
+        self.set_bit_timing(CanBitTiming {
+            bitrate,
+            sample_point,
+            ..CanBitTiming::default()
+        })
+    }
+
+    /// Sets the bit timing for the interface from the builtin table of
+    /// precomputed timings.
+    ///
+    /// This reads the clock frequency of the interface and looks up the
+    /// segments for the bitrate in [`BitTimingTable::builtin()`], rather
+    /// than relying on the kernel to compute them. An error is returned if
+    /// the interface doesn't report a clock, or the table has no entry
+    /// for the clock and bitrate.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub fn set_bitrate_from_table(&self, bitrate: u32) -> NlResult<()> {
+        self.set_bitrate_with_table(&BitTimingTable::builtin(), bitrate)
+    }
+
+    /// Sets the bit timing for the interface from a table of precomputed
+    /// timings.
+    ///
+    /// This is the same as [`set_bitrate_from_table`][Self::set_bitrate_from_table],
+    /// but with a table supplied by the application, such as one with the
+    /// segments recommended for a specific controller.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub fn set_bitrate_with_table(&self, table: &BitTimingTable, bitrate: u32) -> NlResult<()> {
+        let clock = self
+            .clock()
+            .map_err(|err| NlError::Msg(err.to_string()))?
+            .filter(|&clk| clk != 0)
+            .ok_or_else(|| NlError::Msg("The interface has no clock frequency".into()))?;
+
+        let timing = table.get(clock, bitrate).ok_or_else(|| {
+            NlError::Msg(format!(
+                "No bit timing for {} bps with a {} Hz clock",
+                bitrate, clock
+            ))
+        })?;
+
+        // The kernel needs the time quantum, in ns, when the bitrate is
+        // left out, so compute it from the prescaler for a user-built entry.
+        let tq = match (timing.tq, timing.brp) {
+            (0, 0) => {
+                return Err(NlError::Msg(
+                    "The bit timing has neither a time quantum nor a prescaler".into(),
+                ))
+            }
+            (0, brp) => (u64::from(brp) * 1_000_000_000 / u64::from(clock)) as u32,
+            (tq, _) => tq,
+        };
+
+        // The kernel only accepts the segments if the bitrate is left out
+        self.set_bit_timing(CanBitTiming {
+            bitrate: 0,
+            sample_point: 0,
+            tq,
+            ..timing
+        })
+    }
+
     /// Gets the bit timing const data for the interface
     pub fn bit_timing_const(&self) -> Result<Option<CanBitTimingConst>, NlInfoError> {
         self.can_param::<CanBitTimingConst>(IflaCan::BitTimingConst)
@@ -865,11 +1605,51 @@ impl CanInterface {
         self.set_ctrlmodes(CanCtrlModes::from_mode(mode, on))
     }
 
-    /// Gets the automatic CANbus restart time for the interface, in milliseconds.
-    pub fn restart_ms(&self) -> Result<Option<u32>, NlInfoError> {
-        self.can_param::<u32>(IflaCan::RestartMs)
-    }
-
+    /// Set or clear the loopback control mode.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub fn set_loopback(&self, on: bool) -> NlResult<()> {
+        self.set_ctrlmode(CanCtrlMode::Loopback, on)
+    }
+
+    /// Set or clear the listen-only control mode.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub fn set_listen_only(&self, on: bool) -> NlResult<()> {
+        self.set_ctrlmode(CanCtrlMode::ListenOnly, on)
+    }
+
+    /// Set or clear the one-shot control mode.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub fn set_one_shot(&self, on: bool) -> NlResult<()> {
+        self.set_ctrlmode(CanCtrlMode::OneShot, on)
+    }
+
+    /// Set or clear the bus-error reporting control mode.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub fn set_berr_reporting(&self, on: bool) -> NlResult<()> {
+        self.set_ctrlmode(CanCtrlMode::BerrReporting, on)
+    }
+
+    /// Set or clear the CAN FD control mode.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub fn set_fd(&self, on: bool) -> NlResult<()> {
+        self.set_ctrlmode(CanCtrlMode::Fd, on)
+    }
+
+    /// Gets the automatic CANbus restart time for the interface, in milliseconds.
+    pub fn restart_ms(&self) -> Result<Option<u32>, NlInfoError> {
+        self.can_param::<u32>(IflaCan::RestartMs)
+    }
+
     /// Set the automatic restart milliseconds of the interface
     ///
     /// PRIVILEGED: This requires root privilege.
@@ -878,6 +1658,26 @@ impl CanInterface {
         self.set_can_param(IflaCan::RestartMs, &restart_ms.to_ne_bytes()[..])
     }
 
+    /// Gets the automatic restart time of the interface, in milliseconds.
+    ///
+    /// This returns `None` if automatic restart is disabled, or if the
+    /// parameter is not available for the interface.
+    pub fn auto_restart(&self) -> Result<Option<u32>, NlInfoError> {
+        Ok(self.restart_ms()?.filter(|&ms| ms != 0))
+    }
+
+    /// Enables or disables automatic restart of the interface.
+    ///
+    /// With `Some(ms)`, the controller is restarted automatically `ms`
+    /// milliseconds after going bus-off. With `None`, automatic restart is
+    /// disabled, and the interface must be restarted manually.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub fn set_auto_restart(&self, ms: Option<u32>) -> NlResult<()> {
+        self.set_restart_ms(ms.unwrap_or(0))
+    }
+
     /// Manually restart the interface.
     ///
     /// Note that a manual restart if only permitted if automatic restart is
@@ -962,10 +1762,386 @@ impl CanInterface {
     pub fn termination(&self) -> Result<Option<u16>, NlInfoError> {
         self.can_param::<u16>(IflaCan::Termination)
     }
+
+    /// Brings the interface up for the lifetime of the returned guard.
+    ///
+    /// When the guard is dropped, the interface is brought back down, unless
+    /// it was already up when the guard was created, in which case it is
+    /// left up.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub fn up_guard(&self) -> NlResult<LinkUpGuard<'_>> {
+        let was_up = self
+            .details()
+            .map_err(|err| NlError::Msg(err.to_string()))?
+            .is_up;
+
+        if !was_up {
+            self.bring_up()?;
+        }
+        Ok(LinkUpGuard {
+            iface: self,
+            was_up,
+        })
+    }
+
+    /// Gets the FD transmitter delay compensation (TDC) parameters.
+    ///
+    /// This returns `None` if the interface does not support TDC, or if it
+    /// is not enabled.
+    pub fn tdc(&self) -> Result<Option<CanTdc>, NlInfoError> {
+        // The kernel reports the TDC values even when it's disabled, so
+        // check the mode.
+        let can = self.details()?.can;
+        let enabled = can
+            .ctrl_mode
+            .is_some_and(|mode| mode.0.flags & rt::CAN_CTRLMODE_TDC_MASK != 0);
+        Ok(can.tdc.filter(|_| enabled))
+    }
+
+    /// Sets the FD transmitter delay compensation (TDC) parameters.
+    ///
+    /// This puts the controller into manual TDC mode, using the specified
+    /// values. The interface must be down and in FD mode, and the
+    /// controller must support TDC. Virtual interfaces like vcan do not.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub fn set_tdc(&self, tdc: CanTdc) -> NlResult<()> {
+        let ctrlmode = can_ctrlmode {
+            mask: rt::CAN_CTRLMODE_TDC_MASK,
+            flags: rt::CAN_CTRLMODE_TDC_MANUAL,
+        };
+
+        let info = self.info_msg({
+            // The kernel requires the TDC mode to be set along with the
+            // TDC parameters, in the same message.
+            let mut data = Rtattr::new(None, IflaInfo::Data, Buffer::new())?;
+            data.add_nested_attribute(&Rtattr::new(None, IflaCan::CtrlMode, ctrlmode)?)?;
+            data.add_nested_attribute(&Rtattr::<IflaCan, Buffer>::try_from(&tdc)?)?;
+
+            let mut link_info = Rtattr::new(None, Ifla::Linkinfo, Buffer::new())?;
+            link_info.add_nested_attribute(&Rtattr::new(None, IflaInfo::Kind, "can")?)?;
+            link_info.add_nested_attribute(&data)?;
+
+            let mut rtattrs = RtBuffer::new();
+            rtattrs.push(link_info);
+            rtattrs
+        });
+        Self::send_info_msg(Rtm::Newlink, info, &[])
+    }
+
+    /// Enables hardware timestamping on the interface.
+    ///
+    /// This issues a `SIOCSHWTSTAMP` ioctl to turn on hardware timestamps
+    /// for all transmitted frames, and for all received frames. This is
+    /// done through an ioctl rather than netlink, but is a property of the
+    /// interface rather than any one socket.
+    ///
+    /// Sockets still need to request hardware timestamps for themselves
+    /// through the `SO_TIMESTAMPING` socket option, with the
+    /// `SOF_TIMESTAMPING_RX_HARDWARE` and `SOF_TIMESTAMPING_RAW_HARDWARE`
+    /// flags.
+    ///
+    /// This requires support from the device driver, which most CAN
+    /// adapters do not have. Virtual interfaces like vcan do not support it
+    /// either. If the driver does not support hardware timestamps, this will
+    /// fail with an error of kind `Unsupported`.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub fn enable_hardware_timestamping(&self) -> std::io::Result<()> {
+        use std::io;
+
+        let mut config = libc::hwtstamp_config {
+            flags: 0,
+            tx_type: libc::HWTSTAMP_TX_ON as c_int,
+            rx_filter: libc::HWTSTAMP_FILTER_ALL as c_int,
+        };
+
+        let mut ifr: libc::ifreq = unsafe { std::mem::zeroed() };
+        if unsafe { libc::if_indextoname(self.if_index, ifr.ifr_name.as_mut_ptr()) }.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        ifr.ifr_ifru.ifru_data = &mut config as *mut _ as *mut libc::c_char;
+
+        let sock = socket2::Socket::new_raw(
+            socket2::Domain::from(libc::AF_CAN),
+            socket2::Type::RAW,
+            Some(socket2::Protocol::from(libc::CAN_RAW)),
+        )?;
+
+        let ret = unsafe { libc::ioctl(sock.as_raw_fd(), libc::SIOCSHWTSTAMP, &mut ifr) };
+
+        match ret {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+}
+
+/// An RAII guard that keeps an interface up.
+///
+/// This is created by [`CanInterface::up_guard()`]. When dropped, it brings
+/// the interface back down, unless the interface was already up when the
+/// guard was created.
+#[derive(Debug)]
+pub struct LinkUpGuard<'a> {
+    iface: &'a CanInterface,
+    was_up: bool,
+}
+
+impl LinkUpGuard<'_> {
+    /// Whether the interface was already up when the guard was created.
+    pub fn was_up(&self) -> bool {
+        self.was_up
+    }
+}
+
+impl Drop for LinkUpGuard<'_> {
+    fn drop(&mut self) {
+        if !self.was_up {
+            let _ = self.iface.bring_down();
+        }
+    }
+}
+
+// ===== BitTimingTable =====
+
+// The builtin timings as (clock, bitrate, brp, prop_seg, phase_seg1, phase_seg2).
+// These all use a sample point of 87.5%, with 16 time quanta per bit, or 8
+// where the clock can't be divided evenly into 16.
+const BUILTIN_BIT_TIMINGS: [(u32, u32, u32, u32, u32, u32); 20] = [
+    (8_000_000, 125_000, 4, 6, 7, 2),
+    (8_000_000, 250_000, 2, 6, 7, 2),
+    (8_000_000, 500_000, 1, 6, 7, 2),
+    (8_000_000, 1_000_000, 1, 3, 3, 1),
+    (16_000_000, 125_000, 8, 6, 7, 2),
+    (16_000_000, 250_000, 4, 6, 7, 2),
+    (16_000_000, 500_000, 2, 6, 7, 2),
+    (16_000_000, 1_000_000, 1, 6, 7, 2),
+    (24_000_000, 125_000, 12, 6, 7, 2),
+    (24_000_000, 250_000, 6, 6, 7, 2),
+    (24_000_000, 500_000, 3, 6, 7, 2),
+    (24_000_000, 1_000_000, 3, 3, 3, 1),
+    (40_000_000, 125_000, 20, 6, 7, 2),
+    (40_000_000, 250_000, 10, 6, 7, 2),
+    (40_000_000, 500_000, 5, 6, 7, 2),
+    (40_000_000, 1_000_000, 5, 3, 3, 1),
+    (80_000_000, 125_000, 40, 6, 7, 2),
+    (80_000_000, 250_000, 20, 6, 7, 2),
+    (80_000_000, 500_000, 10, 6, 7, 2),
+    (80_000_000, 1_000_000, 5, 6, 7, 2),
+];
+
+/// A table of precomputed bit timings, by controller clock and bitrate.
+///
+/// The kernel can compute the bit segments for a bitrate on its own, but
+/// the sample point that it chooses isn't always the one that a network
+/// requires. A table lets an application pin down the exact segments
+/// for each of the bitrates that it uses.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BitTimingTable {
+    /// The timings, each with the clock frequency that it applies to
+    entries: Vec<(u32, CanBitTiming)>,
+}
+
+impl BitTimingTable {
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a table with timings for the common controller clocks of
+    /// 8, 16, 24, 40, and 80 MHz, at the common bitrates of 125k, 250k,
+    /// 500k, and 1M bps.
+    ///
+    /// All of these have a sample point of 87.5%.
+    pub fn builtin() -> Self {
+        let mut table = Self::new();
+        for (clock, bitrate, brp, prop_seg, phase_seg1, phase_seg2) in BUILTIN_BIT_TIMINGS {
+            table.insert(
+                clock,
+                CanBitTiming {
+                    bitrate,
+                    sample_point: 875,
+                    tq: (1_000_000_000u64 * brp as u64 / clock as u64) as u32,
+                    prop_seg,
+                    phase_seg1,
+                    phase_seg2,
+                    sjw: 1,
+                    brp,
+                },
+            );
+        }
+        table
+    }
+
+    /// Adds a timing for a controller with a clock of `clock_hz`.
+    ///
+    /// The timing is keyed by its `bitrate` field, and replaces any
+    /// existing entry for the same clock and bitrate.
+    pub fn insert(&mut self, clock_hz: u32, timing: CanBitTiming) {
+        match self
+            .entries
+            .iter_mut()
+            .find(|(clock, bt)| *clock == clock_hz && bt.bitrate == timing.bitrate)
+        {
+            Some(entry) => entry.1 = timing,
+            None => self.entries.push((clock_hz, timing)),
+        }
+    }
+
+    /// Looks up the timing for the bitrate on a controller with a clock
+    /// of `clock_hz`.
+    pub fn get(&self, clock_hz: u32, bitrate: u32) -> Option<CanBitTiming> {
+        self.entries
+            .iter()
+            .find(|(clock, bt)| *clock == clock_hz && bt.bitrate == bitrate)
+            .map(|(_, bt)| *bt)
+    }
+
+    /// Gets the number of timings in the table.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Determines if the table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+// ===== Batch configuration =====
+
+/// A common configuration to apply to a number of CAN interfaces.
+///
+/// Any fields that are `None` are left unchanged on the interfaces.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CanConfig {
+    /// The bitrate, in bits per second
+    pub bitrate: Option<u32>,
+    /// The FD data bitrate, in bits per second
+    pub data_bitrate: Option<u32>,
+    /// The control modes
+    pub ctrl_modes: Option<CanCtrlModes>,
+    /// The automatic restart time (in millisec).
+    /// Zero disables auto-restart.
+    pub restart_ms: Option<u32>,
+}
+
+impl From<&CanConfig> for InterfaceCanParams {
+    fn from(cfg: &CanConfig) -> Self {
+        let timing = |bitrate| CanBitTiming {
+            bitrate,
+            ..CanBitTiming::default()
+        };
+
+        Self {
+            bit_timing: cfg.bitrate.map(timing),
+            data_bit_timing: cfg.data_bitrate.map(timing),
+            ctrl_mode: cfg.ctrl_modes,
+            restart_ms: cfg.restart_ms,
+            ..Self::default()
+        }
+    }
+}
+
+/// Applies the same configuration to every interface with a name that
+/// matches the `pattern`.
+///
+/// The pattern is matched against the whole name, where `*` matches any
+/// number of characters and `?` matches any single character, so `"can*"`
+/// matches `can0`, `can1`, and so on. The parameters are sent to each
+/// interface in a single netlink message, as with
+/// [`set_can_params`][CanInterface::set_can_params].
+///
+/// This returns the name of each interface that matched, along with the
+/// result of configuring it. An error on one interface doesn't stop the
+/// others from being configured. The interfaces must be down for the
+/// kernel to accept bit timing changes.
+///
+/// PRIVILEGED: This requires root privilege.
+///
+pub fn configure_all(pattern: &str, cfg: &CanConfig) -> NlResult<Vec<(String, NlResult<()>)>> {
+    let params = InterfaceCanParams::from(cfg);
+    let ifaces = nix::net::if_::if_nameindex().map_err(|err| NlError::Msg(err.to_string()))?;
+
+    Ok(ifaces
+        .iter()
+        .filter_map(|iface| {
+            let name = iface.name().to_string_lossy().into_owned();
+            name_matches(pattern, &name).then(|| {
+                let res = CanInterface::open_iface(iface.index()).set_can_params(&params);
+                (name, res)
+            })
+        })
+        .collect())
+}
+
+// Determines if the interface name matches a simple glob pattern.
+fn name_matches(pattern: &str, name: &str) -> bool {
+    fn matches(pat: &[u8], name: &[u8]) -> bool {
+        match (pat.split_first(), name.split_first()) {
+            (None, None) => true,
+            (Some((b'*', rest)), _) => {
+                matches(rest, name) || (!name.is_empty() && matches(pat, &name[1..]))
+            }
+            (Some((b'?', rest)), Some((_, name))) => matches(rest, name),
+            (Some((p, rest)), Some((c, name))) if p == c => matches(rest, name),
+            _ => false,
+        }
+    }
+    matches(pattern.as_bytes(), name.as_bytes())
 }
 
 /////////////////////////////////////////////////////////////////////////////
 
+/// Tests for tracing netlink messages
+#[cfg(feature = "netlink-trace")]
+#[cfg(test)]
+mod trace_tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// A logger that captures all the netlink trace messages.
+    struct CaptureLogger;
+
+    static CAPTURED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, _metadata: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            if record.target().starts_with("socketcan::nl") {
+                CAPTURED.lock().unwrap().push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CaptureLogger = CaptureLogger;
+
+    #[test]
+    fn trace_details() {
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+
+        // Any user can query the loopback interface.
+        let iface = CanInterface::open("lo").unwrap();
+        let details = iface.details().unwrap();
+        assert_eq!(Some("lo"), details.name.as_deref());
+
+        let captured = CAPTURED.lock().unwrap();
+        assert!(captured.iter().any(|s| s.starts_with("netlink request:")));
+        assert!(captured.iter().any(|s| s.starts_with("netlink response:")));
+    }
+}
+
 /// Netlink tests for SocketCAN control
 #[cfg(feature = "netlink_tests")]
 #[cfg(test)]
@@ -1020,6 +2196,16 @@ pub mod tests {
         }
     }
 
+    /// Gets a physical CAN interface to test, if one is named in the
+    /// environment variable `SOCKETCAN_HW_IFACE`.
+    ///
+    /// Some CAN parameters can't be set on a vcan interface, and are only
+    /// tested against real hardware.
+    fn hw_interface() -> Option<CanInterface> {
+        let name = std::env::var("SOCKETCAN_HW_IFACE").ok()?;
+        Some(CanInterface::open(&name).unwrap())
+    }
+
     #[test]
     #[serial]
     fn up_down() {
@@ -1032,6 +2218,238 @@ pub mod tests {
         assert!(!interface.details().unwrap().is_up);
     }
 
+    #[test]
+    #[serial]
+    fn clone() {
+        let interface = TemporaryInterface::new("clone").unwrap();
+        let iface_a = *interface;
+        let iface_b = iface_a;
+
+        let details_a = iface_a.details().unwrap();
+        let details_b = iface_b.details().unwrap();
+        assert_eq!(details_a.index, details_b.index);
+        assert_eq!(details_a.name.as_deref(), Some("clone"));
+        assert_eq!(details_a.name, details_b.name);
+    }
+
+    #[test]
+    #[serial]
+    fn create_vcan_up() {
+        let interface = CanInterface::create_vcan_up("create_up", None).unwrap();
+        assert!(interface.details().unwrap().is_up);
+        assert!(interface.delete().is_ok());
+    }
+
+    #[test]
+    #[serial]
+    fn up_guard() {
+        let interface = TemporaryInterface::new("up_guard").unwrap();
+        assert!(!interface.details().unwrap().is_up);
+
+        {
+            let guard = interface.up_guard().unwrap();
+            assert!(!guard.was_up());
+            assert!(interface.details().unwrap().is_up);
+        }
+        assert!(!interface.details().unwrap().is_up);
+
+        // An interface that's already up stays up
+        interface.bring_up().unwrap();
+        {
+            let guard = interface.up_guard().unwrap();
+            assert!(guard.was_up());
+        }
+        assert!(interface.details().unwrap().is_up);
+    }
+
+    #[test]
+    #[serial]
+    fn auto_restart() {
+        // A vcan has no CAN parameters
+        let interface = TemporaryInterface::new("auto_restart").unwrap();
+        assert_eq!(None, interface.auto_restart().unwrap());
+
+        if let Some(interface) = hw_interface() {
+            let _ = interface.bring_down();
+
+            interface.set_auto_restart(Some(100)).unwrap();
+            assert_eq!(Some(100), interface.auto_restart().unwrap());
+            assert_eq!(Some(100), interface.restart_ms().unwrap());
+
+            interface.set_auto_restart(None).unwrap();
+            assert_eq!(None, interface.auto_restart().unwrap());
+            assert_eq!(Some(0), interface.restart_ms().unwrap());
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn create_assigned_index() {
+        // Let the kernel assign the index, which should be usable right away
+        let interface = CanInterface::create_vcan("create_idx", None).unwrap();
+        assert_ne!(0, interface.if_index);
+
+        let details = interface.details().unwrap();
+        assert_eq!(interface.if_index, details.index);
+        assert_eq!(Some("create_idx".to_string()), details.name);
+        assert_eq!(interface.if_index, if_nametoindex("create_idx").unwrap());
+
+        assert!(interface.delete().is_ok());
+    }
+
+    #[test]
+    fn interface_kind() {
+        assert_eq!(InterfaceKind::Vcan, InterfaceKind::from("vcan"));
+        assert_eq!(InterfaceKind::Vxcan, InterfaceKind::from("vxcan"));
+        assert_eq!(InterfaceKind::Can, InterfaceKind::from("can"));
+        assert_eq!(
+            InterfaceKind::Other("dummy".into()),
+            InterfaceKind::from("dummy")
+        );
+        assert_eq!("vcan", InterfaceKind::Vcan.as_str());
+        assert_eq!(
+            "dummy",
+            InterfaceKind::from("dummy".to_string()).to_string()
+        );
+        assert_eq!(
+            InterfaceKind::Vcan,
+            InterfaceKind::from(&"vcan".to_string())
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn create_kind() {
+        let interface = CanInterface::create("create_kind", None, InterfaceKind::Vcan).unwrap();
+        assert_eq!(
+            Some("create_kind".to_string()),
+            interface.details().unwrap().name
+        );
+        assert!(interface.delete().is_ok());
+
+        // The kind can still be given by name
+        let interface = CanInterface::create("create_kind", None, "vcan").unwrap();
+        assert!(interface.delete().is_ok());
+    }
+
+    #[test]
+    #[serial]
+    fn vxcan_pair() {
+        let (a, b) = CanInterface::create_vxcan_pair("vxcan_test_a", "vxcan_test_b").unwrap();
+        assert_ne!(a.if_index, b.if_index);
+
+        assert_eq!(a.if_index, if_nametoindex("vxcan_test_a").unwrap());
+        assert_eq!(b.if_index, if_nametoindex("vxcan_test_b").unwrap());
+
+        // Deleting one side of the tunnel removes both
+        assert!(a.delete().is_ok());
+        assert!(if_nametoindex("vxcan_test_b").is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn ctrlmode_shortcuts() {
+        type Shortcut = fn(&CanInterface, bool) -> NlResult<()>;
+
+        if let Some(interface) = hw_interface() {
+            let _ = interface.bring_down();
+
+            let shortcuts: [(Shortcut, CanCtrlMode); 5] = [
+                (CanInterface::set_loopback, CanCtrlMode::Loopback),
+                (CanInterface::set_listen_only, CanCtrlMode::ListenOnly),
+                (CanInterface::set_one_shot, CanCtrlMode::OneShot),
+                (CanInterface::set_berr_reporting, CanCtrlMode::BerrReporting),
+                (CanInterface::set_fd, CanCtrlMode::Fd),
+            ];
+
+            let has_mode = |mode| {
+                let details = interface.details().unwrap();
+                details.can.ctrl_mode.unwrap().has_mode(mode)
+            };
+
+            for (set_mode, mode) in shortcuts {
+                // Not every controller supports every mode
+                if set_mode(&interface, true).is_err() {
+                    continue;
+                }
+                assert!(has_mode(mode));
+
+                set_mode(&interface, false).unwrap();
+                assert!(!has_mode(mode));
+            }
+        }
+    }
+
+    #[test]
+    fn tdc_attr() {
+        let tdc = CanTdc {
+            tdcv: 0,
+            tdco: 12,
+            tdcf: 4,
+        };
+        let attr = Rtattr::<IflaCan, Buffer>::try_from(&tdc).unwrap();
+        assert_eq!(IflaCan::Tdc, attr.rta_type);
+        assert_eq!(tdc, CanTdc::try_from(&attr).unwrap());
+    }
+
+    #[test]
+    fn param_diff() {
+        let current = InterfaceCanParams {
+            bit_timing: Some(CanBitTiming {
+                bitrate: 250_000,
+                sample_point: 875,
+                ..CanBitTiming::default()
+            }),
+            ctrl_mode: Some(CanCtrlModes::from_mode(CanCtrlMode::ListenOnly, false)),
+            restart_ms: Some(100),
+            ..InterfaceCanParams::default()
+        };
+        assert!(current.diff(&current.clone()).is_empty());
+
+        let mut desired = current.clone();
+        desired.bit_timing = Some(CanBitTiming {
+            bitrate: 500_000,
+            sample_point: 875,
+            ..CanBitTiming::default()
+        });
+        desired.ctrl_mode = Some(CanCtrlModes::from_mode(CanCtrlMode::ListenOnly, true));
+
+        let diffs = current.diff(&desired);
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[0].field, "bit_timing");
+        assert!(diffs[0].old.as_ref().unwrap().contains("250000"));
+        assert!(diffs[0].new.as_ref().unwrap().contains("500000"));
+        assert_eq!(diffs[1].field, "ctrl_mode");
+
+        desired.restart_ms = None;
+        let diffs = current.diff(&desired);
+        assert_eq!(diffs.len(), 3);
+        assert_eq!(diffs[1].field, "restart_ms");
+        assert_eq!(diffs[1].old.as_deref(), Some("100"));
+        assert_eq!(diffs[1].new, None);
+    }
+
+    #[test]
+    #[serial]
+    fn tdc() {
+        // TDC is not supported by vcan
+        let interface = TemporaryInterface::new("tdc").unwrap();
+        assert_eq!(None, interface.tdc().unwrap());
+
+        if let Some(interface) = hw_interface() {
+            if interface.supports_fd().unwrap() {
+                let tdc = CanTdc {
+                    tdcv: 0,
+                    tdco: 10,
+                    tdcf: 0,
+                };
+                let _ = interface.bring_down();
+                interface.set_tdc(tdc).unwrap();
+                assert_eq!(tdc.tdco, interface.tdc().unwrap().unwrap().tdco);
+            }
+        }
+    }
+
     #[test]
     #[serial]
     fn details() {
@@ -1042,6 +2460,229 @@ pub mod tests {
         assert!(!details.is_up);
     }
 
+    #[test]
+    #[serial]
+    fn hardware_timestamping() {
+        if let Some(interface) = hw_interface() {
+            assert!(interface.enable_hardware_timestamping().is_ok());
+        }
+
+        let interface = TemporaryInterface::new("hwtstamp").unwrap();
+        let err = interface.enable_hardware_timestamping().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+    }
+
+    fn check_params(interface: &CanInterface) {
+        let params = interface.params().unwrap();
+
+        assert_eq!(interface.bit_rate().unwrap(), params.bit_rate());
+        assert_eq!(interface.bit_timing().unwrap(), params.bit_timing);
+        assert_eq!(
+            interface.bit_timing_const().unwrap(),
+            params.bit_timing_const
+        );
+        assert_eq!(interface.clock().unwrap(), params.clock_freq());
+        assert_eq!(interface.state().unwrap(), params.state);
+        assert_eq!(interface.restart_ms().unwrap(), params.restart_ms);
+        assert_eq!(interface.auto_restart().unwrap(), params.auto_restart());
+        assert_eq!(interface.data_bit_timing().unwrap(), params.data_bit_timing);
+        assert_eq!(interface.termination().unwrap(), params.termination);
+        assert_eq!(interface.tdc().unwrap(), params.tdc);
+    }
+
+    #[test]
+    #[serial]
+    fn params() {
+        let interface = TemporaryInterface::new("params").unwrap();
+        check_params(&interface);
+
+        if let Some(interface) = hw_interface() {
+            check_params(&interface);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn set_sample_point() {
+        // Out of range values are rejected before anything is sent
+        let interface = CanInterface::open_iface(0);
+        assert!(interface.set_sample_point(1000).is_err());
+
+        if let Some(interface) = hw_interface() {
+            let _ = interface.bring_down();
+            let bitrate = interface.bit_timing().unwrap().unwrap().bitrate;
+
+            interface.set_sample_point(750).unwrap();
+            let bt = interface.bit_timing().unwrap().unwrap();
+            assert_eq!(bitrate, bt.bitrate);
+
+            // The controller may not be able to hit it exactly
+            assert!(bt.sample_point.abs_diff(750) <= 25);
+        }
+    }
+
+    #[test]
+    fn bit_timing_table() {
+        let table = BitTimingTable::builtin();
+        assert_eq!(20, table.len());
+
+        for clock in [8_000_000, 16_000_000, 24_000_000, 40_000_000, 80_000_000] {
+            for bitrate in [125_000, 250_000, 500_000, 1_000_000] {
+                let bt = table.get(clock, bitrate).unwrap();
+                assert_eq!(bitrate, bt.computed_bitrate(clock));
+                assert_eq!(87.5, bt.computed_sample_point());
+                assert_eq!(
+                    1_000_000_000u64 * bt.brp as u64 / clock as u64,
+                    bt.tq as u64
+                );
+            }
+        }
+
+        // Some well-known values, as for an SJA1000 or MCP2515
+        let bt = table.get(16_000_000, 500_000).unwrap();
+        assert_eq!(
+            (2, 6, 7, 2, 125),
+            (bt.brp, bt.prop_seg, bt.phase_seg1, bt.phase_seg2, bt.tq)
+        );
+        let bt = table.get(8_000_000, 1_000_000).unwrap();
+        assert_eq!(
+            (1, 3, 3, 1, 125),
+            (bt.brp, bt.prop_seg, bt.phase_seg1, bt.phase_seg2, bt.tq)
+        );
+
+        assert!(table.get(16_000_000, 100_000).is_none());
+        assert!(table.get(20_000_000, 500_000).is_none());
+    }
+
+    #[test]
+    fn bit_timing_table_insert() {
+        let mut table = BitTimingTable::new();
+        assert!(table.is_empty());
+
+        let bt = CanBitTiming {
+            bitrate: 500_000,
+            brp: 2,
+            prop_seg: 5,
+            phase_seg1: 6,
+            phase_seg2: 4,
+            ..CanBitTiming::default()
+        };
+        table.insert(16_000_000, bt);
+        assert_eq!(Some(bt), table.get(16_000_000, 500_000));
+
+        // Replaces the existing entry
+        let bt = CanBitTiming {
+            phase_seg2: 3,
+            ..bt
+        };
+        table.insert(16_000_000, bt);
+        assert_eq!(1, table.len());
+        assert_eq!(Some(bt), table.get(16_000_000, 500_000));
+    }
+
+    #[test]
+    fn name_pattern() {
+        assert!(name_matches("can0", "can0"));
+        assert!(!name_matches("can0", "can01"));
+        assert!(name_matches("can*", "can"));
+        assert!(name_matches("can*", "can12"));
+        assert!(!name_matches("can*", "vcan0"));
+        assert!(name_matches("*can?", "vcan0"));
+        assert!(!name_matches("*can?", "vcan"));
+    }
+
+    #[test]
+    #[serial]
+    fn configure_all() {
+        let _a = TemporaryInterface::new("cfgall_a").unwrap();
+        let _b = TemporaryInterface::new("cfgall_b").unwrap();
+
+        // A vcan has no bit timing, so only the matching is checked here
+        let cfg = CanConfig {
+            restart_ms: Some(100),
+            ..CanConfig::default()
+        };
+        let mut names: Vec<_> = super::configure_all("cfgall_?", &cfg)
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+        assert_eq!(vec!["cfgall_a", "cfgall_b"], names);
+
+        if let Some(interface) = hw_interface() {
+            let _ = interface.bring_down();
+            let name = interface.details().unwrap().name.unwrap();
+            let cfg = CanConfig {
+                bitrate: Some(250_000),
+                ..CanConfig::default()
+            };
+
+            let res = super::configure_all(&name, &cfg).unwrap();
+            assert_eq!(1, res.len());
+            assert!(res[0].1.is_ok());
+            assert_eq!(Some(250_000), interface.bit_rate().unwrap());
+        }
+    }
+
+    #[test]
+    fn link_stats64() {
+        // Any user can query the loopback interface
+        let interface = CanInterface::open("lo").unwrap();
+        assert!(interface.link_stats64().unwrap().is_some());
+
+        if let Some(interface) = hw_interface() {
+            let stats = interface.link_stats64().unwrap().unwrap();
+            let stats2 = interface.link_stats64().unwrap().unwrap();
+            assert!(stats2.rx_packets >= stats.rx_packets);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn error_counters() {
+        // A vcan has no error counters
+        let interface = TemporaryInterface::new("error_counters").unwrap();
+        assert_eq!(None, interface.params().unwrap().error_counters());
+
+        if let Some(interface) = hw_interface() {
+            let counters = interface.params().unwrap().error_counters();
+            let berr_counter = interface.berr_counter().unwrap();
+            assert_eq!(berr_counter.map(|cnt| (cnt.txerr, cnt.rxerr)), counters);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn snapshot() {
+        let interface = TemporaryInterface::new("snapshot").unwrap();
+        let snapshot = interface.snapshot().unwrap();
+        let details = interface.details().unwrap();
+
+        assert_eq!(details.index, snapshot.details.index);
+        assert_eq!(details.name, snapshot.details.name);
+        assert_eq!(details.is_up, snapshot.details.is_up);
+        assert_eq!(details.mtu, snapshot.details.mtu);
+        assert_eq!(details.can, snapshot.details.can);
+
+        // A vcan has no CAN statistics or error counters
+        assert!(snapshot.stats.is_none());
+        assert!(snapshot.berr_counter().is_none());
+
+        if let Some(interface) = hw_interface() {
+            let snapshot = interface.snapshot().unwrap();
+            assert_eq!(interface.details().unwrap().can, snapshot.details.can);
+            assert_eq!(interface.device_stats().unwrap(), snapshot.stats);
+            assert_eq!(
+                interface
+                    .berr_counter()
+                    .unwrap()
+                    .map(|c| (c.txerr, c.rxerr)),
+                snapshot.berr_counter().map(|c| (c.txerr, c.rxerr))
+            );
+        }
+    }
+
     #[test]
     #[serial]
     fn mtu() {
@@ -1053,4 +2694,132 @@ pub mod tests {
         assert!(interface.set_mtu(Mtu::Standard).is_ok());
         assert_eq!(Mtu::Standard, interface.details().unwrap().mtu.unwrap());
     }
+
+    #[test]
+    #[serial]
+    fn alias() {
+        let interface = TemporaryInterface::new("alias").unwrap();
+        assert_eq!(None, interface.alias().unwrap());
+
+        interface.set_alias("engine-bus").unwrap();
+        assert_eq!(Some("engine-bus".into()), interface.alias().unwrap());
+
+        interface.set_alias("").unwrap();
+        assert_eq!(None, interface.alias().unwrap());
+    }
+
+    #[test]
+    #[serial]
+    fn supported_ctrlmodes() {
+        // The vcan driver doesn't report any supported modes
+        let interface = TemporaryInterface::new("ctrlmode_ext").unwrap();
+        assert_eq!(None, interface.supported_ctrlmodes().unwrap());
+
+        if let Some(interface) = hw_interface() {
+            if interface.supports_fd().unwrap() {
+                let modes = interface.supported_ctrlmodes().unwrap().unwrap();
+                assert!(modes.has_mode(CanCtrlMode::Fd));
+            }
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn supports_fd() {
+        let interface = TemporaryInterface::new("supports_fd").unwrap();
+
+        assert!(interface.set_mtu(Mtu::Standard).is_ok());
+        assert!(!interface.supports_fd().unwrap());
+
+        assert!(interface.set_mtu(Mtu::Fd).is_ok());
+        assert!(interface.supports_fd().unwrap());
+    }
+
+    #[test]
+    #[serial]
+    fn protocol_mode() {
+        let interface = TemporaryInterface::new("protocol_mode").unwrap();
+
+        assert!(interface.set_mtu(Mtu::Standard).is_ok());
+        assert_eq!(ProtocolMode::Classic, interface.protocol_mode().unwrap());
+
+        assert!(interface.set_mtu(Mtu::Fd).is_ok());
+        assert_eq!(ProtocolMode::Fd, interface.protocol_mode().unwrap());
+
+        if let Some(interface) = hw_interface() {
+            let _ = interface.bring_down();
+
+            if interface.set_fd(false).is_ok() {
+                assert_eq!(ProtocolMode::Classic, interface.protocol_mode().unwrap());
+            }
+            if interface.set_fd(true).is_ok() {
+                assert_eq!(ProtocolMode::Fd, interface.protocol_mode().unwrap());
+
+                if interface.set_ctrlmode(CanCtrlMode::NonIso, true).is_ok() {
+                    assert_eq!(ProtocolMode::FdNonIso, interface.protocol_mode().unwrap());
+                    let _ = interface.set_ctrlmode(CanCtrlMode::NonIso, false);
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn write_frame_to() {
+        use crate::{CanAddr, CanFrame, CanSocket, EmbeddedFrame, Socket, StandardId};
+        use std::time::Duration;
+
+        let iface_a = TemporaryInterface::new("wrto_a").unwrap();
+        let iface_b = TemporaryInterface::new("wrto_b").unwrap();
+        assert!(iface_a.bring_up().is_ok());
+        assert!(iface_b.bring_up().is_ok());
+
+        let sock = CanSocket::open_iface(0).unwrap();
+        let rx_a = CanSocket::open("wrto_a").unwrap();
+        let rx_b = CanSocket::open("wrto_b").unwrap();
+        for rx in [&rx_a, &rx_b] {
+            rx.set_read_timeout(Duration::from_millis(100)).unwrap();
+        }
+
+        let frame = CanFrame::new(StandardId::new(0x123).unwrap(), &[1, 2, 3]).unwrap();
+        let ifindex = CanAddr::from_iface("wrto_a").unwrap().ifindex();
+        sock.write_frame_to(&frame, ifindex).unwrap();
+
+        let rx_frame = rx_a.read_frame().unwrap();
+        assert_eq!(frame.data(), rx_frame.data());
+        assert!(rx_b.read_frame().is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn socket_pair() {
+        use crate::{CanFrame, CanSocket, EmbeddedFrame, Frame, ShouldRetry, Socket, StandardId};
+        use std::time::Duration;
+
+        let pair = CanSocket::pair().unwrap();
+        let name = pair.interface_name().to_string();
+        let (a, b) = pair.sockets();
+
+        a.set_read_timeout(Duration::from_millis(100)).unwrap();
+        b.set_read_timeout(Duration::from_millis(100)).unwrap();
+
+        let frame = CanFrame::new(StandardId::new(0x123).unwrap(), &[1, 2, 3, 4]).unwrap();
+        a.write_frame(&frame).unwrap();
+        let rx_frame = b.read_frame().unwrap();
+        assert_eq!(frame.raw_id(), rx_frame.raw_id());
+        assert_eq!(frame.data(), rx_frame.data());
+
+        // A socket doesn't receive its own frames by default
+        assert!(a.read_frame().should_retry());
+
+        let frame = CanFrame::new(StandardId::new(0x456).unwrap(), &[5, 6]).unwrap();
+        b.write_frame(&frame).unwrap();
+        let rx_frame = a.read_frame().unwrap();
+        assert_eq!(frame.raw_id(), rx_frame.raw_id());
+        assert_eq!(frame.data(), rx_frame.data());
+
+        // The interface is removed when the pair is dropped
+        drop(pair);
+        assert!(CanSocket::open(&name).is_err());
+    }
 }
@@ -26,10 +26,12 @@ use neli::{
     impl_trait, neli_enum, FromBytes, Size, ToBytes,
 };
 use std::{
+    fmt,
     io::{self, Cursor, Read, Write},
     mem,
     mem::size_of,
 };
+use thiserror::Error;
 
 pub const EXT_FILTER_VF: c_uint = 1 << 0;
 pub const EXT_FILTER_BRVLAN: c_uint = 1 << 1;
@@ -47,7 +49,7 @@ pub const EXT_FILTER_MST: c_uint = 1 << 7;
 /// at http://www.semiconductors.bosch.de/pdf/can2spec.pdf.
 ///
 #[repr(C)]
-#[derive(Debug, Default, Clone, Copy, FromBytes, ToBytes, Size)]
+#[derive(Debug, Default, Clone, Copy, FromBytes, ToBytes, Size, PartialEq, Eq)]
 pub struct can_bittiming {
     pub bitrate: u32,      // Bit-rate in bits/second
     pub sample_point: u32, // Sample point in one-tenth of a percent
@@ -59,13 +61,48 @@ pub struct can_bittiming {
     pub brp: u32,          // Bit-rate prescaler
 }
 
+impl can_bittiming {
+    /// Gets the total number of time quanta in a single bit.
+    ///
+    /// This is the sync segment (always one TQ) plus the propagation and
+    /// both phase buffer segments.
+    pub fn bit_tq(&self) -> u32 {
+        1 + self.prop_seg + self.phase_seg1 + self.phase_seg2
+    }
+
+    /// Computes the actual bitrate, in bits per second, that results from
+    /// the segment values and prescaler when driven by a controller clock
+    /// of `clock_hz`.
+    ///
+    /// This can be compared against the `bitrate` field to verify the
+    /// timing that the kernel computed for the interface. Returns zero if
+    /// the prescaler is not set.
+    pub fn computed_bitrate(&self, clock_hz: u32) -> u32 {
+        match self.brp as u64 * self.bit_tq() as u64 {
+            0 => 0,
+            n => (clock_hz as u64 / n) as u32,
+        }
+    }
+
+    /// Computes the actual sample point, as a percentage of the bit time,
+    /// from the segment values.
+    ///
+    /// The sample is taken at the end of phase segment 1, so this is the
+    /// fraction of the bit that occurs before phase segment 2. Compare to
+    /// the `sample_point` field, which is in tenths of a percent.
+    pub fn computed_sample_point(&self) -> f32 {
+        let tq = self.bit_tq();
+        100.0 * (tq - self.phase_seg2) as f32 / tq as f32
+    }
+}
+
 /// CAN hardware-dependent bit-timing constant
 /// Missing from libc, from linux/can/netlink.h:
 ///
 /// Used for calculating and checking bit-timing parameters
 ///
 #[repr(C)]
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
 pub struct can_bittiming_const {
     pub name: [c_char; 16], // Name of the CAN controller hardware
     pub tseg1_min: u32,     // Time segment 1 = prop_seg + phase_seg1
@@ -78,6 +115,141 @@ pub struct can_bittiming_const {
     pub brp_inc: u32,
 }
 
+impl can_bittiming_const {
+    /// Gets the name of the CAN controller hardware.
+    ///
+    /// The name is decoded from the nul-terminated `name` field. Any
+    /// invalid UTF-8 is replaced.
+    pub fn controller_name(&self) -> String {
+        let bytes: Vec<u8> = self
+            .name
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as u8)
+            .collect();
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    /// Checks a proposed bit timing against the constraints of the
+    /// controller.
+    ///
+    /// This checks the time segments, synchronisation jump width, and
+    /// prescaler the same way that the kernel does when the timing is set,
+    /// so that a tool can report exactly which value is out of range,
+    /// rather than a generic `EINVAL` from the netlink request.
+    pub fn validate(&self, timing: &can_bittiming) -> Result<(), TimingError> {
+        let tseg1 = timing.prop_seg + timing.phase_seg1;
+        if tseg1 < self.tseg1_min || tseg1 > self.tseg1_max {
+            return Err(TimingError::Tseg1 {
+                value: tseg1,
+                min: self.tseg1_min,
+                max: self.tseg1_max,
+            });
+        }
+
+        let tseg2 = timing.phase_seg2;
+        if tseg2 < self.tseg2_min || tseg2 > self.tseg2_max {
+            return Err(TimingError::Tseg2 {
+                value: tseg2,
+                min: self.tseg2_min,
+                max: self.tseg2_max,
+            });
+        }
+
+        let sjw_max = self.sjw_max.min(tseg2);
+        if timing.sjw > sjw_max {
+            return Err(TimingError::Sjw {
+                value: timing.sjw,
+                max: sjw_max,
+            });
+        }
+
+        let brp = timing.brp;
+        if brp < self.brp_min || brp > self.brp_max {
+            return Err(TimingError::Brp {
+                value: brp,
+                min: self.brp_min,
+                max: self.brp_max,
+            });
+        }
+        if self.brp_inc > 1 && brp % self.brp_inc != 0 {
+            return Err(TimingError::BrpIncrement {
+                value: brp,
+                inc: self.brp_inc,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// An error from checking a bit timing against the controller constraints.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimingError {
+    /// Time segment 1 (prop_seg + phase_seg1) is out of range
+    #[error("Time segment 1 is {value}, but must be within {min}..={max}")]
+    Tseg1 {
+        /// The proposed value
+        value: u32,
+        /// The minimum supported by the controller
+        min: u32,
+        /// The maximum supported by the controller
+        max: u32,
+    },
+    /// Time segment 2 (phase_seg2) is out of range
+    #[error("Time segment 2 is {value}, but must be within {min}..={max}")]
+    Tseg2 {
+        /// The proposed value
+        value: u32,
+        /// The minimum supported by the controller
+        min: u32,
+        /// The maximum supported by the controller
+        max: u32,
+    },
+    /// The synchronisation jump width is larger than the controller
+    /// supports, or larger than time segment 2
+    #[error("Synchronisation jump width is {value}, but can be at most {max}")]
+    Sjw {
+        /// The proposed value
+        value: u32,
+        /// The maximum allowed
+        max: u32,
+    },
+    /// The bit-rate prescaler is out of range
+    #[error("Bit-rate prescaler is {value}, but must be within {min}..={max}")]
+    Brp {
+        /// The proposed value
+        value: u32,
+        /// The minimum supported by the controller
+        min: u32,
+        /// The maximum supported by the controller
+        max: u32,
+    },
+    /// The bit-rate prescaler is not a multiple of the required increment
+    #[error("Bit-rate prescaler is {value}, but must be a multiple of {inc}")]
+    BrpIncrement {
+        /// The proposed value
+        value: u32,
+        /// The increment required by the controller
+        inc: u32,
+    },
+}
+
+impl fmt::Debug for can_bittiming_const {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("can_bittiming_const")
+            .field("name", &self.controller_name())
+            .field("tseg1_min", &self.tseg1_min)
+            .field("tseg1_max", &self.tseg1_max)
+            .field("tseg2_min", &self.tseg2_min)
+            .field("tseg2_max", &self.tseg2_max)
+            .field("sjw_max", &self.sjw_max)
+            .field("brp_min", &self.brp_min)
+            .field("brp_max", &self.brp_max)
+            .field("brp_inc", &self.brp_inc)
+            .finish()
+    }
+}
+
 impl ToBytes for can_bittiming_const {
     fn to_bytes(&self, buf: &mut Cursor<Vec<u8>>) -> Result<(), SerError> {
         buf.write_all(as_bytes(self))?;
@@ -102,7 +274,7 @@ impl Size for can_bittiming_const {
 /// CAN clock parameters
 ///
 #[repr(C)]
-#[derive(Debug, Default, Clone, Copy, FromBytes, ToBytes, Size)]
+#[derive(Debug, Default, Clone, Copy, FromBytes, ToBytes, Size, PartialEq, Eq)]
 pub struct can_clock {
     pub freq: u32, // CAN system clock frequency in Hz
 }
@@ -147,7 +319,7 @@ impl TryFrom<u32> for CanState {
 /// CAN bus error counters
 ///
 #[repr(C)]
-#[derive(Debug, Default, Copy, Clone, FromBytes, ToBytes, Size)]
+#[derive(Debug, Default, Copy, Clone, FromBytes, ToBytes, Size, PartialEq, Eq)]
 pub struct can_berr_counter {
     pub txerr: u16,
     pub rxerr: u16,
@@ -159,7 +331,7 @@ pub struct can_berr_counter {
 /// the bit in the `flags` and send via `set_ctrlmode()`.
 ///
 #[repr(C)]
-#[derive(Debug, Default, Copy, Clone, FromBytes, ToBytes, Size)]
+#[derive(Debug, Default, Copy, Clone, FromBytes, ToBytes, Size, PartialEq, Eq)]
 pub struct can_ctrlmode {
     pub mask: u32,
     pub flags: u32,
@@ -183,6 +355,12 @@ pub const CAN_CTRLMODE_PRESUME_ACK: u32 = 0x40;
 pub const CAN_CTRLMODE_FD_NON_ISO: u32 = 0x80;
 /// Classic CAN DLC option
 pub const CAN_CTRLMODE_CC_LEN8_DLC: u32 = 0x100;
+/// Automatic transmitter delay compensation
+pub const CAN_CTRLMODE_TDC_AUTO: u32 = 0x200;
+/// Manual transmitter delay compensation
+pub const CAN_CTRLMODE_TDC_MANUAL: u32 = 0x400;
+/// Mask for the transmitter delay compensation modes
+pub const CAN_CTRLMODE_TDC_MASK: u32 = CAN_CTRLMODE_TDC_AUTO | CAN_CTRLMODE_TDC_MANUAL;
 
 /// u16 termination range: 1..65535 Ohms
 pub const CAN_TERMINATION_DISABLED: u32 = 0;
@@ -191,7 +369,7 @@ pub const CAN_TERMINATION_DISABLED: u32 = 0;
 /// CAN device statistics
 ///
 #[repr(C)]
-#[derive(Debug, Default, Copy, Clone, FromBytes)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, FromBytes)]
 pub struct can_device_stats {
     pub bus_error: u32,        // Bus errors
     pub error_warning: u32,    // Changes to error warning state
@@ -201,6 +379,60 @@ pub struct can_device_stats {
     pub restarts: u32,         // CAN controller re-starts
 }
 
+///
+/// The 64-bit interface statistics, from `IFLA_STATS64`
+///
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct rtnl_link_stats64 {
+    pub rx_packets: u64, // Total packets received
+    pub tx_packets: u64, // Total packets transmitted
+    pub rx_bytes: u64,   // Total bytes received
+    pub tx_bytes: u64,   // Total bytes transmitted
+    pub rx_errors: u64,  // Bad packets received
+    pub tx_errors: u64,  // Packet transmit problems
+    pub rx_dropped: u64, // Dropped, no space in Linux buffers
+    pub tx_dropped: u64, // Dropped, no space available in Linux
+    pub multicast: u64,  // Multicast packets received
+    pub collisions: u64,
+
+    // Detailed rx_errors
+    pub rx_length_errors: u64,
+    pub rx_over_errors: u64, // Receiver ring buff overflow
+    pub rx_crc_errors: u64,  // Received packet with crc error
+    pub rx_frame_errors: u64,
+    pub rx_fifo_errors: u64,   // Receiver fifo overrun
+    pub rx_missed_errors: u64, // Receiver missed packet
+
+    // Detailed tx_errors
+    pub tx_aborted_errors: u64,
+    pub tx_carrier_errors: u64,
+    pub tx_fifo_errors: u64,
+    pub tx_heartbeat_errors: u64,
+    pub tx_window_errors: u64,
+
+    // For cslip etc
+    pub rx_compressed: u64,
+    pub tx_compressed: u64,
+
+    pub rx_nohandler: u64, // Dropped, no handler found
+}
+
+impl rtnl_link_stats64 {
+    /// Creates the stats from the payload of an `IFLA_STATS64` attribute.
+    ///
+    /// The size of the struct has grown over kernel versions, so any
+    /// fields missing from the payload are left as zero, and any extra
+    /// ones at the end are ignored.
+    pub fn from_payload(payload: &[u8]) -> Self {
+        let mut stats = Self::default();
+        let buf = as_bytes_mut(&mut stats);
+        let n = buf.len().min(payload.len());
+        buf[..n].copy_from_slice(&payload[..n]);
+        stats
+    }
+}
+
 pub const IFLA_CAN_UNSPEC: u16 = 0;
 pub const IFLA_CAN_BITTIMING: u16 = 1;
 pub const IFLA_CAN_BITTIMING_CONST: u16 = 2;
@@ -246,6 +478,61 @@ pub enum IflaCan {
 
 impl RtaType for IflaCan {}
 
+pub const IFLA_CAN_TDC_UNSPEC: u16 = 0;
+pub const IFLA_CAN_TDC_TDCV_MIN: u16 = 1;
+pub const IFLA_CAN_TDC_TDCV_MAX: u16 = 2;
+pub const IFLA_CAN_TDC_TDCO_MIN: u16 = 3;
+pub const IFLA_CAN_TDC_TDCO_MAX: u16 = 4;
+pub const IFLA_CAN_TDC_TDCF_MIN: u16 = 5;
+pub const IFLA_CAN_TDC_TDCF_MAX: u16 = 6;
+pub const IFLA_CAN_TDC_TDCV: u16 = 7;
+pub const IFLA_CAN_TDC_TDCO: u16 = 8;
+pub const IFLA_CAN_TDC_TDCF: u16 = 9;
+
+/// CAN FD Transmitter Delay Compensation (TDC) nested attributes
+///
+#[neli_enum(serialized_type = "libc::c_ushort")]
+pub enum IflaCanTdc {
+    Unspec = IFLA_CAN_TDC_UNSPEC,
+    TdcvMin = IFLA_CAN_TDC_TDCV_MIN,
+    TdcvMax = IFLA_CAN_TDC_TDCV_MAX,
+    TdcoMin = IFLA_CAN_TDC_TDCO_MIN,
+    TdcoMax = IFLA_CAN_TDC_TDCO_MAX,
+    TdcfMin = IFLA_CAN_TDC_TDCF_MIN,
+    TdcfMax = IFLA_CAN_TDC_TDCF_MAX,
+    Tdcv = IFLA_CAN_TDC_TDCV,
+    Tdco = IFLA_CAN_TDC_TDCO,
+    Tdcf = IFLA_CAN_TDC_TDCF,
+}
+
+impl RtaType for IflaCanTdc {}
+
+pub const IFLA_CAN_CTRLMODE_UNSPEC: u16 = 0;
+pub const IFLA_CAN_CTRLMODE_SUPPORTED: u16 = 1;
+
+/// CAN extended control mode nested attributes
+///
+#[neli_enum(serialized_type = "libc::c_ushort")]
+pub enum IflaCanCtrlModeExt {
+    Unspec = IFLA_CAN_CTRLMODE_UNSPEC,
+    Supported = IFLA_CAN_CTRLMODE_SUPPORTED,
+}
+
+impl RtaType for IflaCanCtrlModeExt {}
+
+pub const VXCAN_INFO_UNSPEC: u16 = 0;
+pub const VXCAN_INFO_PEER: u16 = 1;
+
+/// vxcan tunnel info attributes
+///
+#[neli_enum(serialized_type = "libc::c_ushort")]
+pub enum IflaVxcan {
+    Unspec = VXCAN_INFO_UNSPEC,
+    Peer = VXCAN_INFO_PEER,
+}
+
+impl RtaType for IflaVxcan {}
+
 /////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
@@ -272,4 +559,131 @@ pub mod tests {
             as_bytes(&timing)
         );
     }
+
+    #[test]
+    fn test_computed_timing() {
+        // 80MHz clock, 20 TQ per bit
+        let timing = can_bittiming {
+            bitrate: 500000,
+            sample_point: 700,
+            tq: 100,
+            prop_seg: 6,
+            phase_seg1: 7,
+            phase_seg2: 6,
+            sjw: 1,
+            brp: 8,
+        };
+
+        assert_eq!(20, timing.bit_tq());
+        assert_eq!(500000, timing.computed_bitrate(80_000_000));
+        assert!((timing.computed_sample_point() - 70.0).abs() < 0.01);
+
+        // 8MHz clock, 16 TQ per bit
+        let timing = can_bittiming {
+            prop_seg: 5,
+            phase_seg1: 6,
+            phase_seg2: 4,
+            brp: 1,
+            ..can_bittiming::default()
+        };
+
+        assert_eq!(500000, timing.computed_bitrate(8_000_000));
+        assert!((timing.computed_sample_point() - 75.0).abs() < 0.01);
+
+        // No prescaler
+        let timing = can_bittiming::default();
+        assert_eq!(0, timing.computed_bitrate(8_000_000));
+    }
+
+    #[test]
+    fn test_controller_name() {
+        let mut timing_const = can_bittiming_const::default();
+        assert_eq!("", timing_const.controller_name());
+
+        for (c, b) in timing_const.name.iter_mut().zip(b"pcan_usb_fd\0junk") {
+            *c = *b as c_char;
+        }
+        assert_eq!("pcan_usb_fd", timing_const.controller_name());
+        assert!(format!("{:?}", timing_const).contains("name: \"pcan_usb_fd\""));
+
+        // A name that fills the whole field has no terminator
+        timing_const.name = [b'x' as c_char; 16];
+        assert_eq!("x".repeat(16), timing_const.controller_name());
+    }
+
+    #[test]
+    fn test_validate_timing() {
+        // Similar to an SJA1000
+        let timing_const = can_bittiming_const {
+            tseg1_min: 1,
+            tseg1_max: 16,
+            tseg2_min: 1,
+            tseg2_max: 8,
+            sjw_max: 4,
+            brp_min: 1,
+            brp_max: 64,
+            brp_inc: 2,
+            ..can_bittiming_const::default()
+        };
+
+        // 500kbps @ 8MHz, 87.5%
+        let timing = can_bittiming {
+            bitrate: 500_000,
+            sample_point: 875,
+            tq: 125,
+            prop_seg: 6,
+            phase_seg1: 7,
+            phase_seg2: 2,
+            sjw: 1,
+            brp: 2,
+        };
+        assert_eq!(Ok(()), timing_const.validate(&timing));
+
+        let bad = can_bittiming {
+            prop_seg: 10,
+            ..timing
+        };
+        assert_eq!(
+            Err(TimingError::Tseg1 {
+                value: 17,
+                min: 1,
+                max: 16
+            }),
+            timing_const.validate(&bad)
+        );
+
+        let bad = can_bittiming { sjw: 3, ..timing };
+        assert_eq!(
+            Err(TimingError::Sjw { value: 3, max: 2 }),
+            timing_const.validate(&bad)
+        );
+
+        let bad = can_bittiming { brp: 3, ..timing };
+        assert_eq!(
+            Err(TimingError::BrpIncrement { value: 3, inc: 2 }),
+            timing_const.validate(&bad)
+        );
+
+        let bad = can_bittiming { brp: 0, ..timing };
+        assert!(matches!(
+            timing_const.validate(&bad),
+            Err(TimingError::Brp { value: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_link_stats64_payload() {
+        let payload: Vec<u8> = (1..=26u64).flat_map(|v| v.to_ne_bytes()).collect();
+
+        // A newer kernel sends more fields than we know about
+        let stats = rtnl_link_stats64::from_payload(&payload);
+        assert_eq!(1, stats.rx_packets);
+        assert_eq!(4, stats.tx_bytes);
+        assert_eq!(24, stats.rx_nohandler);
+
+        // An older kernel sends fewer
+        let stats = rtnl_link_stats64::from_payload(&payload[..23 * 8]);
+        assert_eq!(23, stats.tx_compressed);
+        assert_eq!(0, stats.rx_nohandler);
+    }
 }
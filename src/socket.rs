@@ -14,27 +14,35 @@
 use crate::{
     as_bytes, as_bytes_mut,
     frame::{can_frame_default, canfd_frame_default, AsPtr},
-    id::CAN_ERR_MASK,
-    CanAnyFrame, CanFdFrame, CanFrame, CanRawFrame, Error, IoError, IoErrorKind, IoResult, Result,
+    id::{CANFD_MAX_DLEN, CAN_ERR_MASK},
+    util::FrameRing,
+    CanAnyFrame, CanFrame, CanRawFrame, Error, ErrorMask, Frame, IoError, IoErrorKind, IoResult,
+    Result,
 };
+use bitflags::bitflags;
 pub use embedded_can::{
     self, blocking::Can as BlockingCan, nb::Can as NonBlockingCan, ExtendedId,
     Frame as EmbeddedFrame, Id, StandardId,
 };
-use libc::{canid_t, socklen_t, AF_CAN, EINPROGRESS};
+use libc::{canfd_frame, canid_t, socklen_t, AF_CAN, EINPROGRESS};
 use socket2::SockAddr;
 use std::{
     fmt,
     io::{Read, Write},
-    mem::{size_of, size_of_val},
+    mem::{self, size_of, size_of_val},
+    ops::ControlFlow,
     os::{
-        raw::{c_int, c_void},
+        raw::{c_int, c_uint, c_void},
         unix::io::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd},
     },
     ptr,
-    time::Duration,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant, SystemTime},
 };
 
+// The time between checks of the cancel flag for an interruptible read.
+const INTERRUPT_POLL_INTERVAL_MS: u16 = 50;
+
 pub use libc::{
     CANFD_MTU, CAN_MTU, CAN_RAW, CAN_RAW_ERR_FILTER, CAN_RAW_FD_FRAMES, CAN_RAW_FILTER,
     CAN_RAW_JOIN_FILTERS, CAN_RAW_LOOPBACK, CAN_RAW_RECV_OWN_MSGS, SOL_CAN_BASE, SOL_CAN_RAW,
@@ -85,14 +93,223 @@ impl<E: fmt::Debug> ShouldRetry for IoResult<E> {
 
 /// Tries to open the CAN socket by the interface number.
 fn raw_open_socket(addr: &CanAddr) -> IoResult<socket2::Socket> {
+    raw_open_socket_with_type(addr, socket2::Type::RAW)
+}
+
+/// Tries to open the CAN socket by the interface number, in non-blocking
+/// mode from the start.
+fn raw_open_socket_nonblocking(addr: &CanAddr) -> IoResult<socket2::Socket> {
+    raw_open_socket_with_type(addr, socket2::Type::RAW.nonblocking())
+}
+
+/// Tries to open a CAN socket of the specified type, and bind it to the
+/// address.
+fn raw_open_socket_with_type(addr: &CanAddr, ty: socket2::Type) -> IoResult<socket2::Socket> {
     let af_can = socket2::Domain::from(AF_CAN);
     let can_raw = socket2::Protocol::from(CAN_RAW);
 
-    let sock = socket2::Socket::new_raw(af_can, socket2::Type::RAW, Some(can_raw))?;
+    let sock = socket2::Socket::new_raw(af_can, ty, Some(can_raw))?;
     sock.bind(&SockAddr::from(*addr))?;
     Ok(sock)
 }
 
+/// Reads a single message from the socket's error queue.
+///
+/// Returns the decoded entry, or `None` if the queue was empty.
+fn raw_recv_err_queue(fd: c_int) -> IoResult<Option<ErrorQueueEntry>> {
+    let mut buf = [0u8; CANFD_MTU];
+    let mut ctrl = [0u64; 32];
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut c_void,
+        iov_len: buf.len(),
+    };
+
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = ctrl.as_mut_ptr() as *mut c_void;
+    msg.msg_controllen = size_of_val(&ctrl) as _;
+
+    let ret = unsafe { libc::recvmsg(fd, &mut msg, libc::MSG_ERRQUEUE | libc::MSG_DONTWAIT) };
+
+    if ret < 0 {
+        let err = IoError::last_os_error();
+        return match err.kind() {
+            IoErrorKind::WouldBlock => Ok(None),
+            _ => Err(err),
+        };
+    }
+
+    let mut entry = ErrorQueueEntry::default();
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+    while !cmsg.is_null() {
+        let hdr = unsafe { &*cmsg };
+        let data = unsafe { libc::CMSG_DATA(cmsg) };
+        match (hdr.cmsg_level, hdr.cmsg_type) {
+            (SOL_CAN_RAW, SCM_CAN_RAW_ERRQUEUE) => {
+                let ee = unsafe { ptr::read_unaligned(data as *const libc::sock_extended_err) };
+                entry.errno = ee.ee_errno;
+                entry.origin = ee.ee_origin;
+                entry.info = ee.ee_info;
+                entry.data = ee.ee_data;
+            }
+            (libc::SOL_SOCKET, libc::SCM_TIMESTAMPING) => {
+                // The software timestamp, followed by two hardware ones
+                let ts = unsafe { ptr::read_unaligned(data as *const [libc::timespec; 3]) };
+                entry.timestamp = [ts[0], ts[2]]
+                    .into_iter()
+                    .find(|ts| ts.tv_sec != 0 || ts.tv_nsec != 0)
+                    .map(|ts| {
+                        SystemTime::UNIX_EPOCH + Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+                    });
+            }
+            _ => (),
+        }
+        cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+    }
+    Ok(Some(entry))
+}
+
+/// Writes a batch of frames to the socket with a single `sendmmsg` call.
+///
+/// Returns the number of frames accepted by the kernel.
+fn raw_write_frames<F: AsPtr>(fd: c_int, frames: &[F]) -> IoResult<usize> {
+    if frames.is_empty() {
+        return Ok(0);
+    }
+
+    let mut iovs: Vec<libc::iovec> = frames
+        .iter()
+        .map(|frame| libc::iovec {
+            iov_base: frame.as_ptr() as *mut c_void,
+            iov_len: frame.size(),
+        })
+        .collect();
+
+    let mut msgs: Vec<libc::mmsghdr> = iovs
+        .iter_mut()
+        .map(|iov| {
+            let mut msg: libc::mmsghdr = unsafe { mem::zeroed() };
+            msg.msg_hdr.msg_iov = iov;
+            msg.msg_hdr.msg_iovlen = 1;
+            msg
+        })
+        .collect();
+
+    let ret = unsafe { libc::sendmmsg(fd, msgs.as_mut_ptr(), msgs.len() as c_uint, 0) };
+
+    match ret {
+        n if n < 0 => Err(IoError::last_os_error()),
+        n => Ok(n as usize),
+    }
+}
+
+/// Gets the value of an integer option on the socket.
+///
+/// This is the counterpart to `SocketOptions::set_socket_option()`, but is
+/// limited to `c_int` options, so that the kernel can only ever write an
+/// integer. If the kernel returns a value of any other size, an
+/// `InvalidData` error is returned.
+fn raw_get_socket_option(fd: c_int, level: c_int, name: c_int) -> IoResult<c_int> {
+    let mut val: c_int = 0;
+    let mut len = size_of::<c_int>() as socklen_t;
+
+    let ret =
+        unsafe { libc::getsockopt(fd, level, name, &mut val as *mut _ as *mut c_void, &mut len) };
+
+    match ret {
+        0 if len as usize == size_of::<c_int>() => Ok(val),
+        0 => Err(IoError::new(
+            IoErrorKind::InvalidData,
+            "unexpected size for socket option",
+        )),
+        _ => Err(IoError::last_os_error()),
+    }
+}
+
+/// Reads a single message from the socket into `buf`.
+///
+/// Returns the number of bytes read. If the message was too large for the
+/// buffer, the kernel truncates it and sets `MSG_TRUNC`, which is reported
+/// as an `InvalidData` error.
+fn raw_recv(fd: c_int, buf: &mut [u8]) -> IoResult<usize> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut c_void,
+        iov_len: buf.len(),
+    };
+
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+
+    match unsafe { libc::recvmsg(fd, &mut msg, 0) } {
+        n if n < 0 => Err(IoError::last_os_error()),
+        _ if msg.msg_flags & libc::MSG_TRUNC != 0 => Err(IoError::new(
+            IoErrorKind::InvalidData,
+            "received CAN frame was truncated",
+        )),
+        n => Ok(n as usize),
+    }
+}
+
+/// Reads a single message from the socket into `buf`, along with the
+/// `SO_TIMESTAMPNS` receive timestamp, if the kernel attached one.
+///
+/// Returns the number of bytes read, the timestamp, and the message flags
+/// reported by the kernel. For a raw CAN socket, the flags will contain
+/// `MSG_DONTROUTE` for a frame that was sent from the local host, and
+/// `MSG_CONFIRM` for one that was sent from this socket.
+fn raw_recv_with_timestamp(
+    fd: c_int,
+    buf: &mut [u8],
+) -> IoResult<(usize, Option<SystemTime>, c_int)> {
+    let mut ctrl = [0u8; 64];
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut c_void,
+        iov_len: buf.len(),
+    };
+
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = ctrl.as_mut_ptr() as *mut c_void;
+    msg.msg_controllen = ctrl.len() as _;
+
+    let n = match unsafe { libc::recvmsg(fd, &mut msg, 0) } {
+        n if n < 0 => return Err(IoError::last_os_error()),
+        n => n as usize,
+    };
+
+    let mut ts = None;
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+    while !cmsg.is_null() {
+        let hdr = unsafe { &*cmsg };
+        if hdr.cmsg_level == libc::SOL_SOCKET && hdr.cmsg_type == libc::SCM_TIMESTAMPNS {
+            let tv = unsafe { ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::timespec) };
+            ts = Some(SystemTime::UNIX_EPOCH + Duration::new(tv.tv_sec as u64, tv.tv_nsec as u32));
+        }
+        cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+    }
+    Ok((n, ts, msg.msg_flags))
+}
+
+/// Checks that the socket is actually a raw CAN socket.
+///
+/// This verifies the domain and protocol of the socket, returning an
+/// `InvalidInput` error if it is anything other than AF_CAN / CAN_RAW.
+fn raw_check_can_socket(sock: &socket2::Socket) -> IoResult<()> {
+    let is_can_raw = sock.domain()? == socket2::Domain::from(AF_CAN)
+        && sock.protocol()? == Some(socket2::Protocol::from(CAN_RAW));
+
+    match is_can_raw {
+        true => Ok(()),
+        false => Err(IoError::new(
+            IoErrorKind::InvalidInput,
+            "not a raw CAN socket",
+        )),
+    }
+}
+
 /// `setsockopt` wrapper
 ///
 /// The libc `setsockopt` function is set to set various options on a socket.
@@ -298,6 +515,163 @@ pub trait Socket: AsRawFd {
             }
         }
     }
+
+    /// Blocking write a single can frame, retrying a limited number of
+    /// times if it can't be sent.
+    ///
+    /// This is similar to `write_frame_insist`, but gives up after
+    /// `max_retries` retries on errors for which `should_retry()` is true,
+    /// such as when the transmit queue is full because the bus is down.
+    /// In that case, the last error is returned.
+    fn write_frame_retries<F>(&self, frame: &F, max_retries: usize) -> IoResult<()>
+    where
+        F: Into<Self::FrameType> + AsPtr,
+    {
+        let mut retries = 0;
+        loop {
+            match self.write_frame(frame) {
+                Ok(v) => return Ok(v),
+                Err(e) if e.should_retry() && retries < max_retries => retries += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Writes a frame and waits for the driver to confirm it was sent.
+    ///
+    /// A normal `write_frame` returns as soon as the frame is queued by the
+    /// kernel, not when it has actually gone out on the bus. This enables
+    /// software transmit timestamping on the socket, writes the frame, and
+    /// then waits for the transmit confirmation to appear on the socket's
+    /// error queue, as read by `recvmsg(MSG_ERRQUEUE)`.
+    ///
+    /// The wait is bounded by the socket's write timeout, if one is set,
+    /// otherwise it blocks indefinitely. An error of kind `TimedOut` is
+    /// returned if no confirmation arrives in time, such as when the
+    /// controller is bus-off.
+    ///
+    /// If the error queue reports a transmit error rather than a timestamp,
+    /// that error is returned.
+    ///
+    /// Any stale entries on the error queue are discarded before the frame
+    /// is sent. The socket's previous `SO_TIMESTAMPING` flags are restored
+    /// afterward.
+    fn write_frame_confirm<F>(&self, frame: &F) -> IoResult<()>
+    where
+        Self: SocketOptions,
+        F: Into<Self::FrameType> + AsPtr,
+    {
+        use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+
+        let prev =
+            raw_get_socket_option(self.as_raw_fd(), libc::SOL_SOCKET, libc::SO_TIMESTAMPING)?;
+        let flags = (libc::SOF_TIMESTAMPING_TX_SOFTWARE
+            | libc::SOF_TIMESTAMPING_SOFTWARE
+            | libc::SOF_TIMESTAMPING_OPT_TSONLY) as c_int;
+        self.set_socket_option(libc::SOL_SOCKET, libc::SO_TIMESTAMPING, &flags)?;
+
+        let res = (|| -> IoResult<()> {
+            while raw_recv_err_queue(self.as_raw_fd())?.is_some() {}
+
+            self.write_frame(frame)?;
+
+            let timeout = match self.write_timeout()? {
+                Some(dur) => dur.try_into().unwrap_or(PollTimeout::MAX),
+                None => PollTimeout::NONE,
+            };
+
+            // POLLERR is always reported, so we don't need to request it.
+            let pollfd = PollFd::new(
+                unsafe { BorrowedFd::borrow_raw(self.as_raw_fd()) },
+                PollFlags::empty(),
+            );
+
+            if poll(&mut [pollfd], timeout)? == 0 {
+                return Err(IoErrorKind::TimedOut.into());
+            }
+            match raw_recv_err_queue(self.as_raw_fd())? {
+                Some(entry) if entry.is_tx_timestamp() => Ok(()),
+                Some(entry) => Err(IoError::from_raw_os_error(entry.errno as i32)),
+                None => Err(IoErrorKind::WouldBlock.into()),
+            }
+        })();
+
+        // Don't let a failure here hide the result of the write
+        let _ = self.set_socket_option(libc::SOL_SOCKET, libc::SO_TIMESTAMPING, &prev);
+        res
+    }
+
+    /// Writes a batch of frames to the socket with a single system call.
+    ///
+    /// This uses `sendmmsg` to hand all of the frames to the kernel at once,
+    /// which can considerably improve throughput for applications that
+    /// transmit bursts of frames, such as log replay tools.
+    ///
+    /// On success, this returns the number of frames that were accepted by
+    /// the kernel, which may be less than the number supplied if the
+    /// transmit queue filled up part way through the batch. The caller
+    /// should resubmit any remaining frames.
+    ///
+    /// If the queue was already full and no frames could be sent, this
+    /// returns an error which can be checked with `should_retry()`.
+    fn write_frames<F>(&self, frames: &[F]) -> IoResult<usize>
+    where
+        F: Into<Self::FrameType> + AsPtr,
+    {
+        raw_write_frames(self.as_raw_fd(), frames)
+    }
+}
+
+/// Common trait for the CAN transport protocol sockets, like ISO-TP and
+/// J1939, which transfer whole messages rather than individual frames.
+///
+/// These have none of the frame operations of a [`Socket`], but share the
+/// same blocking and timeout controls.
+pub trait ProtocolSocket: AsRawFd {
+    /// Gets a shared reference to the underlying socket object
+    fn as_raw_socket(&self) -> &socket2::Socket;
+
+    /// Determines if the socket is currently in nonblocking mode.
+    fn nonblocking(&self) -> IoResult<bool> {
+        self.as_raw_socket().nonblocking()
+    }
+
+    /// Change socket to non-blocking mode or back to blocking mode.
+    fn set_nonblocking(&self, nonblocking: bool) -> IoResult<()> {
+        self.as_raw_socket().set_nonblocking(nonblocking)
+    }
+
+    /// Gets the read timeout on the socket, if any.
+    fn read_timeout(&self) -> IoResult<Option<Duration>> {
+        self.as_raw_socket().read_timeout()
+    }
+
+    /// Sets the read timeout on the socket
+    ///
+    /// If the duration is set to `None` then read calls will block
+    /// indefinitely.
+    fn set_read_timeout<D>(&self, duration: D) -> IoResult<()>
+    where
+        D: Into<Option<Duration>>,
+    {
+        self.as_raw_socket().set_read_timeout(duration.into())
+    }
+
+    /// Gets the write timeout on the socket, if any.
+    fn write_timeout(&self) -> IoResult<Option<Duration>> {
+        self.as_raw_socket().write_timeout()
+    }
+
+    /// Sets the write timeout on the socket
+    ///
+    /// If the duration is set to `None` then write calls will block
+    /// indefinitely.
+    fn set_write_timeout<D>(&self, duration: D) -> IoResult<()>
+    where
+        D: Into<Option<Duration>>,
+    {
+        self.as_raw_socket().set_write_timeout(duration.into())
+    }
 }
 
 /// Traits for setting CAN socket options.
@@ -403,6 +777,15 @@ pub trait SocketOptions: AsRawFd {
         self.set_socket_option(SOL_CAN_RAW, CAN_RAW_ERR_FILTER, &mask)
     }
 
+    /// Gets the error mask on the socket.
+    ///
+    /// This is the mask set with `set_error_filter()` or
+    /// `set_error_mask()`.
+    fn error_filter(&self) -> IoResult<u32> {
+        raw_get_socket_option(self.as_raw_fd(), SOL_CAN_RAW, CAN_RAW_ERR_FILTER)
+            .map(|mask| mask as u32)
+    }
+
     /// Sets the error mask on the socket to reject all errors.
     #[inline(always)]
     fn set_error_filter_drop_all(&self) -> IoResult<()> {
@@ -425,6 +808,17 @@ pub trait SocketOptions: AsRawFd {
         self.set_socket_option(SOL_CAN_RAW, CAN_RAW_ERR_FILTER, &mask)
     }
 
+    /// Sets the error mask on the socket to receive only the classes of
+    /// error in the set.
+    ///
+    /// The kernel delivers an error frame if it reports any of the classes
+    /// in the mask. Since a frame can report several classes at once, an
+    /// application can check the classes of each one that it receives
+    /// with [`CanErrorFrame::error_classes()`](crate::CanErrorFrame::error_classes).
+    fn set_error_class_filter(&self, classes: ErrorMask) -> IoResult<()> {
+        self.set_error_filter(classes.bits())
+    }
+
     /// Enable or disable loopback.
     ///
     /// By default, loopback is enabled, causing other applications that open
@@ -435,6 +829,21 @@ pub trait SocketOptions: AsRawFd {
         self.set_socket_option(SOL_CAN_RAW, CAN_RAW_LOOPBACK, &loopback)
     }
 
+    /// Determines if local loopback is enabled on the socket.
+    ///
+    /// When it is, frames sent from this socket are also delivered to the
+    /// other sockets on the host that are bound to the same interface,
+    /// including those in other processes.
+    fn local_loopback_enabled(&self) -> IoResult<bool> {
+        self.loopback()
+    }
+
+    /// Gets whether loopback is enabled.
+    fn loopback(&self) -> IoResult<bool> {
+        let loopback = raw_get_socket_option(self.as_raw_fd(), SOL_CAN_RAW, CAN_RAW_LOOPBACK)?;
+        Ok(loopback != 0)
+    }
+
     /// Enable or disable receiving of own frames.
     ///
     /// When loopback is enabled, this settings controls if CAN frames sent
@@ -444,6 +853,23 @@ pub trait SocketOptions: AsRawFd {
         self.set_socket_option(SOL_CAN_RAW, CAN_RAW_RECV_OWN_MSGS, &recv_own_msgs)
     }
 
+    /// Gets whether receiving of own frames is enabled.
+    fn recv_own_msgs(&self) -> IoResult<bool> {
+        let recv_own_msgs =
+            raw_get_socket_option(self.as_raw_fd(), SOL_CAN_RAW, CAN_RAW_RECV_OWN_MSGS)?;
+        Ok(recv_own_msgs != 0)
+    }
+
+    /// Enable or disable nanosecond receive timestamps.
+    ///
+    /// This sets the `SO_TIMESTAMPNS` option, which has the kernel attach
+    /// the (software) time that each frame was received to the message.
+    /// It is required to read frames with `read_frame_with_timestamp()`.
+    fn set_timestamp_ns(&self, enabled: bool) -> IoResult<()> {
+        let timestamp = c_int::from(enabled);
+        self.set_socket_option(libc::SOL_SOCKET, libc::SO_TIMESTAMPNS, &timestamp)
+    }
+
     /// Enable or disable join filters.
     ///
     /// By default a frame is accepted if it matches any of the filters set
@@ -453,35 +879,122 @@ pub trait SocketOptions: AsRawFd {
         let join_filters = c_int::from(enabled);
         self.set_socket_option(SOL_CAN_RAW, CAN_RAW_JOIN_FILTERS, &join_filters)
     }
+
+    /// Gets whether join filters is enabled.
+    fn join_filters(&self) -> IoResult<bool> {
+        let join_filters =
+            raw_get_socket_option(self.as_raw_fd(), SOL_CAN_RAW, CAN_RAW_JOIN_FILTERS)?;
+        Ok(join_filters != 0)
+    }
 }
 
-// TODO: We need to restore this, but preferably with TIMESTAMPING
+// ===== SendFlags =====
 
-/*
-impl CanSocket {
+bitflags! {
+    /// Flags that can be applied to an individual write to a socket.
+    ///
+    /// These are the relevant `MSG_*` flags for the `send()` call, which
+    /// apply to a single frame, regardless of how the socket is configured.
+    #[repr(transparent)]
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct SendFlags: c_int {
+        /// Don't block if the transmit queue is full, even if the socket
+        /// is in blocking mode. The write fails with `WouldBlock` instead.
+        const DONTWAIT = libc::MSG_DONTWAIT;
+        /// Don't raise SIGPIPE if the socket is no longer connected.
+        const NOSIGNAL = libc::MSG_NOSIGNAL;
+    }
+}
+
+// ===== MsgFlags =====
 
-    /// Blocking read a single can frame with timestamp
+bitflags! {
+    /// Flags that the kernel reports for a message received on a socket.
     ///
-    /// Note that reading a frame and retrieving the timestamp requires two
-    /// consecutive syscalls. To avoid race conditions, exclusive access
-    /// to the socket is enforce through requiring a `mut &self`.
-    pub fn read_frame_with_timestamp(&mut self) -> IoResult<(CanFrame, time::SystemTime)> {
-        let frame = self.read_frame()?;
+    /// These are the relevant `MSG_*` flags returned by the `recvmsg()`
+    /// call for a single frame.
+    #[repr(transparent)]
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct MsgFlags: c_int {
+        /// The frame was sent from the local host.
+        const DONTROUTE = libc::MSG_DONTROUTE;
+        /// The frame was sent from this socket. This is only seen when
+        /// receiving our own messages is enabled.
+        const CONFIRM = libc::MSG_CONFIRM;
+        /// The frame was larger than the buffer and was truncated.
+        const TRUNC = libc::MSG_TRUNC;
+        /// The control data was truncated, such as a timestamp.
+        const CTRUNC = libc::MSG_CTRUNC;
+    }
+}
 
-        let mut ts = timespec { tv_sec: 0, tv_nsec: 0 };
-        let ret = unsafe {
-            libc::ioctl(self.fd, SIOCGSTAMPNS as c_ulong, &mut ts as *mut timespec)
-        };
+// ===== ErrorQueueEntry =====
 
-        if ret == -1 {
-            return Err(IoError::last_os_error());
-        }
+/// The control message type for an error queue entry on a raw CAN socket.
+const SCM_CAN_RAW_ERRQUEUE: c_int = 1;
+
+/// An entry read from a socket's error queue.
+///
+/// The kernel queues these to report transmit timestamps, when they are
+/// enabled with `SO_TIMESTAMPING`, and errors from asynchronous sends.
+/// The fields are those of the kernel's `sock_extended_err`, along with
+/// the timestamp that accompanied the entry, if any.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorQueueEntry {
+    /// The error number. This is `ENOMSG` for a timestamp.
+    pub errno: u32,
+    /// The origin of the entry, one of the `SO_EE_ORIGIN_*` values
+    pub origin: u8,
+    /// Extra info. For a timestamp, this is the `SCM_TSTAMP_*` type.
+    pub info: u32,
+    /// Extra data. For a timestamp, this is the ID, if `SOF_TIMESTAMPING_OPT_ID` is set.
+    pub data: u32,
+    /// The software or hardware timestamp, if one was attached
+    pub timestamp: Option<SystemTime>,
+}
+
+impl ErrorQueueEntry {
+    /// Determines if the entry is a transmit timestamp, rather than an
+    /// error.
+    pub fn is_tx_timestamp(&self) -> bool {
+        self.origin == libc::SO_EE_ORIGIN_TIMESTAMPING && self.errno == libc::ENOMSG as u32
+    }
+}
 
-        Ok((frame, system_time_from_timespec(ts)))
+// ===== Access =====
+
+// The operations that are permitted on a socket.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    #[default]
+    ReadWrite,
+    ReadOnly,
+    WriteOnly,
+}
+
+impl Access {
+    // Checks that frames can be read from the socket.
+    fn check_read(self) -> IoResult<()> {
+        match self {
+            Access::WriteOnly => Err(IoError::new(
+                IoErrorKind::PermissionDenied,
+                "the socket was opened write-only",
+            )),
+            _ => Ok(()),
+        }
     }
 
+    // Checks that frames can be written to the socket.
+    fn check_write(self) -> IoResult<()> {
+        match self {
+            Access::ReadOnly => Err(IoError::new(
+                IoErrorKind::PermissionDenied,
+                "the socket was opened read-only",
+            )),
+            _ => Ok(()),
+        }
+    }
 }
-*/
 
 // ===== CanSocket =====
 
@@ -496,57 +1009,520 @@ impl CanSocket {
 /// (file) descriptor.
 #[allow(missing_copy_implementations)]
 #[derive(Debug)]
-pub struct CanSocket(socket2::Socket);
+pub struct CanSocket(socket2::Socket, Access);
 
 impl CanSocket {
+    /// Opens a named CAN device in non-blocking mode.
+    ///
+    /// The socket is created non-blocking (with `SOCK_NONBLOCK`), so there
+    /// is never a window in which it could block. Reads on an idle bus
+    /// return immediately with an error for which `should_retry()` is true.
+    pub fn open_nonblocking(ifname: &str) -> IoResult<Self> {
+        let addr = CanAddr::from_iface(ifname)?;
+        raw_open_socket_nonblocking(&addr).map(Self::from_socket)
+    }
+
+    /// Opens a named CAN device for reading only.
+    ///
+    /// This is useful for monitoring tools, to guarantee that they never
+    /// put anything on the bus. SocketCAN has no way to prevent a raw
+    /// socket from sending, so this is enforced by the socket object:
+    /// any attempt to write a frame fails with a `PermissionDenied` error.
+    /// Note that this doesn't apply to the underlying descriptor, as from
+    /// `as_raw_socket()`, nor to the socket after it is converted to a
+    /// `CanFdSocket`.
+    pub fn open_readonly(ifname: &str) -> IoResult<Self> {
+        let mut sock = Self::open(ifname)?;
+        sock.1 = Access::ReadOnly;
+        Ok(sock)
+    }
+
+    /// Opens a named CAN device for writing only.
+    ///
+    /// All of the incoming frames are dropped by the kernel, so they don't
+    /// fill the receive buffer, and any attempt to read a frame fails with
+    /// a `PermissionDenied` error.
+    pub fn open_writeonly(ifname: &str) -> IoResult<Self> {
+        let mut sock = Self::open(ifname)?;
+        sock.set_filter_drop_all()?;
+        sock.set_error_filter_drop_all()?;
+        sock.1 = Access::WriteOnly;
+        Ok(sock)
+    }
+
+    // Creates a read/write socket object from an open socket.
+    fn from_socket(sock: socket2::Socket) -> Self {
+        Self(sock, Access::default())
+    }
+
+    /// Creates a socket from an existing file descriptor, after checking
+    /// that it really is a raw CAN socket.
+    ///
+    /// This is safer than `From<OwnedFd>` for a descriptor that was passed
+    /// in from elsewhere, such as from another process. If the descriptor
+    /// is not an AF_CAN / CAN_RAW socket, an `InvalidInput` error is
+    /// returned and the descriptor is closed.
+    pub fn try_from_fd(fd: OwnedFd) -> IoResult<Self> {
+        let sock = socket2::Socket::from(fd);
+        raw_check_can_socket(&sock)?;
+        Ok(Self::from_socket(sock))
+    }
+
     /// Reads a low-level libc `can_frame` from the socket.
     pub fn read_raw_frame(&self) -> IoResult<libc::can_frame> {
+        self.1.check_read()?;
         let mut frame = can_frame_default();
         self.as_raw_socket().read_exact(as_bytes_mut(&mut frame))?;
         Ok(frame)
     }
-}
 
-impl Socket for CanSocket {
-    /// CanSocket reads/writes classic CAN 2.0 frames.
-    type FrameType = CanFrame;
+    /// Reads the next frame without removing it from the receive queue.
+    ///
+    /// This uses `MSG_PEEK`, so the following read, or peek, returns the
+    /// same frame. It can be used to inspect the ID of a frame before
+    /// deciding how to handle it.
+    pub fn peek_frame(&self) -> IoResult<CanFrame> {
+        self.1.check_read()?;
+        let mut frame = can_frame_default();
+        let buf = as_bytes_mut(&mut frame);
 
-    /// Opens the socket by interface index.
-    fn open_addr(addr: &CanAddr) -> IoResult<Self> {
-        let sock = raw_open_socket(addr)?;
-        Ok(Self(sock))
-    }
+        let ret = unsafe {
+            libc::recv(
+                self.as_raw_fd(),
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len(),
+                libc::MSG_PEEK,
+            )
+        };
 
-    /// Gets a shared reference to the underlying socket object
-    fn as_raw_socket(&self) -> &socket2::Socket {
-        &self.0
+        match ret {
+            n if n < 0 => Err(IoError::last_os_error()),
+            n if n as usize != CAN_MTU => Err(IoErrorKind::UnexpectedEof.into()),
+            _ => Ok(frame.into()),
+        }
     }
 
-    /// Gets a mutable reference to the underlying socket object
-    fn as_raw_socket_mut(&mut self) -> &mut socket2::Socket {
-        &mut self.0
+    /// Reads a frame along with the index of the interface it arrived on.
+    ///
+    /// This is mainly useful for a socket bound to all interfaces, with an
+    /// interface index of zero, to determine the bus that sent each frame.
+    /// The index is taken from the source address reported by the kernel.
+    pub fn read_frame_with_iface(&self) -> IoResult<(CanFrame, u32)> {
+        self.1.check_read()?;
+        let mut frame = can_frame_default();
+        let mut addr: libc::sockaddr_can = unsafe { mem::zeroed() };
+        let mut addr_len = size_of::<libc::sockaddr_can>() as socklen_t;
+
+        let ret = unsafe {
+            libc::recvfrom(
+                self.as_raw_fd(),
+                as_bytes_mut(&mut frame).as_mut_ptr() as *mut c_void,
+                size_of::<libc::can_frame>(),
+                0,
+                &mut addr as *mut _ as *mut libc::sockaddr,
+                &mut addr_len,
+            )
+        };
+
+        match ret {
+            n if n < 0 => Err(IoError::last_os_error()),
+            n if n as usize != CAN_MTU => Err(IoErrorKind::UnexpectedEof.into()),
+            _ => Ok((frame.into(), CanAddr::from(addr).ifindex())),
+        }
     }
 
-    /// Writes a normal CAN 2.0 frame to the socket.
-    fn write_frame<F>(&self, frame: &F) -> IoResult<()>
-    where
-        F: Into<CanFrame> + AsPtr,
-    {
-        self.as_raw_socket().write_all(frame.as_bytes())
+    /// Reads all of the frames that are currently available on the socket
+    /// into the ring buffer, without blocking.
+    ///
+    /// This stops when there are no more frames waiting to be read, or
+    /// when the ring is full, and returns the number of frames that were
+    /// added to the ring. Any frames left on the socket can be read on a
+    /// later call, once the consumer has made room for them.
+    pub fn read_into_ring(&self, ring: &FrameRing) -> IoResult<usize> {
+        self.1.check_read()?;
+        let mut count = 0;
+        while !ring.is_full() {
+            match self.read_frame_dontwait()? {
+                // There's only one producer, so this can't fail after the check
+                Some(frame) => {
+                    let _ = ring.push(frame);
+                    count += 1;
+                }
+                None => break,
+            }
+        }
+        Ok(count)
     }
 
-    /// Reads a normal CAN 2.0 frame from the socket.
-    fn read_frame(&self) -> IoResult<CanFrame> {
-        let frame = self.read_raw_frame()?;
-        Ok(frame.into())
+    /// Reads all of the frames that are currently available on the socket,
+    /// without blocking.
+    ///
+    /// This returns an empty list if there are no frames waiting to be
+    /// read. Each read is done with `MSG_DONTWAIT`, so the blocking mode
+    /// of the socket isn't changed.
+    pub fn read_available(&self) -> IoResult<Vec<CanFrame>> {
+        self.1.check_read()?;
+        let mut frames = Vec::new();
+        while let Some(frame) = self.read_frame_dontwait()? {
+            frames.push(frame);
+        }
+        Ok(frames)
     }
-}
 
-// ===== embedded_can I/O traits =====
+    /// Sends a request frame and waits for a response that passes the
+    /// filter.
+    ///
+    /// Any frames that arrive before the response and don't pass the
+    /// filter are read and discarded. If no matching frame arrives within
+    /// the `timeout`, an error of kind `TimedOut` is returned. This is the
+    /// usual pattern for diagnostic request/response protocols.
+    pub fn transaction(
+        &self,
+        request: &CanFrame,
+        response_filter: CanFilter,
+        timeout: Duration,
+    ) -> IoResult<CanFrame> {
+        self.1.check_read()?;
+        let deadline = Instant::now() + timeout;
+        self.write_frame(request)?;
 
-impl embedded_can::blocking::Can for CanSocket {
-    type Frame = CanFrame;
-    type Error = Error;
+        loop {
+            let remaining = deadline
+                .checked_duration_since(Instant::now())
+                .ok_or(IoErrorKind::TimedOut)?;
+            let frame = self.read_frame_timeout(remaining)?;
+            if response_filter.matches(&frame) {
+                return Ok(frame);
+            }
+        }
+    }
+
+    // Reads a single frame from the socket without blocking, returning
+    // `None` if there isn't one available.
+    fn read_frame_dontwait(&self) -> IoResult<Option<CanFrame>> {
+        let mut frame = can_frame_default();
+        let buf = as_bytes_mut(&mut frame);
+
+        let ret = unsafe {
+            libc::recv(
+                self.as_raw_fd(),
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len(),
+                libc::MSG_DONTWAIT,
+            )
+        };
+
+        match ret {
+            n if n < 0 => match IoError::last_os_error() {
+                err if err.kind() == IoErrorKind::WouldBlock => Ok(None),
+                err => Err(err),
+            },
+            n if n as usize != CAN_MTU => Err(IoErrorKind::UnexpectedEof.into()),
+            _ => Ok(Some(frame.into())),
+        }
+    }
+
+    /// Writes a frame to a specific interface.
+    ///
+    /// This uses `sendto()` with the address of the interface, which is
+    /// needed to transmit from a socket that is bound to all interfaces
+    /// (with an index of zero), since a plain write has no interface to
+    /// send on.
+    pub fn write_frame_to<F>(&self, frame: &F, ifindex: u32) -> IoResult<()>
+    where
+        F: Into<CanFrame> + AsPtr,
+    {
+        self.1.check_write()?;
+        let addr = SockAddr::from(CanAddr::new(ifindex));
+        let buf = frame.as_bytes();
+        match self.as_raw_socket().send_to(buf, &addr)? {
+            n if n == buf.len() => Ok(()),
+            _ => Err(IoErrorKind::WriteZero.into()),
+        }
+    }
+
+    /// Writes a single frame, applying the `flags` to just this write.
+    ///
+    /// This is mainly useful with `SendFlags::DONTWAIT` to attempt a
+    /// one-shot, non-blocking send on a socket that is otherwise in
+    /// blocking mode. If the transmit queue is full, this returns an error
+    /// for which `should_retry()` is true.
+    pub fn write_frame_flags<F>(&self, frame: &F, flags: SendFlags) -> IoResult<()>
+    where
+        F: Into<CanFrame> + AsPtr,
+    {
+        self.1.check_write()?;
+        let buf = frame.as_bytes();
+        match self.as_raw_socket().send_with_flags(buf, flags.bits())? {
+            n if n == buf.len() => Ok(()),
+            _ => Err(IoErrorKind::WriteZero.into()),
+        }
+    }
+
+    /// Writes a frame to be transmitted at a specific time in the future.
+    ///
+    /// This enables `SO_TXTIME` on the socket, with the realtime clock,
+    /// and passes the launch time to the kernel in an `SCM_TXTIME` control
+    /// message with the frame.
+    ///
+    /// The launch time is only honored if the interface has the Earliest
+    /// TxTime First (ETF) queuing discipline installed, using the same
+    /// clock. For example:
+    ///
+    /// ```text
+    /// $ tc qdisc replace dev can0 root etf clockid CLOCK_REALTIME delta 200000
+    /// ```
+    ///
+    /// Without it, as on a vcan interface, the frame is sent immediately.
+    ///
+    /// Note that the kernel has no way to turn `SO_TXTIME` off again, so it
+    /// stays enabled on the socket after this call. On an interface with
+    /// the ETF qdisc, any later frame written without a launch time, as
+    /// with `write_frame()`, is then treated as already late and may be
+    /// dropped. Use a separate socket for timed transmission.
+    ///
+    /// PRIVILEGED: Using the realtime clock for `SO_TXTIME` requires the
+    /// `CAP_NET_ADMIN` capability.
+    ///
+    pub fn write_frame_at<F>(&self, frame: &F, txtime: SystemTime) -> IoResult<()>
+    where
+        F: Into<CanFrame> + AsPtr,
+    {
+        self.1.check_write()?;
+        let cfg = libc::sock_txtime {
+            clockid: libc::CLOCK_REALTIME,
+            flags: 0,
+        };
+        self.set_socket_option(libc::SOL_SOCKET, libc::SO_TXTIME, &cfg)?;
+
+        let txtime_ns: u64 = txtime
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_err(|_| IoError::from(IoErrorKind::InvalidInput))?
+            .as_nanos()
+            .try_into()
+            .map_err(|_| IoError::from(IoErrorKind::InvalidInput))?;
+
+        let buf = frame.as_bytes();
+        let mut iov = libc::iovec {
+            iov_base: buf.as_ptr() as *mut c_void,
+            iov_len: buf.len(),
+        };
+
+        // A u64-aligned buffer big enough for the single control message
+        let mut ctrl = [0u64; 4];
+        let ctrl_len = unsafe { libc::CMSG_SPACE(size_of::<u64>() as u32) } as usize;
+        debug_assert!(ctrl_len <= size_of_val(&ctrl));
+
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = ctrl.as_mut_ptr() as *mut c_void;
+        msg.msg_controllen = ctrl_len as _;
+
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_TXTIME;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(size_of::<u64>() as u32) as _;
+            ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut u64, txtime_ns);
+        }
+
+        match unsafe { libc::sendmsg(self.as_raw_fd(), &msg, 0) } {
+            n if n < 0 => Err(IoError::last_os_error()),
+            n if n as usize != buf.len() => Err(IoErrorKind::WriteZero.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Runs a receive loop, passing each frame that is read to the
+    /// callback.
+    ///
+    /// The loop continues until the callback returns `ControlFlow::Break`,
+    /// in which case this returns `Ok(())`, or until a read fails with an
+    /// error that can't be retried, which is returned. Timeouts and
+    /// interrupted reads are ignored, so a read timeout can be set on the
+    /// socket without ending the loop. The socket should not be in
+    /// non-blocking mode, or this will spin while the bus is idle.
+    pub fn for_each_frame<F>(&self, mut f: F) -> IoResult<()>
+    where
+        F: FnMut(CanFrame) -> ControlFlow<()>,
+    {
+        loop {
+            match self.read_frame() {
+                Ok(frame) => {
+                    if f(frame).is_break() {
+                        return Ok(());
+                    }
+                }
+                Err(err) if err.should_retry() || err.kind() == IoErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Reads a frame, blocking until one arrives or the read is cancelled
+    /// by setting the `cancel` flag from another thread.
+    ///
+    /// This polls the socket with a short timeout in a loop, checking the
+    /// flag between polls, so a cancelled read returns `Ok(None)` within a
+    /// fraction of a second. This lets an application shut down a
+    /// blocking receive thread cleanly, without closing the socket out
+    /// from under it. The flag is not cleared by the call.
+    pub fn read_frame_interruptible(&self, cancel: &AtomicBool) -> IoResult<Option<CanFrame>> {
+        use nix::poll::{poll, PollFd, PollFlags};
+
+        while !cancel.load(Ordering::Acquire) {
+            let pollfd = PollFd::new(self.as_fd(), PollFlags::POLLIN);
+            match poll(&mut [pollfd], INTERRUPT_POLL_INTERVAL_MS) {
+                Ok(0) | Err(nix::Error::EINTR) => (),
+                Ok(_) => return self.read_frame().map(Some),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads and removes all of the entries currently on the socket's
+    /// error queue.
+    ///
+    /// Transmit timestamps and send errors accumulate on the error queue
+    /// until they are read, so applications that track transmit completions
+    /// can use this to collect them. It does not block, and returns an
+    /// empty list if the queue is empty.
+    pub fn drain_error_queue(&self) -> IoResult<Vec<ErrorQueueEntry>> {
+        let mut entries = Vec::new();
+        while let Some(entry) = raw_recv_err_queue(self.as_raw_fd())? {
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    /// Reads a frame along with the time that the kernel received it.
+    ///
+    /// This uses the simple `SO_TIMESTAMPNS` software timestamp, which
+    /// must first be enabled on the socket with `set_timestamp_ns(true)`.
+    /// If the frame arrives without a timestamp, an error is returned.
+    pub fn read_frame_with_timestamp(&self) -> IoResult<(CanFrame, SystemTime)> {
+        let (frame, ts) = self.read_frame_ts()?;
+        let ts = ts
+            .ok_or_else(|| IoError::new(IoErrorKind::Other, "no receive timestamp on the frame"))?;
+        Ok((frame, ts))
+    }
+
+    /// Reads a frame along with the time that the kernel received it, if
+    /// the kernel attached one.
+    ///
+    /// This is like `read_frame_with_timestamp()`, but a frame that arrives
+    /// without a timestamp, such as when `set_timestamp_ns(true)` hasn't
+    /// been called on the socket, is returned with `None` rather than an
+    /// error.
+    pub fn read_frame_ts(&self) -> IoResult<(CanFrame, Option<SystemTime>)> {
+        self.1.check_read()?;
+        let mut frame = can_frame_default();
+        let (n, ts, _) = raw_recv_with_timestamp(self.as_raw_fd(), as_bytes_mut(&mut frame))?;
+
+        if n != CAN_MTU {
+            return Err(IoErrorKind::UnexpectedEof.into());
+        }
+        Ok((frame.into(), ts))
+    }
+
+    /// Reads a frame from the socket, along with the message flags that
+    /// the kernel reported for it.
+    ///
+    /// See [`CanFdSocket::read_frame_meta()`] for the flags.
+    pub fn read_frame_meta(&self) -> IoResult<(CanFrame, MsgFlags)> {
+        self.1.check_read()?;
+        let mut frame = can_frame_default();
+        let (n, _, flags) = raw_recv_with_timestamp(self.as_raw_fd(), as_bytes_mut(&mut frame))?;
+
+        if n != CAN_MTU {
+            return Err(IoErrorKind::UnexpectedEof.into());
+        }
+        Ok((frame.into(), MsgFlags::from_bits_truncate(flags)))
+    }
+
+    /// Reads the echo of a frame that was sent from this socket, along with
+    /// the time that the kernel looped it back.
+    ///
+    /// This can be used to measure the latency of the local transmit path.
+    /// The socket must have both `set_recv_own_msgs(true)` and
+    /// `set_timestamp_ns(true)` enabled. On real hardware, the echo is
+    /// generated when the controller reports that the frame was sent.
+    ///
+    /// Any frames that were not sent from this socket are read and
+    /// discarded while waiting for the echo, so this should not be used on
+    /// a socket that also needs to receive traffic from the bus.
+    pub fn read_own_echo_timestamp(&self) -> IoResult<(CanFrame, SystemTime)> {
+        self.1.check_read()?;
+        loop {
+            let mut frame = can_frame_default();
+            let (n, ts, flags) =
+                raw_recv_with_timestamp(self.as_raw_fd(), as_bytes_mut(&mut frame))?;
+
+            if n != CAN_MTU {
+                return Err(IoErrorKind::UnexpectedEof.into());
+            }
+            if flags & libc::MSG_CONFIRM != 0 {
+                let ts = ts.ok_or_else(|| {
+                    IoError::new(IoErrorKind::Other, "no receive timestamp on the frame")
+                })?;
+                return Ok((frame.into(), ts));
+            }
+        }
+    }
+}
+
+impl Socket for CanSocket {
+    /// CanSocket reads/writes classic CAN 2.0 frames.
+    type FrameType = CanFrame;
+
+    /// Opens the socket by interface index.
+    fn open_addr(addr: &CanAddr) -> IoResult<Self> {
+        let sock = raw_open_socket(addr)?;
+        Ok(Self::from_socket(sock))
+    }
+
+    /// Gets a shared reference to the underlying socket object
+    fn as_raw_socket(&self) -> &socket2::Socket {
+        &self.0
+    }
+
+    /// Gets a mutable reference to the underlying socket object
+    fn as_raw_socket_mut(&mut self) -> &mut socket2::Socket {
+        &mut self.0
+    }
+
+    /// Writes a normal CAN 2.0 frame to the socket.
+    fn write_frame<F>(&self, frame: &F) -> IoResult<()>
+    where
+        F: Into<CanFrame> + AsPtr,
+    {
+        self.1.check_write()?;
+        self.as_raw_socket().write_all(frame.as_bytes())
+    }
+
+    /// Writes a batch of frames to the socket with a single system call.
+    fn write_frames<F>(&self, frames: &[F]) -> IoResult<usize>
+    where
+        F: Into<CanFrame> + AsPtr,
+    {
+        self.1.check_write()?;
+        raw_write_frames(self.as_raw_fd(), frames)
+    }
+
+    /// Reads a normal CAN 2.0 frame from the socket.
+    fn read_frame(&self) -> IoResult<CanFrame> {
+        let frame = self.read_raw_frame()?;
+        Ok(frame.into())
+    }
+}
+
+// ===== embedded_can I/O traits =====
+
+impl embedded_can::blocking::Can for CanSocket {
+    type Frame = CanFrame;
+    type Error = Error;
 
     /// Blocking call to receive the next frame from the bus.
     ///
@@ -607,7 +1583,7 @@ impl AsRawFd for CanSocket {
 
 impl From<OwnedFd> for CanSocket {
     fn from(fd: OwnedFd) -> Self {
-        Self(socket2::Socket::from(fd))
+        Self::from_socket(socket2::Socket::from(fd))
     }
 }
 
@@ -625,12 +1601,14 @@ impl AsFd for CanSocket {
 
 impl Read for CanSocket {
     fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        self.1.check_read()?;
         self.0.read(buf)
     }
 }
 
 impl Write for CanSocket {
     fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.1.check_write()?;
         self.0.write(buf)
     }
 
@@ -639,6 +1617,162 @@ impl Write for CanSocket {
     }
 }
 
+// ===== CanSocketPair =====
+
+/// A pair of connected CAN sockets for in-process testing.
+///
+/// SocketCAN does not support `socketpair(2)`, so the pair is built on a
+/// temporary virtual CAN (vcan) interface that is created, brought up, and
+/// bound by both sockets. Frames written to one socket are received by the
+/// other through the normal CAN loopback.
+///
+/// The interface is deleted when the pair is dropped.
+///
+/// PRIVILEGED: Creating the interface requires root privilege.
+#[cfg(feature = "netlink")]
+#[derive(Debug)]
+pub struct CanSocketPair {
+    sockets: (CanSocket, CanSocket),
+    iface: Option<crate::CanInterface>,
+    name: String,
+}
+
+#[cfg(feature = "netlink")]
+impl CanSocketPair {
+    /// Gets references to the two sockets in the pair.
+    pub fn sockets(&self) -> (&CanSocket, &CanSocket) {
+        (&self.sockets.0, &self.sockets.1)
+    }
+
+    /// Gets the name of the temporary vcan interface backing the pair.
+    pub fn interface_name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[cfg(feature = "netlink")]
+impl Drop for CanSocketPair {
+    fn drop(&mut self) {
+        if let Some(iface) = self.iface.take() {
+            let _ = iface.delete();
+        }
+    }
+}
+
+#[cfg(feature = "netlink")]
+impl CanSocket {
+    /// Creates a pair of CAN sockets connected to each other.
+    ///
+    /// This creates a temporary vcan interface with a unique name, and opens
+    /// two sockets on it. See [`CanSocketPair`].
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub fn pair() -> IoResult<CanSocketPair> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let to_io_err = |err: neli::err::NlError| IoError::new(IoErrorKind::Other, err.to_string());
+
+        let n = COUNT.fetch_add(1, Ordering::Relaxed);
+        let name = format!("vcp{}_{}", std::process::id() % 10_000_000, n % 10_000);
+
+        let iface = crate::CanInterface::create_vcan(&name, None).map_err(to_io_err)?;
+
+        // From here on, the pair owns the interface and will clean it up
+        // on any error.
+        let open_pair = || -> IoResult<(CanSocket, CanSocket)> {
+            iface.bring_up().map_err(to_io_err)?;
+            Ok((CanSocket::open(&name)?, CanSocket::open(&name)?))
+        };
+
+        match open_pair() {
+            Ok(sockets) => Ok(CanSocketPair {
+                sockets,
+                iface: Some(iface),
+                name,
+            }),
+            Err(err) => {
+                let _ = iface.delete();
+                Err(err)
+            }
+        }
+    }
+}
+
+// ===== FrameKind =====
+
+/// The kinds of frames that a `CanFdSocket` will return from a read.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameKind {
+    /// Both classic CAN 2.0 and FD frames
+    #[default]
+    Any,
+    /// Only classic CAN 2.0 frames (data, remote, and error)
+    Classic,
+    /// Only FD frames
+    Fd,
+}
+
+impl FrameKind {
+    /// Determines if a frame that was read is of this kind.
+    pub fn matches(&self, frame: &CanRawFrame) -> bool {
+        match self {
+            FrameKind::Any => true,
+            FrameKind::Classic => matches!(frame, CanRawFrame::Classic(_)),
+            FrameKind::Fd => matches!(frame, CanRawFrame::Fd(_)),
+        }
+    }
+}
+
+// ===== KernelFeature =====
+
+/// The optional CAN features that the running kernel may or may not
+/// support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KernelFeature {
+    /// Raw CAN sockets, `CAN_RAW`
+    Raw,
+    /// CAN FD frames on raw sockets
+    RawFd,
+    /// The Broadcast Manager, `CAN_BCM`
+    Bcm,
+    /// ISO-TP (ISO 15765-2) sockets, `CAN_ISOTP`
+    IsoTp,
+    /// SAE J1939 sockets, `CAN_J1939`
+    J1939,
+}
+
+/// Determines if the running kernel supports a CAN feature.
+///
+/// This tries to open a socket for the relevant protocol, which fails
+/// with `EPROTONOSUPPORT` if the protocol isn't built into the kernel and
+/// can't be loaded as a module, or with `EAFNOSUPPORT` if the kernel has
+/// no CAN support at all. For FD frames, it also tries to enable them on
+/// a raw socket. Any failure is reported as the feature being unsupported.
+///
+/// No interface is needed, and the socket is closed before returning.
+pub fn kernel_supports(feature: KernelFeature) -> bool {
+    use socket2::Type;
+
+    match feature {
+        KernelFeature::Raw => raw_probe_protocol(Type::RAW, CAN_RAW).is_ok(),
+        KernelFeature::RawFd => raw_probe_protocol(Type::RAW, CAN_RAW)
+            .and_then(|sock| CanFdSocket::set_fd_mode(sock, true))
+            .is_ok(),
+        KernelFeature::Bcm => raw_probe_protocol(Type::DGRAM, libc::CAN_BCM).is_ok(),
+        KernelFeature::IsoTp => raw_probe_protocol(Type::DGRAM, libc::CAN_ISOTP).is_ok(),
+        KernelFeature::J1939 => raw_probe_protocol(Type::DGRAM, libc::CAN_J1939).is_ok(),
+    }
+}
+
+/// Tries to create an unbound CAN socket for the protocol.
+fn raw_probe_protocol(ty: socket2::Type, protocol: c_int) -> IoResult<socket2::Socket> {
+    let af_can = socket2::Domain::from(AF_CAN);
+    socket2::Socket::new_raw(af_can, ty, Some(socket2::Protocol::from(protocol)))
+}
+
 // ===== CanFdSocket =====
 
 /// A socket for CAN FD devices.
@@ -647,7 +1781,7 @@ impl Write for CanSocket {
 /// or CAN Flexible Data (FD) frames with up to 64-bytes of data.
 #[allow(missing_copy_implementations)]
 #[derive(Debug)]
-pub struct CanFdSocket(socket2::Socket);
+pub struct CanFdSocket(socket2::Socket, FrameKind);
 
 impl CanFdSocket {
     // Enable or disable FD mode on a socket.
@@ -670,14 +1804,124 @@ impl CanFdSocket {
         }
     }
 
+    /// Opens a named CAN FD device in non-blocking mode.
+    ///
+    /// See [`CanSocket::open_nonblocking()`].
+    pub fn open_nonblocking(ifname: &str) -> IoResult<Self> {
+        let addr = CanAddr::from_iface(ifname)?;
+        raw_open_socket_nonblocking(&addr)
+            .and_then(|sock| Self::set_fd_mode(sock, true))
+            .map(Self::from_socket)
+    }
+
+    // Wraps an FD socket, initially returning any kind of frame.
+    fn from_socket(sock: socket2::Socket) -> Self {
+        Self(sock, FrameKind::Any)
+    }
+
+    /// Gets the kind of frames returned by reads on the socket.
+    pub fn frame_kind_filter(&self) -> FrameKind {
+        self.1
+    }
+
+    /// Sets the kind of frames returned by reads on the socket.
+    ///
+    /// SocketCAN can't filter frames by type, so this is enforced in user
+    /// space: `read_raw_frame()` and `read_frame()` read and discard any
+    /// frames of the wrong kind until one of the right kind arrives. The
+    /// kernel still delivers all of the frames to the socket, so this
+    /// doesn't reduce the load on the receive queue. It also doesn't apply
+    /// to reads through the `std::io::Read` trait.
+    pub fn set_frame_kind_filter(&mut self, kind: FrameKind) {
+        self.1 = kind;
+    }
+
     /// Reads a raw CAN frame from the socket.
     ///
     /// This might be either type of CAN frame, a classic CAN 2.0 frame
-    /// or an FD frame.
+    /// or an FD frame, unless restricted with `set_frame_kind_filter()`.
     pub fn read_raw_frame(&self) -> IoResult<CanRawFrame> {
-        let mut fdframe = canfd_frame_default();
+        loop {
+            let mut fdframe = canfd_frame_default();
+            let n = raw_recv(self.as_raw_fd(), as_bytes_mut(&mut fdframe))?;
+            let frame = Self::raw_frame_from_read(fdframe, n)?;
+            if self.1.matches(&frame) {
+                return Ok(frame);
+            }
+        }
+    }
 
-        match self.as_raw_socket().read(as_bytes_mut(&mut fdframe))? {
+    /// Reads a frame from the socket, along with the message flags that
+    /// the kernel reported for it.
+    ///
+    /// The flags contain `DONTROUTE` for a frame that was sent from the
+    /// local host, and `CONFIRM` for one that was sent from this socket
+    /// (which is only received with `set_recv_own_msgs(true)`). `TRUNC`
+    /// would indicate that the frame didn't fit in the buffer.
+    ///
+    /// Like `read_raw_frame()`, this respects the frame kind filter.
+    pub fn read_frame_meta(&self) -> IoResult<(CanAnyFrame, MsgFlags)> {
+        loop {
+            let mut fdframe = canfd_frame_default();
+            let (n, _, flags) =
+                raw_recv_with_timestamp(self.as_raw_fd(), as_bytes_mut(&mut fdframe))?;
+            let frame = Self::raw_frame_from_read(fdframe, n)?;
+            if self.1.matches(&frame) {
+                return Ok((frame.into(), MsgFlags::from_bits_truncate(flags)));
+            }
+        }
+    }
+
+    /// Gets the MTU of the interface that the socket is bound to.
+    ///
+    /// This is `CANFD_MTU` (72) for an interface that is configured for
+    /// CAN FD, or `CAN_MTU` (16) for one that only supports classic CAN 2.0
+    /// frames. Writing an FD frame to the latter fails, so this can be used
+    /// to pick the type of frames to send.
+    ///
+    /// An error of kind `InvalidInput` is returned if the socket is bound
+    /// to all interfaces, rather than a specific one.
+    pub fn interface_mtu(&self) -> IoResult<u32> {
+        let mut addr: libc::sockaddr_can = unsafe { mem::zeroed() };
+        let mut addr_len = size_of::<libc::sockaddr_can>() as socklen_t;
+
+        let ret = unsafe {
+            libc::getsockname(
+                self.as_raw_fd(),
+                &mut addr as *mut _ as *mut libc::sockaddr,
+                &mut addr_len,
+            )
+        };
+        if ret != 0 {
+            return Err(IoError::last_os_error());
+        }
+
+        let ifindex = CanAddr::from(addr).ifindex();
+        if ifindex == 0 {
+            return Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "socket is not bound to a specific interface",
+            ));
+        }
+
+        let mut ifr: libc::ifreq = unsafe { mem::zeroed() };
+        if unsafe { libc::if_indextoname(ifindex, ifr.ifr_name.as_mut_ptr()) }.is_null() {
+            return Err(IoError::last_os_error());
+        }
+
+        match unsafe { libc::ioctl(self.as_raw_fd(), libc::SIOCGIFMTU, &mut ifr) } {
+            0 => Ok(unsafe { ifr.ifr_ifru.ifru_mtu } as u32),
+            _ => Err(IoError::last_os_error()),
+        }
+    }
+
+    // Determines the type of frame from the number of bytes read.
+    //
+    // The kernel always delivers a whole frame per read, so the size of
+    // the read is exactly CAN_MTU for a classic frame or CANFD_MTU for an
+    // FD frame. Anything else is an error.
+    fn raw_frame_from_read(fdframe: canfd_frame, n: usize) -> IoResult<CanRawFrame> {
+        match n {
             // If we only get 'can_frame' number of bytes, then the return is,
             // by definition, a can_frame, so we just copy the bytes into the
             // proper type.
@@ -686,8 +1930,14 @@ impl CanFdSocket {
                 as_bytes_mut(&mut frame)[..CAN_MTU].copy_from_slice(&as_bytes(&fdframe)[..CAN_MTU]);
                 Ok(frame.into())
             }
-            CANFD_MTU => Ok(fdframe.into()),
-            _ => Err(IoError::last_os_error()),
+            CANFD_MTU => {
+                debug_assert!(usize::from(fdframe.len) <= CANFD_MAX_DLEN);
+                Ok(fdframe.into())
+            }
+            _ => Err(IoError::new(
+                IoErrorKind::InvalidData,
+                format!("unexpected CAN frame size: {} bytes", n),
+            )),
         }
     }
 }
@@ -700,7 +1950,7 @@ impl Socket for CanFdSocket {
     fn open_addr(addr: &CanAddr) -> IoResult<Self> {
         raw_open_socket(addr)
             .and_then(|sock| Self::set_fd_mode(sock, true))
-            .map(Self)
+            .map(Self::from_socket)
     }
 
     /// Gets a shared reference to the underlying socket object
@@ -723,20 +1973,7 @@ impl Socket for CanFdSocket {
 
     /// Reads either type of CAN frame from the socket.
     fn read_frame(&self) -> IoResult<CanAnyFrame> {
-        let mut fdframe = canfd_frame_default();
-
-        match self.as_raw_socket().read(as_bytes_mut(&mut fdframe))? {
-            // If we only get 'can_frame' number of bytes, then the return is,
-            // by definition, a can_frame, so we just copy the bytes into the
-            // proper type.
-            CAN_MTU => {
-                let mut frame = can_frame_default();
-                as_bytes_mut(&mut frame)[..CAN_MTU].copy_from_slice(&as_bytes(&fdframe)[..CAN_MTU]);
-                Ok(CanFrame::from(frame).into())
-            }
-            CANFD_MTU => Ok(CanFdFrame::from(fdframe).into()),
-            _ => Err(IoError::last_os_error()),
-        }
+        self.read_raw_frame().map(CanAnyFrame::from)
     }
 }
 
@@ -803,7 +2040,7 @@ impl AsRawFd for CanFdSocket {
 
 impl From<OwnedFd> for CanFdSocket {
     fn from(fd: OwnedFd) -> CanFdSocket {
-        Self(socket2::Socket::from(fd))
+        Self::from_socket(socket2::Socket::from(fd))
     }
 }
 
@@ -811,9 +2048,9 @@ impl TryFrom<CanSocket> for CanFdSocket {
     type Error = IoError;
 
     fn try_from(sock: CanSocket) -> std::result::Result<Self, Self::Error> {
-        let CanSocket(sock2) = sock;
+        let CanSocket(sock2, _) = sock;
         let sock = CanFdSocket::set_fd_mode(sock2, true)?;
-        Ok(CanFdSocket(sock))
+        Ok(CanFdSocket::from_socket(sock))
     }
 }
 
@@ -871,6 +2108,73 @@ impl CanFilter {
     pub fn new_inverted(id: canid_t, mask: canid_t) -> Self {
         Self::new(id | libc::CAN_INV_FILTER, mask)
     }
+
+    /// Creates the minimal set of filters that accept all the IDs in the
+    /// inclusive range, `[lo, hi]`, and no others.
+    ///
+    /// This is similar to breaking an IP address range into network
+    /// prefixes: the range is split into aligned, power-of-two blocks of
+    /// IDs, each of which can be matched with a single id/mask pair.
+    ///
+    /// The filters only match frames with the same type of ID (standard or
+    /// extended) as the range. If `lo` is a standard ID and `hi` is an
+    /// extended one, the range covers the standard IDs from `lo` up, and
+    /// the extended IDs up to `hi`. An empty range gives no filters.
+    pub fn from_range(lo: Id, hi: Id) -> Vec<Self> {
+        use libc::{CAN_EFF_FLAG, CAN_EFF_MASK, CAN_SFF_MASK};
+
+        match (lo, hi) {
+            (Id::Standard(lo), Id::Standard(hi)) => {
+                Self::range_filters(lo.as_raw().into(), hi.as_raw().into(), CAN_SFF_MASK, 0)
+            }
+            (Id::Extended(lo), Id::Extended(hi)) => {
+                Self::range_filters(lo.as_raw(), hi.as_raw(), CAN_EFF_MASK, CAN_EFF_FLAG)
+            }
+            (Id::Standard(lo), Id::Extended(hi)) => {
+                let mut filters =
+                    Self::range_filters(lo.as_raw().into(), CAN_SFF_MASK, CAN_SFF_MASK, 0);
+                filters.extend(Self::range_filters(
+                    0,
+                    hi.as_raw(),
+                    CAN_EFF_MASK,
+                    CAN_EFF_FLAG,
+                ));
+                filters
+            }
+            (Id::Extended(_), Id::Standard(_)) => Vec::new(),
+        }
+    }
+
+    // Splits the range of ID's into aligned blocks, with a filter for each.
+    // The EFF flag is always in the mask, and the `flags` are set in the
+    // filter ID, to select the type of ID to match.
+    fn range_filters(lo: canid_t, hi: canid_t, id_mask: canid_t, flags: canid_t) -> Vec<Self> {
+        let mut filters = Vec::new();
+        let (mut cur, hi) = (u64::from(lo), u64::from(hi));
+
+        while cur <= hi {
+            // The largest aligned block starting at `cur` that fits in range
+            let mut size = 1u64;
+            while cur % (size * 2) == 0 && cur + size * 2 - 1 <= hi {
+                size *= 2;
+            }
+            let mask = id_mask & !((size - 1) as canid_t);
+            filters.push(Self::new(cur as canid_t | flags, mask | libc::CAN_EFF_FLAG));
+            cur += size;
+        }
+        filters
+    }
+
+    /// Determines if the filter accepts the frame, using the same test as
+    /// the kernel: the frame's ID word matches if it is equal to the filter
+    /// ID in all of the bits set in the mask. An inverted filter accepts
+    /// the frames that don't match.
+    pub fn matches<F: Frame>(&self, frame: &F) -> bool {
+        let id = self.0.can_id & !libc::CAN_INV_FILTER;
+        let mask = self.0.can_mask;
+        let matched = (frame.id_word() & mask) == (id & mask);
+        matched != (self.0.can_id & libc::CAN_INV_FILTER != 0)
+    }
 }
 
 impl From<libc::can_filter> for CanFilter {
@@ -890,3 +2194,399 @@ impl AsRef<libc::can_filter> for CanFilter {
         &self.0
     }
 }
+
+/// Displays the filter as `id:mask` in hex, with a `!` prefix if the
+/// filter is inverted, such as `123:7FF` or `!123:7FF`.
+impl fmt::Display for CanFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let id = self.0.can_id;
+        if id & libc::CAN_INV_FILTER != 0 {
+            f.write_str("!")?;
+        }
+        write!(
+            f,
+            "{:03X}:{:03X}",
+            id & !libc::CAN_INV_FILTER,
+            self.0.can_mask
+        )
+    }
+}
+
+/// Parses a filter from an `id:mask` string in hex, with a `!` prefix for
+/// an inverted filter.
+///
+/// The can-utils form for an inverted filter, `id~mask`, is also accepted.
+impl std::str::FromStr for CanFilter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (s, inverted) = match s.strip_prefix('!') {
+            Some(s) => (s, true),
+            None => (s, false),
+        };
+        let (id, mask, inverted) = match (s.split_once(':'), s.split_once('~')) {
+            (Some((id, mask)), None) => (id, mask, inverted),
+            (None, Some((id, mask))) if !inverted => (id, mask, true),
+            _ => return Err(IoError::from(IoErrorKind::InvalidInput).into()),
+        };
+
+        let parse = |s: &str| {
+            canid_t::from_str_radix(s, 16).map_err(|_| IoError::from(IoErrorKind::InvalidInput))
+        };
+        let (id, mask) = (parse(id)?, parse(mask)?);
+
+        Ok(match inverted {
+            true => Self::new_inverted(id, mask),
+            false => Self::new(id, mask),
+        })
+    }
+}
+
+// ===== SocketBuilder =====
+
+/// A builder to open a socket with all of its options configured.
+///
+/// Any option that isn't specified is left at the kernel default. The
+/// options are applied when the socket is opened, and if any of them
+/// fails, the socket is closed and the error returned, so a socket is
+/// never handed back half-configured.
+///
+/// ```no_run
+/// use socketcan::{CanFilter, CanSocket, SocketBuilder};
+///
+/// let sock: CanSocket = SocketBuilder::new()
+///     .interface("can0")
+///     .nonblocking(true)
+///     .filters(&[CanFilter::new(0x100, 0x7FF)])
+///     .loopback(false)
+///     .open()
+///     .unwrap();
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct SocketBuilder {
+    /// The name of the interface, if opened by name
+    ifname: Option<String>,
+    /// The interface index, if opened by index
+    ifindex: u32,
+    /// Whether the socket should be nonblocking
+    nonblocking: bool,
+    /// The CAN ID filters
+    filters: Option<Vec<CanFilter>>,
+    /// The error mask
+    error_mask: Option<u32>,
+    /// Whether loopback is enabled
+    loopback: Option<bool>,
+    /// Whether the socket receives its own frames
+    recv_own_msgs: Option<bool>,
+}
+
+impl SocketBuilder {
+    /// Creates a new builder, which would open a socket on all interfaces
+    /// with the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the name of the interface to open, like "can0".
+    pub fn interface(mut self, ifname: &str) -> Self {
+        self.ifname = Some(ifname.to_string());
+        self
+    }
+
+    /// Sets the index of the interface to open.
+    ///
+    /// An index of zero opens the socket on all interfaces.
+    pub fn iface(mut self, ifindex: u32) -> Self {
+        self.ifname = None;
+        self.ifindex = ifindex;
+        self
+    }
+
+    /// Sets whether the socket is opened in nonblocking mode.
+    pub fn nonblocking(mut self, nonblocking: bool) -> Self {
+        self.nonblocking = nonblocking;
+        self
+    }
+
+    /// Sets the CAN ID filters for the socket.
+    pub fn filters<F>(mut self, filters: &[F]) -> Self
+    where
+        F: Into<CanFilter> + Copy,
+    {
+        self.filters = Some(filters.iter().map(|f| (*f).into()).collect());
+        self
+    }
+
+    /// Sets the error mask for the socket.
+    pub fn error_mask(mut self, mask: u32) -> Self {
+        self.error_mask = Some(mask);
+        self
+    }
+
+    /// Sets whether loopback is enabled on the socket.
+    pub fn loopback(mut self, enabled: bool) -> Self {
+        self.loopback = Some(enabled);
+        self
+    }
+
+    /// Sets whether the socket receives the frames that it sends.
+    pub fn recv_own(mut self, enabled: bool) -> Self {
+        self.recv_own_msgs = Some(enabled);
+        self
+    }
+
+    /// Opens the socket and applies all of the options.
+    pub fn open<S>(&self) -> IoResult<S>
+    where
+        S: Socket + SocketOptions,
+    {
+        let sock = match &self.ifname {
+            Some(ifname) => S::open(ifname)?,
+            None => S::open_iface(self.ifindex)?,
+        };
+
+        if self.nonblocking {
+            sock.set_nonblocking(true)?;
+        }
+        if let Some(filters) = &self.filters {
+            sock.set_filters(filters)?;
+        }
+        if let Some(mask) = self.error_mask {
+            sock.set_error_mask(mask)?;
+        }
+        if let Some(enabled) = self.loopback {
+            sock.set_loopback(enabled)?;
+        }
+        if let Some(enabled) = self.recv_own_msgs {
+            sock.set_recv_own_msgs(enabled)?;
+        }
+        Ok(sock)
+    }
+
+    /// Opens a CAN FD socket and applies all of the options.
+    pub fn open_fd(&self) -> IoResult<CanFdSocket> {
+        self.open()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// A socket that refuses to send anything, as if the TX queue is full.
+    #[derive(Debug)]
+    struct BlockedSocket {
+        sock: socket2::Socket,
+        n_writes: Cell<usize>,
+    }
+
+    impl BlockedSocket {
+        fn new() -> Self {
+            let sock =
+                socket2::Socket::new(socket2::Domain::UNIX, socket2::Type::DGRAM, None).unwrap();
+            Self {
+                sock,
+                n_writes: Cell::new(0),
+            }
+        }
+    }
+
+    impl AsRawFd for BlockedSocket {
+        fn as_raw_fd(&self) -> RawFd {
+            self.sock.as_raw_fd()
+        }
+    }
+
+    impl Socket for BlockedSocket {
+        type FrameType = CanFrame;
+
+        fn open_addr(_addr: &CanAddr) -> IoResult<Self> {
+            Ok(Self::new())
+        }
+
+        fn as_raw_socket(&self) -> &socket2::Socket {
+            &self.sock
+        }
+
+        fn as_raw_socket_mut(&mut self) -> &mut socket2::Socket {
+            &mut self.sock
+        }
+
+        fn read_frame(&self) -> IoResult<CanFrame> {
+            Err(IoErrorKind::WouldBlock.into())
+        }
+
+        fn write_frame<F>(&self, _frame: &F) -> IoResult<()>
+        where
+            F: Into<CanFrame> + AsPtr,
+        {
+            self.n_writes.set(self.n_writes.get() + 1);
+            Err(IoErrorKind::WouldBlock.into())
+        }
+    }
+
+    #[test]
+    fn test_fd_read_frame_type() {
+        let mut fdframe = canfd_frame_default();
+        fdframe.can_id = 0x123;
+        fdframe.len = 4;
+
+        let frame = CanFdSocket::raw_frame_from_read(fdframe, CAN_MTU).unwrap();
+        assert!(matches!(frame, CanRawFrame::Classic(_)));
+        assert!(matches!(CanAnyFrame::from(frame), CanAnyFrame::Normal(_)));
+
+        let frame = CanFdSocket::raw_frame_from_read(fdframe, CANFD_MTU).unwrap();
+        assert!(matches!(frame, CanRawFrame::Fd(_)));
+        assert!(matches!(CanAnyFrame::from(frame), CanAnyFrame::Fd(_)));
+
+        let res = CanFdSocket::raw_frame_from_read(fdframe, CAN_MTU - 1);
+        assert!(matches!(res, Err(err) if err.kind() == IoErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn test_write_frame_retries() {
+        const N: usize = 5;
+
+        let sock = BlockedSocket::new();
+        let frame = CanFrame::from_raw_id(0x123, &[1, 2, 3]).unwrap();
+
+        let res = sock.write_frame_retries(&frame, N);
+        assert!(res.should_retry());
+        assert_eq!(sock.n_writes.get(), N + 1);
+
+        let sock = BlockedSocket::new();
+        assert!(sock.write_frame_retries(&frame, 0).is_err());
+        assert_eq!(sock.n_writes.get(), 1);
+    }
+
+    #[test]
+    fn test_try_from_fd() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let res = CanSocket::try_from_fd(OwnedFd::from(listener));
+        assert!(matches!(res, Err(err) if err.kind() == IoErrorKind::InvalidInput));
+
+        let (sock, _) =
+            socket2::Socket::pair(socket2::Domain::UNIX, socket2::Type::DGRAM, None).unwrap();
+        let res = CanSocket::try_from_fd(OwnedFd::from(sock));
+        assert!(matches!(res, Err(err) if err.kind() == IoErrorKind::InvalidInput));
+    }
+
+    // Determines if a filter would accept the (raw) CAN ID word
+    fn filter_matches(filters: &[CanFilter], id: canid_t) -> bool {
+        filters
+            .iter()
+            .any(|f| id & f.0.can_mask == f.0.can_id & f.0.can_mask)
+    }
+
+    #[test]
+    fn test_filter_from_range() {
+        use libc::CAN_EFF_FLAG;
+
+        let std_id = |id| Id::Standard(StandardId::new(id).unwrap());
+        let ext_id = |id| Id::Extended(ExtendedId::new(id).unwrap());
+
+        // An aligned block needs a single filter
+        let filters = CanFilter::from_range(std_id(0x100), std_id(0x1FF));
+        assert_eq!(filters.len(), 1);
+
+        // A single ID
+        let filters = CanFilter::from_range(std_id(0x123), std_id(0x123));
+        assert_eq!(filters, vec![CanFilter::new(0x123, 0x7FF | CAN_EFF_FLAG)]);
+
+        // An empty range
+        assert!(CanFilter::from_range(std_id(0x200), std_id(0x100)).is_empty());
+
+        for (lo, hi) in [(0x123, 0x456), (0, 0x7FF), (0x001, 0x7FE), (0x7FF, 0x7FF)] {
+            let filters = CanFilter::from_range(std_id(lo), std_id(hi));
+            for id in 0..=0x7FF {
+                let expected = (u32::from(lo)..=u32::from(hi)).contains(&id);
+                assert_eq!(filter_matches(&filters, id), expected);
+                // Never matches an extended ID
+                assert!(!filter_matches(&filters, id | CAN_EFF_FLAG));
+            }
+        }
+
+        let (lo, hi) = (0x1234_5678, 0x1234_9ABC);
+        let filters = CanFilter::from_range(ext_id(lo), ext_id(hi));
+        for id in (lo - 0x1000)..=(hi + 0x1000) {
+            let expected = (lo..=hi).contains(&id);
+            assert_eq!(filter_matches(&filters, id | CAN_EFF_FLAG), expected);
+        }
+        assert!(!filter_matches(&filters, 0x678));
+
+        // Standard up to extended
+        let filters = CanFilter::from_range(std_id(0x700), ext_id(0x10));
+        assert!(filter_matches(&filters, 0x700));
+        assert!(filter_matches(&filters, 0x7FF));
+        assert!(!filter_matches(&filters, 0x6FF));
+        assert!(filter_matches(&filters, CAN_EFF_FLAG));
+        assert!(filter_matches(&filters, 0x10 | CAN_EFF_FLAG));
+        assert!(!filter_matches(&filters, 0x11 | CAN_EFF_FLAG));
+    }
+
+    #[test]
+    fn test_raw_recv_truncated() {
+        let (tx, rx) =
+            socket2::Socket::pair(socket2::Domain::UNIX, socket2::Type::DGRAM, None).unwrap();
+
+        tx.send(&[0u8; CAN_MTU]).unwrap();
+        let mut buf = [0u8; CANFD_MTU];
+        assert_eq!(CAN_MTU, raw_recv(rx.as_raw_fd(), &mut buf).unwrap());
+
+        // A buffer too small for the message reports the truncation
+        tx.send(&[0u8; CANFD_MTU]).unwrap();
+        let mut buf = [0u8; CAN_MTU];
+        let err = raw_recv(rx.as_raw_fd(), &mut buf).unwrap_err();
+        assert_eq!(IoErrorKind::InvalidData, err.kind());
+        assert_eq!("received CAN frame was truncated", err.to_string());
+    }
+
+    #[test]
+    fn test_kernel_supports_absent_protocol() {
+        // CAN_MCNET was reserved, but never implemented in the kernel
+        assert!(raw_probe_protocol(socket2::Type::DGRAM, libc::CAN_MCNET).is_err());
+    }
+
+    #[test]
+    fn test_filter_string() {
+        let filter: CanFilter = "123:7FF".parse().unwrap();
+        assert_eq!(CanFilter::new(0x123, 0x7FF), filter);
+        assert_eq!("123:7FF", filter.to_string());
+
+        let filter: CanFilter = "!123:7FF".parse().unwrap();
+        assert_eq!(CanFilter::new_inverted(0x123, 0x7FF), filter);
+        assert_eq!("!123:7FF", filter.to_string());
+        assert_eq!(filter, "123~7FF".parse().unwrap());
+
+        let filter = CanFilter::new(0x80012345, 0x9FFFFFFF);
+        assert_eq!("80012345:9FFFFFFF", filter.to_string());
+        assert_eq!(filter, filter.to_string().parse().unwrap());
+
+        assert!("123".parse::<CanFilter>().is_err());
+        assert!("123:XYZ".parse::<CanFilter>().is_err());
+        assert!("!123~7FF".parse::<CanFilter>().is_err());
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let frame = |id: u16| CanFrame::new(StandardId::new(id).unwrap(), &[]).unwrap();
+
+        let filter = CanFilter::new(0x120, 0x7F0);
+        assert!(filter.matches(&frame(0x120)));
+        assert!(filter.matches(&frame(0x12F)));
+        assert!(!filter.matches(&frame(0x130)));
+
+        let filter = CanFilter::new_inverted(0x120, 0x7F0);
+        assert!(!filter.matches(&frame(0x12F)));
+        assert!(filter.matches(&frame(0x130)));
+
+        let ext = CanFrame::new(ExtendedId::new(0x120).unwrap(), &[]).unwrap();
+        let filter = CanFilter::new(0x120, 0x7FF | libc::CAN_EFF_FLAG);
+        assert!(filter.matches(&frame(0x120)));
+        assert!(!filter.matches(&ext));
+    }
+}
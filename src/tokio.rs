@@ -27,9 +27,14 @@
 //! }
 //! ```
 use crate::{
-    frame::AsPtr, CanAddr, CanAnyFrame, CanFrame, Error, IoResult, Result, Socket, SocketOptions,
+    frame::AsPtr, CanAddr, CanAnyFrame, CanFrame, EmbeddedFrame, Error, Frame, IoError,
+    IoErrorKind, IoResult, MsgFlags, Result, Socket, SocketOptions,
 };
+#[cfg(feature = "netlink")]
+use crate::{nl::InterfaceDetails, InterfaceCanParams};
 use futures::{prelude::*, ready, task::Context};
+#[cfg(feature = "netlink")]
+use neli::{consts::rtnl::Rtm, err::NlError, rtnl::Ifinfomsg};
 use std::{
     io::{Read, Write},
     os::unix::{
@@ -38,6 +43,7 @@ use std::{
     },
     pin::Pin,
     task::Poll,
+    time::Duration,
 };
 use tokio::io::unix::AsyncFd;
 use tokio::io::Interest;
@@ -95,6 +101,70 @@ impl CanSocket {
             .async_io(Interest::READABLE, |inner| inner.read_frame())
             .await
     }
+
+    /// Write a CAN frame to the socket, and wait for its loopback echo as
+    /// confirmation that it was sent.
+    ///
+    /// This enables loopback and the receipt of our own messages on the
+    /// socket, which are left on afterward. On real hardware, the echo is
+    /// generated when the controller reports that the frame was sent, so
+    /// this verifies the transmission from end to end. An error of kind
+    /// `TimedOut` is returned if the echo isn't seen within the timeout.
+    ///
+    /// Any other frames that arrive while waiting for the echo are read and
+    /// discarded.
+    pub async fn send_and_confirm(&self, frame: CanFrame, timeout: Duration) -> IoResult<()> {
+        self.set_loopback(true)?;
+        self.set_recv_own_msgs(true)?;
+        self.write_frame(frame).await?;
+
+        let confirm = async {
+            loop {
+                let (echo, flags) = self
+                    .0
+                    .async_io(Interest::READABLE, |inner| inner.read_frame_meta())
+                    .await?;
+                if flags.contains(MsgFlags::CONFIRM)
+                    && echo.id_word() == frame.id_word()
+                    && echo.data() == frame.data()
+                {
+                    return Ok(());
+                }
+            }
+        };
+
+        tokio::time::timeout(timeout, confirm)
+            .await
+            .map_err(|_| IoError::from(IoErrorKind::TimedOut))?
+    }
+
+    /// Read a CAN frame and the index of the interface it arrived on,
+    /// asynchronously.
+    pub async fn read_frame_with_iface(&self) -> IoResult<(CanFrame, u32)> {
+        self.0
+            .async_io(Interest::READABLE, |inner| inner.read_frame_with_iface())
+            .await
+    }
+
+    /// Gets a stream of frames paired with the index of the interface each
+    /// one arrived on.
+    ///
+    /// This is useful for a socket opened on all interfaces (index zero),
+    /// where the plain `Stream` implementation would lose track of which
+    /// bus each frame came from.
+    ///
+    /// This is only available with tokio. The async-io and async-std
+    /// sockets don't implement `Stream`, so they should call
+    /// `read_frame_with_iface()` in a loop instead.
+    pub fn frames_with_iface(&self) -> impl Stream<Item = Result<(CanFrame, u32)>> + '_ {
+        stream::poll_fn(move |cx| loop {
+            let mut ready_guard = ready!(self.0.poll_read_ready(cx))?;
+            match ready_guard.try_io(|inner| inner.get_ref().read_frame_with_iface()) {
+                Ok(result) => return Poll::Ready(Some(result.map_err(|e| e.into()))),
+                Err(_would_block) => continue,
+            }
+        })
+    }
 }
 
 impl Stream for CanSocket {
@@ -289,6 +359,113 @@ impl AsyncWrite for CanFdSocket {
     }
 }
 
+// ===== CanInterface =====
+
+/// An asynchronous wrapper for the netlink interface to a CAN device.
+///
+/// The netlink calls in [`crate::nl::CanInterface`] block while waiting
+/// for the kernel to respond. This runs each of them on tokio's pool of
+/// blocking threads with `spawn_blocking()`, so they can be awaited
+/// without stalling the async runtime.
+///
+/// For anything not wrapped here, use `inner()` to get the blocking
+/// interface.
+#[cfg(feature = "netlink")]
+#[derive(Debug, Clone, Copy)]
+pub struct CanInterface(crate::nl::CanInterface);
+
+#[cfg(feature = "netlink")]
+impl CanInterface {
+    /// Open a CAN interface by name.
+    pub fn open(ifname: &str) -> std::result::Result<Self, nix::Error> {
+        crate::nl::CanInterface::open(ifname).map(Self)
+    }
+
+    /// Open a CAN interface by kernel interface number.
+    pub fn open_iface(if_index: u32) -> Self {
+        Self(crate::nl::CanInterface::open_iface(if_index))
+    }
+
+    /// Gets the underlying, blocking, netlink interface.
+    pub fn inner(&self) -> &crate::nl::CanInterface {
+        &self.0
+    }
+
+    // Runs the blocking netlink call on a blocking thread.
+    async fn run<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce(crate::nl::CanInterface) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let iface = self.0;
+        tokio::task::spawn_blocking(move || f(iface))
+            .await
+            .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()))
+    }
+
+    /// Bring down this interface.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub async fn bring_down(&self) -> std::result::Result<(), NlError> {
+        self.run(|iface| iface.bring_down()).await
+    }
+
+    /// Bring up this interface
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub async fn bring_up(&self) -> std::result::Result<(), NlError> {
+        self.run(|iface| iface.bring_up()).await
+    }
+
+    /// Attempt to query detailed information on the interface.
+    pub async fn details(&self) -> std::result::Result<InterfaceDetails, NlError<Rtm, Ifinfomsg>> {
+        self.run(|iface| iface.details()).await
+    }
+
+    /// Set the bitrate and, optionally, sample point of this interface.
+    ///
+    /// See [`crate::nl::CanInterface::set_bitrate()`].
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub async fn set_bitrate(
+        &self,
+        bitrate: u32,
+        sample_point: Option<u32>,
+    ) -> std::result::Result<(), NlError> {
+        self.run(move |iface| iface.set_bitrate(bitrate, sample_point))
+            .await
+    }
+
+    /// Set a CAN-specific set of parameters in a single message.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub async fn set_can_params(
+        &self,
+        params: InterfaceCanParams,
+    ) -> std::result::Result<(), NlError> {
+        self.run(move |iface| iface.set_can_params(&params)).await
+    }
+
+    /// Restart the interface after it has gone bus-off.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub async fn restart(&self) -> std::result::Result<(), NlError> {
+        self.run(|iface| iface.restart()).await
+    }
+}
+
+#[cfg(feature = "netlink")]
+impl From<crate::nl::CanInterface> for CanInterface {
+    fn from(iface: crate::nl::CanInterface) -> Self {
+        Self(iface)
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////
 
 #[cfg(feature = "vcan_tests")]
@@ -665,4 +842,76 @@ mod tests {
 
         Ok(())
     }
+
+    #[serial]
+    #[tokio::test]
+    async fn test_send_and_confirm() -> Result<()> {
+        let socket = CanSocket::open("vcan0").unwrap();
+
+        let frame = CanFrame::new(StandardId::new(0x123).unwrap(), &[1, 2, 3]).unwrap();
+        socket.send_and_confirm(frame, TIMEOUT).await?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "netlink_tests")]
+#[cfg(test)]
+mod nl_tests {
+    use super::*;
+    use crate::StandardId;
+    use futures::select;
+    use futures_timer::Delay;
+    use serial_test::serial;
+    use std::time::Duration;
+
+    #[tokio::test]
+    #[serial]
+    async fn async_bring_up() {
+        let vcan = crate::nl::CanInterface::create_vcan("async_up", None).unwrap();
+        let iface = CanInterface::from(vcan);
+
+        iface.bring_up().await.unwrap();
+        assert!(iface.details().await.unwrap().is_up);
+
+        iface.bring_down().await.unwrap();
+        assert!(!iface.details().await.unwrap().is_up);
+
+        vcan.delete().unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn frames_with_iface() -> Result<()> {
+        let pair1 = crate::CanSocket::pair()?;
+        let pair2 = crate::CanSocket::pair()?;
+        let idx1 = CanAddr::from_iface(pair1.interface_name())?.ifindex();
+        let idx2 = CanAddr::from_iface(pair2.interface_name())?.ifindex();
+
+        // Bound to all interfaces
+        let socket = CanSocket::open_if(0)?;
+        let mut frames = Box::pin(socket.frames_with_iface());
+
+        let frame1 = CanFrame::new(StandardId::new(0x101).unwrap(), &[1]).unwrap();
+        let frame2 = CanFrame::new(StandardId::new(0x202).unwrap(), &[2]).unwrap();
+        pair1.sockets().0.write_frame(&frame1)?;
+        pair2.sockets().0.write_frame(&frame2)?;
+
+        let mut n = 0;
+        while n < 2 {
+            let (frame, idx) = select!(
+                item = frames.next().fuse() => item.unwrap()?,
+                _timeout = Delay::new(Duration::from_millis(100)).fuse() => return Err(IoErrorKind::TimedOut.into()),
+            );
+            // Ignore any traffic on other interfaces
+            if idx == idx1 {
+                assert_eq!(frame.raw_id(), frame1.raw_id());
+                n += 1;
+            } else if idx == idx2 {
+                assert_eq!(frame.raw_id(), frame2.raw_id());
+                n += 1;
+            }
+        }
+
+        Ok(())
+    }
 }
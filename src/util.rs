@@ -0,0 +1,372 @@
+// socketcan/src/util.rs
+//
+// Miscellaneous utilities for working with CAN sockets.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! Miscellaneous utilities for working with CAN sockets.
+
+use crate::{frame::AsPtr, CanFrame, CanSocket, IoResult, Socket};
+use std::{
+    cell::{Cell, UnsafeCell},
+    fmt,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+// ===== RateLimiter =====
+
+/// A token-bucket rate limiter to pace the frames sent to a bus.
+///
+/// Tokens accumulate at the target rate, up to the size of the burst, and
+/// each frame sent uses one of them. When the bucket is empty, the sender
+/// sleeps until the next token is available. This is useful for tools,
+/// like log replay, which could otherwise overwhelm the bus.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimiter {
+    /// The time to accumulate a single token
+    interval: Duration,
+    /// The maximum number of tokens that can accumulate
+    burst: f64,
+    /// The number of tokens currently available
+    tokens: f64,
+    /// The last time the tokens were refilled
+    last: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a rate limiter to send, at most, `frames_per_sec` frames
+    /// per second, with no bursts.
+    ///
+    /// A rate of zero is treated as one frame per second.
+    pub fn new(frames_per_sec: u32) -> Self {
+        Self::with_burst(frames_per_sec, 1)
+    }
+
+    /// Creates a rate limiter to send, on average, `frames_per_sec` frames
+    /// per second, while allowing up to `burst` frames to be sent back to
+    /// back after the limiter has been idle.
+    pub fn with_burst(frames_per_sec: u32, burst: u32) -> Self {
+        let burst = f64::from(burst.max(1));
+        Self {
+            interval: Duration::from_secs(1) / frames_per_sec.max(1),
+            burst,
+            tokens: burst,
+            last: Instant::now(),
+        }
+    }
+
+    /// Blocks until a frame can be sent at the target rate, and uses up
+    /// the token for it.
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() / self.interval.as_secs_f64()).min(self.burst);
+        self.last = now;
+
+        if self.tokens < 1.0 {
+            let delay = self.interval.mul_f64(1.0 - self.tokens);
+            thread::sleep(delay);
+            self.tokens = 1.0;
+            self.last += delay;
+        }
+        self.tokens -= 1.0;
+    }
+
+    /// Sends a frame on the socket, sleeping first as needed to maintain
+    /// the target rate.
+    pub fn send<S, F>(&mut self, sock: &S, frame: &F) -> IoResult<()>
+    where
+        S: Socket,
+        F: Into<S::FrameType> + AsPtr,
+    {
+        self.wait();
+        sock.write_frame(frame)
+    }
+}
+
+// ===== PacedCanSocket =====
+
+/// A CAN socket that enforces a minimum gap between the frames that it
+/// sends.
+///
+/// Some devices misbehave when frames arrive back to back. Unlike the
+/// `RateLimiter`, which paces frames to an average rate and allows bursts,
+/// this sleeps before each write, as needed, so that consecutive frames
+/// are always at least the gap apart.
+#[derive(Debug)]
+pub struct PacedCanSocket {
+    /// The underlying socket
+    sock: CanSocket,
+    /// The minimum time between frames
+    gap: Duration,
+    /// The time that the last frame was sent
+    last: Cell<Option<Instant>>,
+}
+
+impl PacedCanSocket {
+    /// Wraps the socket to send frames at least `gap` apart.
+    pub fn new(sock: CanSocket, gap: Duration) -> Self {
+        Self {
+            sock,
+            gap,
+            last: Cell::new(None),
+        }
+    }
+
+    /// Gets the minimum gap between frames.
+    pub fn gap(&self) -> Duration {
+        self.gap
+    }
+
+    /// Sets the minimum gap between frames.
+    pub fn set_gap(&mut self, gap: Duration) {
+        self.gap = gap;
+    }
+
+    /// Gets a reference to the underlying socket.
+    pub fn as_socket(&self) -> &CanSocket {
+        &self.sock
+    }
+
+    /// Unwraps the underlying socket.
+    pub fn into_inner(self) -> CanSocket {
+        self.sock
+    }
+
+    /// Writes a frame to the socket, first sleeping for whatever remains
+    /// of the gap since the previous frame was sent.
+    pub fn write_frame<F>(&self, frame: &F) -> IoResult<()>
+    where
+        F: Into<CanFrame> + AsPtr,
+    {
+        if let Some(last) = self.last.get() {
+            if let Some(dur) = (last + self.gap).checked_duration_since(Instant::now()) {
+                thread::sleep(dur);
+            }
+        }
+        self.sock.write_frame(frame)?;
+        self.last.set(Some(Instant::now()));
+        Ok(())
+    }
+}
+
+// ===== FrameRing =====
+
+/// A fixed-capacity, single-producer, single-consumer ring buffer of
+/// frames.
+///
+/// This lets a receive thread hand frames off to a consumer thread without
+/// any allocation or locking. It is shared between the threads by
+/// reference (such as through an `Arc`); one thread pushes frames, as with
+/// `CanSocket::read_into_ring()`, while the other pops them.
+///
+/// Only one thread may push, and one thread may pop, at any given time.
+/// Concurrent pushes, or concurrent pops, are detected and cause a panic.
+pub struct FrameRing {
+    /// The storage for the frames
+    slots: Box<[UnsafeCell<CanFrame>]>,
+    /// The total number of frames ever popped
+    head: AtomicUsize,
+    /// The total number of frames ever pushed
+    tail: AtomicUsize,
+    /// Set while a frame is being pushed
+    pushing: AtomicBool,
+    /// Set while a frame is being popped
+    popping: AtomicBool,
+}
+
+// The producer only writes to slots that the consumer can't see until the
+// tail is published, and vice versa, and the flags prevent more than one
+// of each from running at once.
+unsafe impl Sync for FrameRing {}
+
+impl FrameRing {
+    /// Creates a ring that can hold up to `capacity` frames.
+    ///
+    /// A capacity of zero is treated as one.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity.max(1))
+                .map(|_| UnsafeCell::new(CanFrame::default()))
+                .collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            pushing: AtomicBool::new(false),
+            popping: AtomicBool::new(false),
+        }
+    }
+
+    /// Gets the maximum number of frames that the ring can hold.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Gets the number of frames currently in the ring.
+    pub fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        tail.wrapping_sub(self.head.load(Ordering::Acquire))
+    }
+
+    /// Determines if the ring is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Determines if the ring is full.
+    pub fn is_full(&self) -> bool {
+        self.len() >= self.capacity()
+    }
+
+    /// Pushes a frame onto the back of the ring.
+    ///
+    /// If the ring is full, the frame is handed back as an error.
+    pub fn push(&self, frame: CanFrame) -> Result<(), CanFrame> {
+        let _guard = RingGuard::acquire(&self.pushing, "push");
+
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail.wrapping_sub(self.head.load(Ordering::Acquire)) >= self.capacity() {
+            return Err(frame);
+        }
+        unsafe { *self.slots[tail % self.capacity()].get() = frame };
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// Pops a frame from the front of the ring, if there is one.
+    pub fn pop(&self) -> Option<CanFrame> {
+        let _guard = RingGuard::acquire(&self.popping, "pop");
+
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let frame = unsafe { *self.slots[head % self.capacity()].get() };
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(frame)
+    }
+}
+
+impl fmt::Debug for FrameRing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameRing")
+            .field("capacity", &self.capacity())
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+// Marks one side of the ring as busy for the duration of an operation.
+struct RingGuard<'a>(&'a AtomicBool);
+
+impl<'a> RingGuard<'a> {
+    fn acquire(flag: &'a AtomicBool, op: &str) -> Self {
+        if flag.swap(true, Ordering::Acquire) {
+            panic!("concurrent {} on a FrameRing", op);
+        }
+        Self(flag)
+    }
+}
+
+impl Drop for RingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EmbeddedFrame, StandardId};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_rate_limiter() {
+        const N: u32 = 11;
+        let mut limiter = RateLimiter::new(200);
+
+        let start = Instant::now();
+        for _ in 0..N {
+            limiter.wait();
+        }
+
+        // The first frame goes immediately, then each one after takes 5ms
+        assert!(start.elapsed() >= Duration::from_millis(5 * (N as u64 - 1)));
+    }
+
+    #[test]
+    fn test_rate_limiter_burst() {
+        let mut limiter = RateLimiter::with_burst(10, 5);
+
+        // The initial burst doesn't have to wait...
+        let start = Instant::now();
+        for _ in 0..5 {
+            limiter.wait();
+        }
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        // ...but the next frame does
+        limiter.wait();
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+
+    #[test]
+    fn test_frame_ring() {
+        let ring = FrameRing::new(2);
+        assert!(ring.is_empty());
+
+        let frame = |n: u8| CanFrame::new(StandardId::new(0x100).unwrap(), &[n]).unwrap();
+        assert!(ring.push(frame(1)).is_ok());
+        assert!(ring.push(frame(2)).is_ok());
+        assert!(ring.is_full());
+        assert!(ring.push(frame(3)).is_err());
+
+        assert_eq!(Some(&[1u8][..]), ring.pop().as_ref().map(|f| f.data()));
+        assert!(ring.push(frame(3)).is_ok());
+        assert_eq!(Some(&[2u8][..]), ring.pop().as_ref().map(|f| f.data()));
+        assert_eq!(Some(&[3u8][..]), ring.pop().as_ref().map(|f| f.data()));
+        assert!(ring.pop().is_none());
+    }
+
+    #[test]
+    fn test_frame_ring_concurrent() {
+        const N: u32 = 10_000;
+        let ring = Arc::new(FrameRing::new(16));
+
+        let producer = {
+            let ring = Arc::clone(&ring);
+            thread::spawn(move || {
+                for i in 0..N {
+                    let mut frame =
+                        CanFrame::new(StandardId::new(0x100).unwrap(), &i.to_le_bytes()).unwrap();
+                    while let Err(f) = ring.push(frame) {
+                        frame = f;
+                        thread::yield_now();
+                    }
+                }
+            })
+        };
+
+        let mut expected = 0;
+        while expected < N {
+            match ring.pop() {
+                Some(frame) => {
+                    assert_eq!(&expected.to_le_bytes(), frame.data());
+                    expected += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+        producer.join().unwrap();
+        assert!(ring.is_empty());
+    }
+}
@@ -11,8 +11,13 @@
 
 #[cfg(feature = "vcan_tests")]
 use socketcan::{
+    errors::ErrorMask,
     id::{ERR_MASK_ALL, ERR_MASK_NONE},
-    CanFrame, CanSocket, EmbeddedFrame, ShouldRetry, Socket, SocketOptions, StandardId,
+    socket::kernel_supports,
+    util::{FrameRing, PacedCanSocket},
+    CanAnyFrame, CanFdFrame, CanFdSocket, CanFilter, CanFrame, CanSocket, EmbeddedFrame, Frame,
+    FrameKind, KernelFeature, MsgFlags, SendFlags, ShouldRetry, Socket, SocketBuilder,
+    SocketOptions, StandardId,
 };
 
 #[cfg(feature = "vcan_tests")]
@@ -48,6 +53,27 @@ fn vcan_set_error_mask() {
     sock.set_error_mask(ERR_MASK_NONE).unwrap();
 }
 
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_set_error_mask_builder() {
+    let sock = CanSocket::open(VCAN).unwrap();
+    let mask = ErrorMask::new().bus_off().no_ack().controller();
+    sock.set_error_filter(mask.bits()).unwrap();
+    sock.set_error_filter(ErrorMask::new().none().bits())
+        .unwrap();
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_set_error_class_filter() {
+    use socketcan::ErrorClass;
+
+    let sock = CanSocket::open(VCAN).unwrap();
+    let classes = ErrorMask::from(ErrorClass::BusOff).with(ErrorClass::Controller);
+    sock.set_error_class_filter(classes).unwrap();
+    assert_eq!(classes.bits(), sock.error_filter().unwrap());
+}
+
 #[test]
 #[cfg(feature = "vcan_tests")]
 fn vcan_enable_own_loopback() {
@@ -80,6 +106,635 @@ fn vcan_test_nonblocking() {
     assert!(sock.read_frame().should_retry());
 }
 
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_open_nonblocking() {
+    let sock = CanSocket::open_nonblocking(VCAN).unwrap();
+    assert!(sock.nonblocking().unwrap());
+
+    // Filter out _any_ traffic, and the read should return immediately
+    sock.set_filter_drop_all().unwrap();
+    let err = sock.read_frame().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+
+    let sock = CanFdSocket::open_nonblocking(VCAN).unwrap();
+    assert!(sock.nonblocking().unwrap());
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_write_frames() {
+    const N: usize = 50;
+
+    let writer = CanSocket::open(VCAN).unwrap();
+    let reader = CanSocket::open(VCAN).unwrap();
+    reader
+        .set_read_timeout(time::Duration::from_millis(100))
+        .unwrap();
+
+    let frames: Vec<CanFrame> = (0..N)
+        .map(|i| CanFrame::from_raw_id(0x100 + i as u32, &[i as u8]).unwrap())
+        .collect();
+
+    let mut n = 0;
+    while n < N {
+        n += writer.write_frames(&frames[n..]).unwrap();
+    }
+
+    for frame in &frames {
+        let rx_frame = reader.read_frame().unwrap();
+        assert_eq!(frame.raw_id(), rx_frame.raw_id());
+        assert_eq!(frame.data(), rx_frame.data());
+    }
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_write_frame_confirm() {
+    let sock = CanSocket::open(VCAN).unwrap();
+    sock.set_write_timeout(time::Duration::from_millis(500))
+        .unwrap();
+
+    let frame = CanFrame::new(StandardId::new(0x123).unwrap(), &[1, 2, 3]).unwrap();
+    for _ in 0..3 {
+        sock.write_frame_confirm(&frame).unwrap();
+    }
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_drain_error_queue() {
+    let sock = CanSocket::open(VCAN).unwrap();
+    let flags = (libc::SOF_TIMESTAMPING_TX_SOFTWARE
+        | libc::SOF_TIMESTAMPING_SOFTWARE
+        | libc::SOF_TIMESTAMPING_OPT_TSONLY) as libc::c_int;
+    sock.set_socket_option(libc::SOL_SOCKET, libc::SO_TIMESTAMPING, &flags)
+        .unwrap();
+
+    let frame = CanFrame::new(StandardId::new(0x123).unwrap(), &[1, 2, 3]).unwrap();
+    sock.write_frame(&frame).unwrap();
+    sock.write_frame(&frame).unwrap();
+    std::thread::sleep(time::Duration::from_millis(50));
+
+    let entries = sock.drain_error_queue().unwrap();
+    assert!(!entries.is_empty());
+    assert!(entries
+        .iter()
+        .all(|e| e.is_tx_timestamp() && e.timestamp.is_some()));
+
+    // The queue is now empty
+    assert!(sock.drain_error_queue().unwrap().is_empty());
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_read_own_echo_timestamp() {
+    let sock = CanSocket::open(VCAN).unwrap();
+    sock.set_recv_own_msgs(true).unwrap();
+    sock.set_timestamp_ns(true).unwrap();
+    sock.set_read_timeout(time::Duration::from_millis(100))
+        .unwrap();
+
+    let frame = CanFrame::new(StandardId::new(0x123).unwrap(), &[1, 2, 3]).unwrap();
+    let start = time::SystemTime::now();
+    sock.write_frame(&frame).unwrap();
+
+    let (echo, ts) = sock.read_own_echo_timestamp().unwrap();
+    assert_eq!(frame.id(), echo.id());
+    assert_eq!(frame.data(), echo.data());
+
+    // The system clock could step, so only sanity-check the round trip.
+    if let Ok(rtt) = ts.duration_since(start) {
+        assert!(rtt < time::Duration::from_secs(1));
+    }
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_for_each_frame() {
+    use std::ops::ControlFlow;
+
+    const N: usize = 5;
+
+    let writer = CanSocket::open(VCAN).unwrap();
+    let reader = CanSocket::open(VCAN).unwrap();
+    reader
+        .set_read_timeout(time::Duration::from_millis(10))
+        .unwrap();
+
+    let handle = std::thread::spawn(move || {
+        // Let the reader hit a few timeouts first
+        std::thread::sleep(time::Duration::from_millis(50));
+        for i in 0..N {
+            let frame = CanFrame::new(StandardId::new(0x100).unwrap(), &[i as u8]).unwrap();
+            writer.write_frame(&frame).unwrap();
+        }
+    });
+
+    let mut data = Vec::new();
+    reader
+        .for_each_frame(|frame| {
+            data.push(frame.data()[0]);
+            if data.len() == N {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .unwrap();
+
+    handle.join().unwrap();
+    assert_eq!(vec![0, 1, 2, 3, 4], data);
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_peek_frame() {
+    let writer = CanSocket::open(VCAN).unwrap();
+    let reader = CanSocket::open(VCAN).unwrap();
+    reader
+        .set_read_timeout(time::Duration::from_millis(100))
+        .unwrap();
+
+    let frame = CanFrame::new(StandardId::new(0x123).unwrap(), &[1, 2, 3]).unwrap();
+    writer.write_frame(&frame).unwrap();
+
+    let peeked = reader.peek_frame().unwrap();
+    assert_eq!(frame.id(), peeked.id());
+    assert_eq!(frame.data(), peeked.data());
+
+    // The peek didn't consume the frame
+    let rx_frame = reader.read_frame().unwrap();
+    assert_eq!(frame.id(), rx_frame.id());
+    assert_eq!(frame.data(), rx_frame.data());
+
+    assert!(reader.read_frame().is_err());
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_local_loopback_enabled() {
+    let sock = CanSocket::open(VCAN).unwrap();
+
+    // Loopback is on by default
+    assert!(sock.local_loopback_enabled().unwrap());
+
+    sock.set_loopback(false).unwrap();
+    assert!(!sock.local_loopback_enabled().unwrap());
+
+    sock.set_loopback(true).unwrap();
+    assert!(sock.local_loopback_enabled().unwrap());
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_option_getters() {
+    let sock = CanSocket::open(VCAN).unwrap();
+
+    for on in [true, false] {
+        sock.set_loopback(on).unwrap();
+        assert_eq!(on, sock.loopback().unwrap());
+
+        sock.set_recv_own_msgs(on).unwrap();
+        assert_eq!(on, sock.recv_own_msgs().unwrap());
+
+        sock.set_join_filters(on).unwrap();
+        assert_eq!(on, sock.join_filters().unwrap());
+    }
+
+    for mask in [ERR_MASK_ALL, ERR_MASK_NONE, libc::CAN_ERR_BUSOFF] {
+        sock.set_error_filter(mask).unwrap();
+        assert_eq!(mask, sock.error_filter().unwrap());
+    }
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_read_into_ring() {
+    let writer = CanSocket::open(VCAN).unwrap();
+    let reader = CanSocket::open(VCAN).unwrap();
+    let ring = FrameRing::new(2);
+
+    // Nothing waiting, so this returns immediately
+    assert_eq!(0, reader.read_into_ring(&ring).unwrap());
+
+    for i in 0..3u8 {
+        let frame = CanFrame::new(StandardId::new(0x123).unwrap(), &[i]).unwrap();
+        writer.write_frame(&frame).unwrap();
+    }
+    std::thread::sleep(time::Duration::from_millis(10));
+
+    // The ring fills up, leaving the last frame on the socket
+    assert_eq!(2, reader.read_into_ring(&ring).unwrap());
+    assert_eq!(&[0], ring.pop().unwrap().data());
+    assert_eq!(&[1], ring.pop().unwrap().data());
+
+    assert_eq!(1, reader.read_into_ring(&ring).unwrap());
+    assert_eq!(&[2], ring.pop().unwrap().data());
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_socket_builder() {
+    let sock: CanSocket = SocketBuilder::new()
+        .interface(VCAN)
+        .nonblocking(true)
+        .filters(&[CanFilter::new(0x100, 0x7FF)])
+        .error_mask(ERR_MASK_ALL)
+        .loopback(false)
+        .recv_own(false)
+        .open()
+        .unwrap();
+
+    assert!(sock.nonblocking().unwrap());
+    assert_eq!(ERR_MASK_ALL, sock.error_filter().unwrap());
+    assert!(!sock.loopback().unwrap());
+    assert!(!sock.recv_own_msgs().unwrap());
+
+    let sock = SocketBuilder::new()
+        .interface(VCAN)
+        .recv_own(true)
+        .open_fd()
+        .unwrap();
+
+    assert!(!sock.nonblocking().unwrap());
+    assert!(sock.recv_own_msgs().unwrap());
+
+    assert!(SocketBuilder::new()
+        .interface("invalid")
+        .open::<CanSocket>()
+        .is_err());
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_readonly_writeonly() {
+    use std::io::ErrorKind;
+
+    let reader = CanSocket::open_readonly(VCAN).unwrap();
+    let writer = CanSocket::open_writeonly(VCAN).unwrap();
+    reader
+        .set_read_timeout(time::Duration::from_millis(100))
+        .unwrap();
+
+    let frame = CanFrame::new(StandardId::new(0x123).unwrap(), &[1, 2, 3]).unwrap();
+
+    let err = reader.write_frame(&frame).unwrap_err();
+    assert_eq!(ErrorKind::PermissionDenied, err.kind());
+    assert!(reader.write_frames(&[frame]).is_err());
+
+    let err = writer.read_frame().unwrap_err();
+    assert_eq!(ErrorKind::PermissionDenied, err.kind());
+
+    writer.write_frame(&frame).unwrap();
+    let rx_frame = reader.read_frame().unwrap();
+    assert_eq!(frame.id(), rx_frame.id());
+    assert_eq!(frame.data(), rx_frame.data());
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_read_frame_interruptible() {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+    };
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    let reader = {
+        let cancel = Arc::clone(&cancel);
+        thread::spawn(move || {
+            let sock = CanSocket::open(VCAN).unwrap();
+            sock.set_filter_drop_all().unwrap();
+            sock.read_frame_interruptible(&cancel)
+        })
+    };
+
+    thread::sleep(time::Duration::from_millis(100));
+    let start = time::Instant::now();
+    cancel.store(true, Ordering::Release);
+
+    let res = reader.join().unwrap();
+    assert!(res.unwrap().is_none());
+    assert!(start.elapsed() < time::Duration::from_millis(500));
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_kernel_supports() {
+    // Raw sockets are always available when the vcan tests can run
+    assert!(kernel_supports(KernelFeature::Raw));
+    assert!(kernel_supports(KernelFeature::RawFd));
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_read_frame_ts() {
+    let tx = CanSocket::open(VCAN).unwrap();
+    let rx = CanSocket::open(VCAN).unwrap();
+    rx.set_read_timeout(time::Duration::from_millis(100))
+        .unwrap();
+
+    // Without timestamping enabled, frames arrive without one
+    let frame = CanFrame::new(StandardId::new(0x123).unwrap(), &[1, 2, 3]).unwrap();
+    tx.write_frame(&frame).unwrap();
+    let (rx_frame, ts) = rx.read_frame_ts().unwrap();
+    assert_eq!(frame.data(), rx_frame.data());
+    assert!(ts.is_none());
+
+    rx.set_timestamp_ns(true).unwrap();
+    let before = time::SystemTime::now();
+    tx.write_frame(&frame).unwrap();
+    let (rx_frame, ts) = rx.read_frame_ts().unwrap();
+    assert_eq!(frame.raw_id(), rx_frame.raw_id());
+    assert!(ts.unwrap() + time::Duration::from_millis(10) >= before);
+}
+
+#[test]
+#[cfg(all(feature = "vcan_tests", feature = "dump"))]
+fn vcan_replay_dump() {
+    use socketcan::dump::{self, Reader};
+
+    let log: &[u8] = b"(1000.000000) vcan0 123#0102\n\
+                       (1000.050000) vcan0 124##1AABB\n\
+                       (1000.100000) vcan0 125#R\n";
+
+    let tx = CanSocket::open(VCAN).unwrap();
+    let rx = CanSocket::open(VCAN).unwrap();
+    rx.set_read_timeout(time::Duration::from_millis(100))
+        .unwrap();
+
+    // At double speed, the 100ms log takes 50ms; the FD frame is skipped
+    let start = time::Instant::now();
+    let n = dump::replay(Reader::from_reader(log), &tx, 2.0).unwrap();
+    assert_eq!(2, n);
+    assert!(start.elapsed() >= time::Duration::from_millis(50));
+
+    let frame = rx.read_frame().unwrap();
+    assert_eq!(0x123, frame.raw_id());
+    assert_eq!(&[1, 2], frame.data());
+
+    let frame = rx.read_frame().unwrap();
+    assert_eq!(0x125, frame.raw_id());
+    assert!(frame.is_remote_frame());
+
+    assert!(dump::replay(Reader::from_reader(log), &tx, 0.0).is_err());
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_read_available() {
+    let tx = CanSocket::open(VCAN).unwrap();
+    let rx = CanSocket::open(VCAN).unwrap();
+
+    assert!(rx.read_available().unwrap().is_empty());
+
+    for i in 0..3u8 {
+        let frame = CanFrame::new(StandardId::new(0x100).unwrap(), &[i]).unwrap();
+        tx.write_frame(&frame).unwrap();
+    }
+    std::thread::sleep(time::Duration::from_millis(10));
+
+    let frames = rx.read_available().unwrap();
+    assert_eq!(3, frames.len());
+    for (i, frame) in frames.iter().enumerate() {
+        assert_eq!(&[i as u8], frame.data());
+    }
+
+    // The socket is still in blocking mode
+    assert!(!rx.nonblocking().unwrap());
+    assert!(rx.read_available().unwrap().is_empty());
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_fd_read_frame_meta() {
+    let tx = CanFdSocket::open(VCAN).unwrap();
+    let rx = CanFdSocket::open(VCAN).unwrap();
+    tx.set_recv_own_msgs(true).unwrap();
+    tx.set_read_timeout(time::Duration::from_millis(100))
+        .unwrap();
+    rx.set_read_timeout(time::Duration::from_millis(100))
+        .unwrap();
+
+    let frame = CanFdFrame::new(StandardId::new(0x123).unwrap(), &[0xAA; 32]).unwrap();
+    tx.write_frame(&frame).unwrap();
+
+    let (rx_frame, flags) = rx.read_frame_meta().unwrap();
+    assert_eq!(frame.data(), rx_frame.data());
+    assert!(!flags.contains(MsgFlags::TRUNC));
+    assert!(!flags.contains(MsgFlags::CONFIRM));
+
+    // The echo of our own frame is marked as confirmed
+    let (_, flags) = tx.read_frame_meta().unwrap();
+    assert!(!flags.contains(MsgFlags::TRUNC));
+    assert!(flags.contains(MsgFlags::CONFIRM));
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_paced_socket() {
+    let gap = time::Duration::from_millis(20);
+    let tx = PacedCanSocket::new(CanSocket::open(VCAN).unwrap(), gap);
+    let rx = CanSocket::open(VCAN).unwrap();
+    rx.set_read_timeout(time::Duration::from_millis(100))
+        .unwrap();
+    rx.set_timestamp_ns(true).unwrap();
+
+    let start = time::Instant::now();
+    let frame = CanFrame::new(StandardId::new(0x123).unwrap(), &[1]).unwrap();
+    tx.write_frame(&frame).unwrap();
+    tx.write_frame(&frame).unwrap();
+    assert!(start.elapsed() >= gap);
+
+    // The frames arrived at least the gap apart
+    let (_, ts1) = rx.read_frame_with_timestamp().unwrap();
+    let (_, ts2) = rx.read_frame_with_timestamp().unwrap();
+    assert!(ts2.duration_since(ts1).unwrap() >= gap);
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_transaction() {
+    let client = CanSocket::open(VCAN).unwrap();
+    let server = CanSocket::open(VCAN).unwrap();
+    server.set_filters(&[CanFilter::new(0x7E0, 0x7FF)]).unwrap();
+
+    let responder = std::thread::spawn(move || {
+        let req = server.read_frame().unwrap();
+
+        // Some unrelated traffic before the response
+        let other = CanFrame::new(StandardId::new(0x100).unwrap(), &[0xFF]).unwrap();
+        server.write_frame(&other).unwrap();
+
+        let resp = CanFrame::new(StandardId::new(0x7E8).unwrap(), &[req.data()[0] + 0x40]).unwrap();
+        server.write_frame(&resp).unwrap();
+    });
+
+    let req = CanFrame::new(StandardId::new(0x7E0).unwrap(), &[0x10]).unwrap();
+    let resp = client
+        .transaction(
+            &req,
+            CanFilter::new(0x7E8, 0x7FF),
+            time::Duration::from_millis(500),
+        )
+        .unwrap();
+    responder.join().unwrap();
+
+    assert_eq!(0x7E8, resp.raw_id());
+    assert_eq!(&[0x50], resp.data());
+
+    // Nobody answers this time
+    let err = client
+        .transaction(
+            &req,
+            CanFilter::new(0x7E8, 0x7FF),
+            time::Duration::from_millis(50),
+        )
+        .unwrap_err();
+    assert_eq!(std::io::ErrorKind::TimedOut, err.kind());
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_fd_read_frame_type() {
+    let writer = CanFdSocket::open(VCAN).unwrap();
+    let reader = CanFdSocket::open(VCAN).unwrap();
+    reader
+        .set_read_timeout(time::Duration::from_millis(100))
+        .unwrap();
+
+    let id = StandardId::new(0x123).unwrap();
+
+    // A classic frame is read back as a classic frame, and an FD frame as
+    // an FD frame, even with the same ID and payload.
+    let frame = CanFrame::new(id, &[1, 2, 3]).unwrap();
+    writer.write_frame(&frame).unwrap();
+    let fdframe = CanFdFrame::new(id, &[1, 2, 3]).unwrap();
+    writer.write_frame(&fdframe).unwrap();
+
+    match reader.read_frame().unwrap() {
+        CanAnyFrame::Normal(rx_frame) => assert_eq!(frame.data(), rx_frame.data()),
+        rx_frame => panic!("Expected a classic frame, got {:?}", rx_frame),
+    }
+    match reader.read_frame().unwrap() {
+        CanAnyFrame::Fd(rx_frame) => assert_eq!(fdframe.data(), rx_frame.data()),
+        rx_frame => panic!("Expected an FD frame, got {:?}", rx_frame),
+    }
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_fd_interface_mtu() {
+    // The test interface is configured for CAN FD
+    let sock = CanFdSocket::open(VCAN).unwrap();
+    assert_eq!(libc::CANFD_MTU as u32, sock.interface_mtu().unwrap());
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_fd_frame_kind_filter() {
+    let writer = CanFdSocket::open(VCAN).unwrap();
+    let mut reader = CanFdSocket::open(VCAN).unwrap();
+    reader
+        .set_read_timeout(time::Duration::from_millis(100))
+        .unwrap();
+
+    let id = StandardId::new(0x123).unwrap();
+    let frame = CanFrame::new(id, &[1, 2, 3]).unwrap();
+    let fdframe = CanFdFrame::new(id, &[4, 5, 6]).unwrap();
+
+    // The classic frame is skipped
+    reader.set_frame_kind_filter(FrameKind::Fd);
+    writer.write_frame(&frame).unwrap();
+    writer.write_frame(&fdframe).unwrap();
+
+    match reader.read_frame().unwrap() {
+        CanAnyFrame::Fd(rx_frame) => assert_eq!(fdframe.data(), rx_frame.data()),
+        rx_frame => panic!("Expected an FD frame, got {:?}", rx_frame),
+    }
+
+    // The FD frame is skipped
+    reader.set_frame_kind_filter(FrameKind::Classic);
+    writer.write_frame(&fdframe).unwrap();
+    writer.write_frame(&frame).unwrap();
+
+    match reader.read_frame().unwrap() {
+        CanAnyFrame::Normal(rx_frame) => assert_eq!(frame.data(), rx_frame.data()),
+        rx_frame => panic!("Expected a classic frame, got {:?}", rx_frame),
+    }
+    assert!(reader.read_frame().is_err());
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_read_frame_with_timestamp() {
+    let tx = CanSocket::open(VCAN).unwrap();
+    let rx = CanSocket::open(VCAN).unwrap();
+    rx.set_read_timeout(time::Duration::from_millis(100))
+        .unwrap();
+    rx.set_timestamp_ns(true).unwrap();
+
+    let before = time::SystemTime::now();
+    let frame = CanFrame::new(StandardId::new(0x123).unwrap(), &[1, 2, 3]).unwrap();
+    tx.write_frame(&frame).unwrap();
+
+    let (rx_frame, ts) = rx.read_frame_with_timestamp().unwrap();
+    assert_eq!(frame.raw_id(), rx_frame.raw_id());
+    assert_eq!(frame.data(), rx_frame.data());
+
+    // The timestamp should be within a short time of sending the frame
+    let after = time::SystemTime::now();
+    let slack = time::Duration::from_millis(10);
+    assert!(ts + slack >= before);
+    assert!(ts <= after + slack);
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_write_frame_dontwait() {
+    let sock = CanSocket::open(VCAN).unwrap();
+    assert!(!sock.nonblocking().unwrap());
+    sock.as_raw_socket().set_send_buffer_size(0).unwrap();
+
+    let frame = CanFrame::new(StandardId::new(0x123).unwrap(), &[1, 2, 3]).unwrap();
+    sock.write_frame_flags(&frame, SendFlags::DONTWAIT).unwrap();
+
+    // Flood the socket until the queue fills. It never blocks, but fails
+    // with WouldBlock, even though the socket is in blocking mode.
+    let mut err = None;
+    for _ in 0..100_000 {
+        if let Err(e) = sock.write_frame_flags(&frame, SendFlags::DONTWAIT) {
+            err = Some(e);
+            break;
+        }
+    }
+    let err = err.expect("the send queue never filled");
+    assert_eq!(std::io::ErrorKind::WouldBlock, err.kind());
+    assert!(!sock.nonblocking().unwrap());
+}
+
+#[test]
+#[cfg(feature = "vcan_tests")]
+fn vcan_write_frame_at() {
+    let tx = CanSocket::open(VCAN).unwrap();
+    let rx = CanSocket::open(VCAN).unwrap();
+    rx.set_read_timeout(time::Duration::from_millis(500))
+        .unwrap();
+
+    // Without an ETF qdisc on the vcan, the frame just goes out right away
+    let txtime = time::SystemTime::now() + time::Duration::from_millis(10);
+    let frame = CanFrame::new(StandardId::new(0x123).unwrap(), &[1, 2, 3]).unwrap();
+    tx.write_frame_at(&frame, txtime).unwrap();
+
+    let rx_frame = rx.read_frame().unwrap();
+    assert_eq!(frame.raw_id(), rx_frame.raw_id());
+    assert_eq!(frame.data(), rx_frame.data());
+}
+
 /*
 #[test]
 #[cfg(feature = "vcan_tests")]
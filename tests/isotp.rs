@@ -0,0 +1,40 @@
+// socketcan/tests/isotp.rs
+//
+// Integration tests for ISO-TP sockets.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+#[cfg(all(feature = "vcan_tests", feature = "isotp"))]
+use socketcan::{CanIsoTpSocket, ProtocolSocket, StandardId};
+
+#[cfg(all(feature = "vcan_tests", feature = "isotp"))]
+use std::time;
+
+// The virtual CAN interface to use for tests.
+#[cfg(all(feature = "vcan_tests", feature = "isotp"))]
+const VCAN: &str = "vcan0";
+
+#[test]
+#[cfg(all(feature = "vcan_tests", feature = "isotp"))]
+fn vcan_isotp_long_payload() {
+    let id_a = StandardId::new(0x7E0).unwrap();
+    let id_b = StandardId::new(0x7E8).unwrap();
+
+    let sock_a = CanIsoTpSocket::open(VCAN, id_b, id_a).unwrap();
+    let sock_b = CanIsoTpSocket::open(VCAN, id_a, id_b).unwrap();
+
+    sock_b
+        .set_read_timeout(time::Duration::from_millis(500))
+        .unwrap();
+
+    // Much more than fits in a single frame
+    let payload: Vec<u8> = (0..100).collect();
+    sock_a.write_payload(&payload).unwrap();
+
+    assert_eq!(payload, sock_b.read_payload().unwrap());
+}
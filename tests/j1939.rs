@@ -0,0 +1,47 @@
+// socketcan/tests/j1939.rs
+//
+// Integration tests for J1939 sockets.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+#[cfg(all(feature = "vcan_tests", feature = "j1939"))]
+use socketcan::{
+    j1939::{J1939_NO_NAME, J1939_NO_PGN},
+    CanAddr, J1939Socket, ProtocolSocket,
+};
+
+#[cfg(all(feature = "vcan_tests", feature = "j1939"))]
+use std::time;
+
+// The virtual CAN interface to use for tests.
+#[cfg(all(feature = "vcan_tests", feature = "j1939"))]
+const VCAN: &str = "vcan0";
+
+#[test]
+#[cfg(all(feature = "vcan_tests", feature = "j1939"))]
+fn vcan_j1939_multi_packet() {
+    const SRC_ADDR: u8 = 0x10;
+    const DST_ADDR: u8 = 0x20;
+    // Proprietary A, a destination-specific PGN
+    const PGN: u32 = 0xEF00;
+
+    let tx = J1939Socket::open(VCAN, J1939_NO_NAME, J1939_NO_PGN, SRC_ADDR).unwrap();
+    let rx = J1939Socket::open(VCAN, J1939_NO_NAME, J1939_NO_PGN, DST_ADDR).unwrap();
+
+    rx.set_read_timeout(time::Duration::from_secs(2)).unwrap();
+
+    // Too big for a single frame, so it uses the transport protocol
+    let data: Vec<u8> = (0..100).collect();
+    let dest = CanAddr::from_iface_j1939(VCAN, J1939_NO_NAME, PGN, DST_ADDR).unwrap();
+    tx.send_to(&data, &dest).unwrap();
+
+    let (rx_data, src) = rx.recv_msg().unwrap();
+    assert_eq!(data, rx_data);
+    assert_eq!(PGN, src.j1939_pgn());
+    assert_eq!(SRC_ADDR, src.j1939_addr());
+}